@@ -43,7 +43,10 @@ fn benchmark_request_validation(c: &mut Criterion) {
         stream: true,
         top_p: Some(0.9),
         top_k: Some(40),
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     c.bench_function("validate_claude_request", |b| {
@@ -77,7 +80,10 @@ fn benchmark_request_transformation(c: &mut Criterion) {
         stream: true,
         top_p: Some(0.9),
         top_k: Some(40),
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     c.bench_function("transform_request", |b| {
@@ -101,7 +107,10 @@ fn benchmark_json_serialization(c: &mut Criterion) {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     let gemini_req = transform_request(req).unwrap();
@@ -168,8 +177,11 @@ fn benchmark_sse_generation(c: &mut Criterion) {
                 prompt_token_count: Some(10),
                 candidates_token_count: None,
                 total_token_count: None,
+                thoughts_token_count: None,
+                cached_content_token_count: None,
             }),
             prompt_feedback: None,
+            error: None,
         },
         GeminiStreamChunk {
             candidates: vec![Candidate {
@@ -185,6 +197,7 @@ fn benchmark_sse_generation(c: &mut Criterion) {
             }],
             usage_metadata: None,
             prompt_feedback: None,
+            error: None,
         },
         GeminiStreamChunk {
             candidates: vec![Candidate {
@@ -202,8 +215,11 @@ fn benchmark_sse_generation(c: &mut Criterion) {
                 prompt_token_count: None,
                 candidates_token_count: Some(5),
                 total_token_count: Some(15),
+                thoughts_token_count: None,
+                cached_content_token_count: None,
             }),
             prompt_feedback: None,
+            error: None,
         },
     ];
 
@@ -231,7 +247,10 @@ fn benchmark_end_to_end(c: &mut Criterion) {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     let gemini_stream = br#"[{"candidates":[{"content":{"parts":[{"text":"Hello!"}],"role":"model"}}],"usageMetadata":{"promptTokenCount":5}},{"candidates":[{"finishReason":"STOP"}]}]"#;
@@ -18,7 +18,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     println!("Original Claude Request:");
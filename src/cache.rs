@@ -1,56 +1,135 @@
 use arc_swap::ArcSwap;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 
+use crate::models::claude::ClaudeTool;
 use crate::models::gemini::GeminiFunctionDeclaration;
 
+/// Default maximum number of cached translations
+pub const DEFAULT_CACHE_CAPACITY: usize = 512;
+
+/// SHA-256 of a tool's name, description and full input schema
+type ToolKey = [u8; 32];
+
 /// Cache for transformed tool schemas to avoid repeated conversion
 ///
 /// Uses ArcSwap for efficient lock-free reads with infrequent writes.
 /// Perfect for tool schemas which are defined once and reused many times.
+///
+/// Entries are keyed by a hash of the whole tool, so a tool whose schema or
+/// description changes mid-session is translated again. When the cache is
+/// full, the least recently used entry is evicted.
 #[derive(Clone)]
 pub struct ToolSchemaCache {
-    /// Cache: tool_name -> transformed Gemini function declaration
-    cache: Arc<ArcSwap<HashMap<String, GeminiFunctionDeclaration>>>,
+    /// Cache: tool content hash -> transformed Gemini function declaration
+    cache: Arc<ArcSwap<HashMap<ToolKey, Arc<CachedTool>>>>,
+    capacity: usize,
+    counters: Arc<CacheCounters>,
+}
+
+struct CachedTool {
+    declaration: GeminiFunctionDeclaration,
+    /// Value of `CacheCounters::clock` at the last access
+    last_used: AtomicU64,
+}
+
+#[derive(Default)]
+struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    /// Logical clock for LRU ordering
+    clock: AtomicU64,
+}
+
+impl CacheCounters {
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
 }
 
 impl ToolSchemaCache {
     pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Create a cache holding at most `capacity` translations
+    pub fn with_capacity(capacity: usize) -> Self {
         Self {
             cache: Arc::new(ArcSwap::from_pointee(HashMap::new())),
+            capacity: capacity.max(1),
+            counters: Arc::new(CacheCounters::default()),
         }
     }
 
     /// Get a cached schema or transform and cache it
     pub fn get_or_transform(
         &self,
-        tool: &crate::models::claude::ClaudeTool,
+        tool: &ClaudeTool,
     ) -> crate::error::Result<GeminiFunctionDeclaration> {
+        let key = tool_key(tool);
+
         // Fast path: check cache first (lock-free read)
         {
             let cache = self.cache.load();
-            if let Some(cached) = cache.get(&tool.name) {
+            if let Some(cached) = cache.get(&key) {
                 tracing::debug!(tool_name = %tool.name, "Cache hit for tool schema");
-                return Ok(cached.clone());
+                self.counters.hits.fetch_add(1, Ordering::Relaxed);
+                cached
+                    .last_used
+                    .store(self.counters.tick(), Ordering::Relaxed);
+                return Ok(cached.declaration.clone());
             }
         }
 
         // Slow path: transform and cache
         tracing::debug!(tool_name = %tool.name, "Cache miss, transforming tool schema");
+        self.counters.misses.fetch_add(1, Ordering::Relaxed);
+
+        let lowered = crate::transform::schema::lower_schema(&tool.input_schema);
+        if !lowered.lost.is_empty() {
+            tracing::warn!(
+                tool_name = %tool.name,
+                lost = %lowered.lost.join("; "),
+                "Tool schema uses constraints Gemini does not support; they were dropped"
+            );
+        }
 
         let transformed = GeminiFunctionDeclaration {
             name: tool.name.clone(),
             description: tool.description.clone(),
-            parameters: tool.input_schema.clone(),
+            parameters: lowered.schema,
         };
+        let entry = Arc::new(CachedTool {
+            declaration: transformed.clone(),
+            last_used: AtomicU64::new(self.counters.tick()),
+        });
 
-        // Update cache atomically
+        // Update cache atomically; the closure may rerun, so only the last run's eviction counts
+        let mut evicted = false;
         self.cache.rcu(|current| {
             let mut new_cache = (**current).clone();
-            new_cache.insert(tool.name.clone(), transformed.clone());
+            evicted = false;
+            if !new_cache.contains_key(&key) && new_cache.len() >= self.capacity {
+                let oldest = new_cache
+                    .iter()
+                    .min_by_key(|(_, cached)| cached.last_used.load(Ordering::Relaxed))
+                    .map(|(key, _)| *key);
+                if let Some(oldest) = oldest {
+                    new_cache.remove(&oldest);
+                    evicted = true;
+                }
+            }
+            new_cache.insert(key, entry.clone());
             new_cache
         });
 
+        if evicted {
+            self.counters.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+
         Ok(transformed)
     }
 
@@ -64,9 +143,12 @@ impl ToolSchemaCache {
         self.cache.load().is_empty()
     }
 
-    /// Clear the cache
+    /// Clear the cache and its counters
     pub fn clear(&self) {
         self.cache.store(Arc::new(HashMap::new()));
+        self.counters.hits.store(0, Ordering::Relaxed);
+        self.counters.misses.store(0, Ordering::Relaxed);
+        self.counters.evictions.store(0, Ordering::Relaxed);
     }
 
     /// Get cache statistics
@@ -74,7 +156,14 @@ impl ToolSchemaCache {
         let cache = self.cache.load();
         CacheStats {
             total_entries: cache.len(),
-            tools: cache.keys().cloned().collect(),
+            capacity: self.capacity,
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            evictions: self.counters.evictions.load(Ordering::Relaxed),
+            tools: cache
+                .values()
+                .map(|cached| cached.declaration.name.clone())
+                .collect(),
         }
     }
 }
@@ -85,13 +174,42 @@ impl Default for ToolSchemaCache {
     }
 }
 
+/// Hash everything that affects the translation
+fn tool_key(tool: &ClaudeTool) -> ToolKey {
+    let mut hasher = Sha256::new();
+    hasher.update(tool.name.as_bytes());
+    hasher.update([0]);
+    hasher.update(tool.description.as_bytes());
+    hasher.update([0]);
+    hasher.update(tool.input_schema.to_json_schema().to_string().as_bytes());
+    hasher.finalize().into()
+}
+
 /// Cache statistics
 #[derive(Debug, Clone)]
 pub struct CacheStats {
     pub total_entries: usize,
+    pub capacity: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped because the cache was full
+    pub evictions: u64,
+    /// Names of cached tools; a name appears once per cached version
     pub tools: Vec<String>,
 }
 
+impl CacheStats {
+    /// Share of lookups served from the cache, as a percentage
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total > 0 {
+            (self.hits as f64 / total as f64) * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
 lazy_static::lazy_static! {
     /// Global tool schema cache
     pub static ref TOOL_CACHE: ToolSchemaCache = ToolSchemaCache::new();
@@ -122,6 +240,7 @@ mod tests {
                 properties: Some(properties),
                 ..Default::default()
             },
+            cache_control: None,
         }
     }
 
@@ -165,6 +284,59 @@ mod tests {
         assert!(stats.tools.contains(&"tool_3".to_string()));
     }
 
+    #[test]
+    fn test_changed_schema_is_retranslated() {
+        let cache = ToolSchemaCache::new();
+        let tool = make_test_tool("mcp_tool");
+        cache.get_or_transform(&tool).unwrap();
+
+        let mut changed = tool.clone();
+        changed.input_schema.required = Some(vec!["param".to_string()]);
+        let result = cache.get_or_transform(&changed).unwrap();
+        assert_eq!(result.parameters.required, Some(vec!["param".to_string()]));
+
+        let mut redescribed = tool.clone();
+        redescribed.description = "Updated description".to_string();
+        let result = cache.get_or_transform(&redescribed).unwrap();
+        assert_eq!(result.description, "Updated description");
+
+        // Keywords Gemini never sees still change the key
+        let mut extended = tool.clone();
+        extended
+            .input_schema
+            .additional
+            .insert("additionalProperties".to_string(), serde_json::json!(false));
+        cache.get_or_transform(&extended).unwrap();
+
+        let stats = cache.stats();
+        assert_eq!(stats.total_entries, 4);
+        assert_eq!(stats.misses, 4);
+        assert_eq!(stats.hits, 0);
+    }
+
+    #[test]
+    fn test_capacity_evicts_least_recently_used() {
+        let cache = ToolSchemaCache::with_capacity(2);
+        let (a, b, c) = (
+            make_test_tool("tool_a"),
+            make_test_tool("tool_b"),
+            make_test_tool("tool_c"),
+        );
+
+        cache.get_or_transform(&a).unwrap();
+        cache.get_or_transform(&b).unwrap();
+        cache.get_or_transform(&a).unwrap(); // a is now more recent than b
+        cache.get_or_transform(&c).unwrap(); // evicts b
+
+        let stats = cache.stats();
+        assert_eq!(stats.total_entries, 2);
+        assert_eq!(stats.evictions, 1);
+        assert!(stats.tools.contains(&"tool_a".to_string()));
+        assert!(!stats.tools.contains(&"tool_b".to_string()));
+        assert_eq!((stats.hits, stats.misses), (1, 3));
+        assert_eq!(stats.hit_rate(), 25.0);
+    }
+
     #[test]
     fn test_clear() {
         let cache = ToolSchemaCache::new();
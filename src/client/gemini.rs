@@ -4,9 +4,13 @@ use std::fs::OpenOptions;
 use std::io::Write;
 use tracing::info;
 
+use super::{RetryPolicy, Timeouts};
 use crate::config::GeminiConfig;
 use crate::error::{ProxyError, Result};
-use crate::provider::{Provider, ProviderStream, StreamFuture};
+use crate::models::gemini::CachedContent;
+use crate::provider::{
+    CachedContentFuture, CountTokensFuture, Provider, ProviderStream, StreamFuture, WireFormat,
+};
 
 pub struct GeminiClient {
     client: Client,
@@ -16,7 +20,7 @@ pub struct GeminiClient {
 impl GeminiClient {
     pub fn new(config: GeminiConfig) -> Result<Self> {
         let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(120))
+            .connect_timeout(config.timeouts.connect)
             .build()
             .map_err(|e| {
                 ProxyError::InternalError(format!("Failed to create HTTP client: {}", e))
@@ -35,14 +39,44 @@ impl Provider for GeminiClient {
         let body = body.clone();
         let client = self.client.clone();
         let api_key = self.config.api_key.clone();
+        let retry = self.config.retry;
+        let timeouts = self.config.timeouts;
 
-        Box::pin(
-            async move { Self::stream_generate_content_impl(url, body, client, api_key).await },
-        )
+        Box::pin(async move {
+            Self::stream_generate_content_impl(url, body, client, api_key, retry, timeouts).await
+        })
     }
 
-    fn needs_transformation(&self) -> bool {
-        true // Gemini needs Claude->Gemini transformation
+    fn count_tokens(&self, model: &str, body: Bytes) -> CountTokensFuture {
+        let url = format!(
+            "https://{}/v1beta/models/{}:countTokens",
+            self.config.endpoint, model
+        );
+        let model = format!("models/{}", model);
+        let client = self.client.clone();
+        let api_key = self.config.api_key.clone();
+        let retry = self.config.retry;
+        let timeouts = self.config.timeouts;
+
+        Box::pin(async move {
+            Self::count_tokens_impl(url, model, body, client, api_key, retry, timeouts).await
+        })
+    }
+
+    fn create_cached_content(&self, body: Bytes) -> CachedContentFuture {
+        let url = format!("https://{}/v1beta/cachedContents", self.config.endpoint);
+        let client = self.client.clone();
+        let api_key = self.config.api_key.clone();
+        let retry = self.config.retry;
+        let timeouts = self.config.timeouts;
+
+        Box::pin(async move {
+            Self::create_cached_content_impl(url, body, client, api_key, retry, timeouts).await
+        })
+    }
+
+    fn wire_format(&self) -> WireFormat {
+        WireFormat::Gemini // Gemini needs Claude->Gemini transformation
     }
 
     fn name(&self) -> &str {
@@ -56,6 +90,8 @@ impl GeminiClient {
         body: Bytes,
         client: Client,
         api_key: String,
+        retry: RetryPolicy,
+        timeouts: Timeouts,
     ) -> Result<ProviderStream> {
         info!(
             "Gemini: Sending {} bytes to: {}",
@@ -73,30 +109,121 @@ impl GeminiClient {
             let _ = writeln!(file, "{}", String::from_utf8_lossy(&body));
         }
 
-        let response = client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .header("Content-Length", body.len())
-            .header("x-goog-api-key", &api_key)
-            .body(body)
-            .send()
-            .await
-            .map_err(|e| ProxyError::UpstreamError(format!("Gemini request failed: {}", e)))?;
+        let response = retry
+            .send("Gemini", timeouts.first_byte, || {
+                client
+                    .post(&url)
+                    .header("Content-Type", "application/json")
+                    .header("Content-Length", body.len())
+                    .header("x-goog-api-key", &api_key)
+                    .body(body.clone())
+            })
+            .await?;
 
         let status = response.status();
         info!("Gemini responded with status: {}", status);
 
         if !status.is_success() {
-            let error_body = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(ProxyError::UpstreamError(format!(
-                "Gemini API error {}: {}",
-                status, error_body
-            )));
+            let error_body = timeouts.error_body("Gemini", response).await?;
+            return Err(ProxyError::upstream_status(
+                "Gemini",
+                status.as_u16(),
+                &error_body,
+            ));
+        }
+
+        Ok(timeouts.stream("Gemini", response))
+    }
+
+    /// Create a `cachedContents` resource holding a prompt prefix
+    async fn create_cached_content_impl(
+        url: String,
+        body: Bytes,
+        client: Client,
+        api_key: String,
+        retry: RetryPolicy,
+        timeouts: Timeouts,
+    ) -> Result<CachedContent> {
+        info!("Gemini: Creating cached content ({} bytes)", body.len());
+
+        let response = retry
+            .send("Gemini", timeouts.first_byte, || {
+                client
+                    .post(&url)
+                    .header("Content-Type", "application/json")
+                    .header("x-goog-api-key", &api_key)
+                    .body(body.clone())
+            })
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_body = timeouts.error_body("Gemini", response).await?;
+            return Err(ProxyError::upstream_status(
+                "Gemini",
+                status.as_u16(),
+                &error_body,
+            ));
         }
 
-        Ok(Box::pin(response.bytes_stream()))
+        let bytes = timeouts.body("Gemini", response).await?;
+        serde_json::from_slice(&bytes).map_err(|e| {
+            ProxyError::InvalidGeminiResponse(format!("Invalid cachedContents response: {}", e))
+        })
+    }
+
+    /// Call Gemini's countTokens with the transformed request
+    ///
+    /// countTokens only accepts `contents` at the top level, so the full request
+    /// (system instruction and tools included) is wrapped in `generateContentRequest`.
+    async fn count_tokens_impl(
+        url: String,
+        model: String,
+        body: Bytes,
+        client: Client,
+        api_key: String,
+        retry: RetryPolicy,
+        timeouts: Timeouts,
+    ) -> Result<u32> {
+        let mut generate_request: serde_json::Value = serde_json::from_slice(&body)?;
+        generate_request["model"] = serde_json::Value::String(model);
+        let payload = serde_json::json!({ "generateContentRequest": generate_request }).to_string();
+
+        info!("Gemini: Counting tokens at: {}", url);
+
+        let response = retry
+            .send("Gemini", timeouts.first_byte, || {
+                client
+                    .post(&url)
+                    .header("Content-Type", "application/json")
+                    .header("x-goog-api-key", &api_key)
+                    .body(payload.clone())
+            })
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_body = timeouts.error_body("Gemini", response).await?;
+            return Err(ProxyError::upstream_status(
+                "Gemini",
+                status.as_u16(),
+                &error_body,
+            ));
+        }
+
+        let bytes = timeouts.body("Gemini", response).await?;
+        let result: serde_json::Value = serde_json::from_slice(&bytes).map_err(|e| {
+            ProxyError::InvalidGeminiResponse(format!("Invalid countTokens response: {}", e))
+        })?;
+
+        result["totalTokens"]
+            .as_u64()
+            .map(|n| n as u32)
+            .ok_or_else(|| {
+                ProxyError::InvalidGeminiResponse(format!(
+                    "countTokens response missing totalTokens: {}",
+                    result
+                ))
+            })
     }
 }
@@ -4,9 +4,10 @@ use std::fs::OpenOptions;
 use std::io::Write;
 use tracing::info;
 
+use super::{RetryPolicy, Timeouts};
 use crate::config::KimiConfig;
 use crate::error::{ProxyError, Result};
-use crate::provider::{Provider, ProviderStream, StreamFuture};
+use crate::provider::{CountTokensFuture, Provider, ProviderStream, StreamFuture, WireFormat};
 
 pub struct KimiClient {
     client: Client,
@@ -16,7 +17,7 @@ pub struct KimiClient {
 impl KimiClient {
     pub fn new(config: KimiConfig) -> Result<Self> {
         let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(120))
+            .connect_timeout(config.timeouts.connect)
             .build()
             .map_err(|e| {
                 ProxyError::InternalError(format!("Failed to create HTTP client: {}", e))
@@ -33,14 +34,29 @@ impl Provider for KimiClient {
         let client = self.client.clone();
         let api_key = self.config.api_key.clone();
         let model = self.config.model.clone();
+        let retry = self.config.retry;
+        let timeouts = self.config.timeouts;
 
         Box::pin(async move {
-            Self::stream_generate_content_impl(url, body, client, api_key, model).await
+            Self::stream_generate_content_impl(url, body, client, api_key, model, retry, timeouts)
+                .await
+        })
+    }
+
+    fn count_tokens(&self, _model: &str, body: Bytes) -> CountTokensFuture {
+        let url = format!("{}/v1/messages/count_tokens", self.config.endpoint);
+        let client = self.client.clone();
+        let api_key = self.config.api_key.clone();
+        let retry = self.config.retry;
+        let timeouts = self.config.timeouts;
+
+        Box::pin(async move {
+            Self::count_tokens_impl(url, body, client, api_key, retry, timeouts).await
         })
     }
 
-    fn needs_transformation(&self) -> bool {
-        false // Kimi is Claude-compatible, no transformation needed
+    fn wire_format(&self) -> WireFormat {
+        WireFormat::Anthropic // Kimi is Claude-compatible, no transformation needed
     }
 
     fn name(&self) -> &str {
@@ -55,6 +71,8 @@ impl KimiClient {
         client: Client,
         api_key: String,
         model: String,
+        retry: RetryPolicy,
+        timeouts: Timeouts,
     ) -> Result<ProviderStream> {
         info!(
             "Kimi: Sending {} bytes to: {} with model: {}",
@@ -87,15 +105,16 @@ impl KimiClient {
             let _ = writeln!(file);
         }
 
-        let response = client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .header("anthropic-version", "2023-06-01")
-            .header("x-api-key", &api_key)
-            .body(body)
-            .send()
-            .await
-            .map_err(|e| ProxyError::UpstreamError(format!("Kimi request failed: {}", e)))?;
+        let response = retry
+            .send("Kimi", timeouts.first_byte, || {
+                client
+                    .post(&url)
+                    .header("Content-Type", "application/json")
+                    .header("anthropic-version", "2023-06-01")
+                    .header("x-api-key", &api_key)
+                    .body(body.clone())
+            })
+            .await?;
 
         let status = response.status();
         info!("Kimi responded with status: {}", status);
@@ -112,10 +131,7 @@ impl KimiClient {
         }
 
         if !status.is_success() {
-            let error_body = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
+            let error_body = timeouts.error_body("Kimi", response).await?;
 
             // Log error
             if let Ok(mut file) = OpenOptions::new()
@@ -128,12 +144,61 @@ impl KimiClient {
                 let _ = writeln!(file, "\n");
             }
 
-            return Err(ProxyError::UpstreamError(format!(
-                "Kimi API error {}: {}",
-                status, error_body
-            )));
+            return Err(ProxyError::upstream_status(
+                "Kimi",
+                status.as_u16(),
+                &error_body,
+            ));
+        }
+
+        Ok(timeouts.stream("Kimi", response))
+    }
+
+    /// Forward count_tokens to Kimi's Claude-compatible endpoint
+    async fn count_tokens_impl(
+        url: String,
+        body: Bytes,
+        client: Client,
+        api_key: String,
+        retry: RetryPolicy,
+        timeouts: Timeouts,
+    ) -> Result<u32> {
+        info!("Kimi: Counting tokens at: {}", url);
+
+        let response = retry
+            .send("Kimi", timeouts.first_byte, || {
+                client
+                    .post(&url)
+                    .header("Content-Type", "application/json")
+                    .header("anthropic-version", "2023-06-01")
+                    .header("x-api-key", &api_key)
+                    .body(body.clone())
+            })
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_body = timeouts.error_body("Kimi", response).await?;
+            return Err(ProxyError::upstream_status(
+                "Kimi",
+                status.as_u16(),
+                &error_body,
+            ));
         }
 
-        Ok(Box::pin(response.bytes_stream()))
+        let bytes = timeouts.body("Kimi", response).await?;
+        let result: serde_json::Value = serde_json::from_slice(&bytes).map_err(|e| {
+            ProxyError::UpstreamError(format!("Invalid count_tokens response: {}", e))
+        })?;
+
+        result["input_tokens"]
+            .as_u64()
+            .map(|n| n as u32)
+            .ok_or_else(|| {
+                ProxyError::UpstreamError(format!(
+                    "count_tokens response missing input_tokens: {}",
+                    result
+                ))
+            })
     }
 }
@@ -1,5 +1,26 @@
 mod gemini;
 mod kimi;
+mod openai;
+mod retry;
+mod timeout;
 
 pub use gemini::GeminiClient;
 pub use kimi::KimiClient;
+pub use openai::OpenAIClient;
+pub use retry::RetryPolicy;
+pub use timeout::Timeouts;
+
+use std::sync::Arc;
+
+use crate::config::ProviderConfig;
+use crate::error::Result;
+use crate::provider::Provider;
+
+/// Create the client for a provider configuration
+pub fn build_provider(config: &ProviderConfig) -> Result<Arc<dyn Provider>> {
+    Ok(match config {
+        ProviderConfig::Gemini(config) => Arc::new(GeminiClient::new(config.clone())?),
+        ProviderConfig::Kimi(config) => Arc::new(KimiClient::new(config.clone())?),
+        ProviderConfig::OpenAI(config) => Arc::new(OpenAIClient::new(config.clone())?),
+    })
+}
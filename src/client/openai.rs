@@ -0,0 +1,114 @@
+use bytes::Bytes;
+use reqwest::{Client, RequestBuilder};
+use tracing::info;
+
+use super::{RetryPolicy, Timeouts};
+use crate::config::OpenAIConfig;
+use crate::error::{ProxyError, Result};
+use crate::provider::{CountTokensFuture, Provider, ProviderStream, StreamFuture, WireFormat};
+
+pub struct OpenAIClient {
+    client: Client,
+    config: OpenAIConfig,
+}
+
+impl OpenAIClient {
+    pub fn new(config: OpenAIConfig) -> Result<Self> {
+        let client = Client::builder()
+            .connect_timeout(config.timeouts.connect)
+            .build()
+            .map_err(|e| {
+                ProxyError::InternalError(format!("Failed to create HTTP client: {}", e))
+            })?;
+
+        Ok(Self { client, config })
+    }
+
+    fn authorize(builder: RequestBuilder, api_key: Option<&str>) -> RequestBuilder {
+        match api_key {
+            Some(key) => builder.bearer_auth(key),
+            None => builder,
+        }
+    }
+}
+
+impl Provider for OpenAIClient {
+    fn stream_generate_content(&self, model: &str, body: Bytes) -> StreamFuture {
+        let url = format!("{}/chat/completions", self.config.base_url);
+        let client = self.client.clone();
+        let api_key = self.config.api_key.clone();
+        let model = model.to_string();
+        let retry = self.config.retry;
+        let timeouts = self.config.timeouts;
+
+        Box::pin(async move {
+            Self::stream_generate_content_impl(url, body, client, api_key, model, retry, timeouts)
+                .await
+        })
+    }
+
+    /// Chat Completions has no token counting endpoint, so this is a local estimate
+    ///
+    /// Uses the common ~4 bytes per token heuristic over the transformed request.
+    fn count_tokens(&self, model: &str, body: Bytes) -> CountTokensFuture {
+        let estimate = (body.len() as u32).div_ceil(4);
+        info!(
+            "OpenAI: Estimated {} input tokens for {} (no count_tokens endpoint)",
+            estimate, model
+        );
+
+        Box::pin(async move { Ok(estimate) })
+    }
+
+    fn wire_format(&self) -> WireFormat {
+        WireFormat::OpenAI // Claude->Chat Completions transformation
+    }
+
+    fn name(&self) -> &str {
+        "OpenAI"
+    }
+}
+
+impl OpenAIClient {
+    async fn stream_generate_content_impl(
+        url: String,
+        body: Bytes,
+        client: Client,
+        api_key: Option<String>,
+        model: String,
+        retry: RetryPolicy,
+        timeouts: Timeouts,
+    ) -> Result<ProviderStream> {
+        info!(
+            "OpenAI: Sending {} bytes to: {} with model: {}",
+            body.len(),
+            url,
+            model
+        );
+
+        let response = retry
+            .send("OpenAI", timeouts.first_byte, || {
+                let request = client
+                    .post(&url)
+                    .header("Content-Type", "application/json")
+                    .header("Accept", "text/event-stream")
+                    .body(body.clone());
+                Self::authorize(request, api_key.as_deref())
+            })
+            .await?;
+
+        let status = response.status();
+        info!("OpenAI responded with status: {}", status);
+
+        if !status.is_success() {
+            let error_body = timeouts.error_body("OpenAI", response).await?;
+            return Err(ProxyError::upstream_status(
+                "OpenAI",
+                status.as_u16(),
+                &error_body,
+            ));
+        }
+
+        Ok(timeouts.stream("OpenAI", response))
+    }
+}
@@ -0,0 +1,177 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::time::{Duration, SystemTime};
+use tracing::warn;
+
+use crate::error::{ProxyError, Result};
+
+/// When and how often a failed upstream request is sent again
+///
+/// Only connect errors and transient statuses (429, 500, 503, 529) are
+/// retried, and only before the response body is handed to the caller:
+/// once a stream has started, a failure is reported rather than replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first; 1 disables retries
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each further one
+    pub initial_backoff: Duration,
+    /// Upper bound for a single delay; a longer `Retry-After` is not waited out
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Send a request at most once
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Jittered delay before retry number `retry` (starting at 1)
+    ///
+    /// Half of the exponential delay is fixed and the other half random, so
+    /// clients that failed together don't all come back at the same instant.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_backoff);
+        let half = exponential / 2;
+        half + half.mul_f64(rand::random::<f64>())
+    }
+
+    /// Send the request built by `build`, retrying transient failures
+    ///
+    /// Each attempt gets `first_byte` to produce response headers; an upstream
+    /// that stays silent that long is not retried. The final response is
+    /// returned whatever its status, so callers handle errors exactly as for a
+    /// single attempt.
+    pub async fn send<F>(&self, provider: &str, first_byte: Duration, build: F) -> Result<Response>
+    where
+        F: Fn() -> RequestBuilder,
+    {
+        let mut attempt = 1;
+        loop {
+            let result = tokio::time::timeout(first_byte, build().send())
+                .await
+                .map_err(|_| {
+                    ProxyError::UpstreamTimeout(format!(
+                        "{} sent no response within {:?}",
+                        provider, first_byte
+                    ))
+                })?;
+            let failed = |e: reqwest::Error| {
+                ProxyError::UpstreamError(format!("{} request failed: {}", provider, e))
+            };
+            if attempt >= self.max_attempts {
+                return result.map_err(failed);
+            }
+
+            let (delay, reason) = match &result {
+                Err(e) if e.is_connect() => (self.backoff(attempt), e.to_string()),
+                Ok(response) if is_retryable(response.status()) => {
+                    match retry_after(response.headers()) {
+                        // Waiting that long would stall the client; let fallback take over
+                        Some(delay) if delay > self.max_backoff => return result.map_err(failed),
+                        Some(delay) => (delay, response.status().to_string()),
+                        None => (self.backoff(attempt), response.status().to_string()),
+                    }
+                }
+                _ => return result.map_err(failed),
+            };
+
+            warn!(
+                "{}: attempt {}/{} failed ({}), retrying in {:?}",
+                provider, attempt, self.max_attempts, reason, delay
+            );
+            drop(result);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Statuses worth repeating the same request for
+fn is_retryable(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 503 | 529)
+}
+
+/// `Retry-After` as either delay seconds or an HTTP date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_backoff_grows_with_jitter_and_cap() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+        };
+
+        for _ in 0..20 {
+            let first = policy.backoff(1);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            let second = policy.backoff(2);
+            assert!(second >= Duration::from_millis(100) && second <= Duration::from_millis(200));
+            let capped = policy.backoff(10);
+            assert!(capped >= Duration::from_millis(150) && capped <= Duration::from_millis(300));
+        }
+    }
+
+    #[test]
+    fn test_retry_after_parsing() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+
+        let past = httpdate::fmt_http_date(SystemTime::UNIX_EPOCH);
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(&past).unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+
+        let future = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(120));
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(&future).unwrap());
+        let delay = retry_after(&headers).unwrap();
+        assert!(delay > Duration::from_secs(100) && delay <= Duration::from_secs(120));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_retryable_statuses() {
+        for status in [429, 500, 503, 529] {
+            assert!(is_retryable(StatusCode::from_u16(status).unwrap()));
+        }
+        for status in [400, 401, 404, 502] {
+            assert!(!is_retryable(StatusCode::from_u16(status).unwrap()));
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use bytes::Bytes;
+use futures::StreamExt;
+use reqwest::Response;
+use std::time::Duration;
+
+use crate::error::{ProxyError, Result};
+use crate::provider::ProviderStream;
+
+/// How long an upstream may take at each stage of a request
+///
+/// There is deliberately no limit on the whole response: a long reasoning
+/// stream is fine as long as it keeps sending data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Establishing the TCP and TLS connection
+    pub connect: Duration,
+    /// Waiting for the response headers, and then for the first body chunk
+    pub first_byte: Duration,
+    /// Longest gap between two body chunks
+    pub idle: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs(10),
+            first_byte: Duration::from_secs(120),
+            idle: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Timeouts {
+    /// Stream the response body, failing once the upstream goes quiet
+    ///
+    /// A read error or timeout is yielded as the last item, so the handler can
+    /// report it to the client instead of dropping the connection.
+    pub fn stream(&self, provider: &'static str, response: Response) -> ProviderStream {
+        let upstream = response.bytes_stream().boxed();
+        let (first_byte, idle) = (self.first_byte, self.idle);
+
+        Box::pin(futures::stream::unfold(
+            Some((upstream, first_byte)),
+            move |state| async move {
+                let (mut upstream, wait) = state?;
+                match tokio::time::timeout(wait, upstream.next()).await {
+                    Ok(Some(Ok(chunk))) => Some((Ok(chunk), Some((upstream, idle)))),
+                    Ok(Some(Err(e))) => Some((
+                        Err(ProxyError::UpstreamError(format!(
+                            "{} stream read failed: {}",
+                            provider, e
+                        ))),
+                        None,
+                    )),
+                    Ok(None) => None,
+                    Err(_) => Some((
+                        Err(ProxyError::UpstreamTimeout(format!(
+                            "{} sent no data for {:?}",
+                            provider, wait
+                        ))),
+                        None,
+                    )),
+                }
+            },
+        ))
+    }
+
+    /// Read a complete, non-streamed body, allowing `idle` for all of it
+    pub async fn body(&self, provider: &str, response: Response) -> Result<Bytes> {
+        tokio::time::timeout(self.idle, response.bytes())
+            .await
+            .map_err(|_| self.body_timeout(provider))?
+            .map_err(|e| {
+                ProxyError::UpstreamError(format!("{} response read failed: {}", provider, e))
+            })
+    }
+
+    /// Read the body of an error response for the error message
+    ///
+    /// An unreadable body becomes "Unknown error", but one that stalls is
+    /// reported as a timeout rather than waited on.
+    pub async fn error_body(&self, provider: &str, response: Response) -> Result<String> {
+        let text = tokio::time::timeout(self.idle, response.text())
+            .await
+            .map_err(|_| self.body_timeout(provider))?;
+        Ok(text.unwrap_or_else(|_| "Unknown error".to_string()))
+    }
+
+    fn body_timeout(&self, provider: &str) -> ProxyError {
+        ProxyError::UpstreamTimeout(format!(
+            "{} sent no complete response body within {:?}",
+            provider, self.idle
+        ))
+    }
+}
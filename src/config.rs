@@ -1,23 +1,61 @@
+use crate::client::{RetryPolicy, Timeouts};
 use crate::error::{ProxyError, Result};
+use crate::routing::{FallbackTarget, ModelRoute, ModelRouter};
+use crate::state::StateStorage;
+use crate::transform::context::ContextStrategy;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Largest accepted `/v1/messages` body; long Claude Code sessions exceed axum's 2 MiB default
+///
+/// Leaves room for Gemini's 20 MB of inline data plus base64 overhead, so
+/// oversized attachments get the inline data error rather than a body limit one.
+pub const DEFAULT_MAX_REQUEST_BYTES: usize = 32 * 1024 * 1024;
 
 #[derive(Debug, Clone)]
 pub struct ProxyConfig {
     pub server: ServerConfig,
+    /// Provider serving requests whose route doesn't name another one
     pub provider: ProviderConfig,
+    /// Name of `provider`; its subcommand name when loaded from the environment
+    pub default_provider: String,
+    /// Other named providers from a config file, selected by routes
+    pub providers: BTreeMap<String, ProviderConfig>,
+    /// Model routing rules shared by all providers
+    pub routes: ModelRouter,
+    /// Backends tried in order when a request's target is rate limited or
+    /// unavailable, unless its route has its own chain
+    pub fallback: Vec<FallbackTarget>,
+    /// History trimming applied before translating to a non-Anthropic backend
+    pub context_strategy: ContextStrategy,
+    /// How long an idle session's conversation state is kept
+    pub session_ttl: Duration,
+    /// Where conversation state is kept
+    pub state_storage: StateStorage,
+    /// Drop all conversation state (including persisted state) at startup
+    pub clear_state_on_startup: bool,
+    /// How often the background sweeper expires and evicts state
+    pub state_sweep_interval: Duration,
+    /// Cap on tool mappings across all sessions, evicted LRU (0 = unbounded)
+    pub state_max_entries: usize,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ServerConfig {
     pub listen_addr: String,
     pub workers: usize,
+    /// Largest accepted request body in bytes
+    pub max_request_bytes: usize,
 }
 
 #[derive(Debug, Clone)]
 pub enum ProviderConfig {
     Gemini(GeminiConfig),
     Kimi(KimiConfig),
+    OpenAI(OpenAIConfig),
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -29,6 +67,15 @@ pub struct GeminiConfig {
     /// Whether to prompt model to update todo list after tool execution
     #[serde(default = "default_auto_todo_prompt")]
     pub auto_todo_prompt: bool,
+    /// Whether `cache_control` prefixes are stored as Gemini cached contents
+    #[serde(default = "default_prompt_cache")]
+    pub prompt_cache: bool,
+    /// Retries for connect errors and transient statuses
+    #[serde(skip)]
+    pub retry: RetryPolicy,
+    /// Connect, first-byte and idle-stream timeouts
+    #[serde(skip)]
+    pub timeouts: Timeouts,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -36,10 +83,104 @@ pub struct KimiConfig {
     pub api_key: String,
     pub endpoint: String,
     pub model: String,
+    /// Retries for connect errors and transient statuses
+    #[serde(skip)]
+    pub retry: RetryPolicy,
+    /// Connect, first-byte and idle-stream timeouts
+    #[serde(skip)]
+    pub timeouts: Timeouts,
+}
+
+/// Any OpenAI Chat Completions compatible server (OpenAI, vLLM, llama.cpp, OpenRouter, ...)
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenAIConfig {
+    /// Bearer token; local servers usually don't need one
+    pub api_key: Option<String>,
+    /// Base URL including the version prefix, e.g. `https://api.openai.com/v1`
+    pub base_url: String,
+    /// Model used when no routing rule matches
+    pub model: String,
+    /// Retries for connect errors and transient statuses
+    #[serde(skip)]
+    pub retry: RetryPolicy,
+    /// Connect, first-byte and idle-stream timeouts
+    #[serde(skip)]
+    pub timeouts: Timeouts,
+}
+
+impl ProviderConfig {
+    /// Provider type as written in config files and subcommands
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ProviderConfig::Gemini(_) => "gemini",
+            ProviderConfig::Kimi(_) => "kimi",
+            ProviderConfig::OpenAI(_) => "openai",
+        }
+    }
+
+    pub fn retry(&self) -> &RetryPolicy {
+        match self {
+            ProviderConfig::Gemini(config) => &config.retry,
+            ProviderConfig::Kimi(config) => &config.retry,
+            ProviderConfig::OpenAI(config) => &config.retry,
+        }
+    }
+
+    pub fn timeouts(&self) -> &Timeouts {
+        match self {
+            ProviderConfig::Gemini(config) => &config.timeouts,
+            ProviderConfig::Kimi(config) => &config.timeouts,
+            ProviderConfig::OpenAI(config) => &config.timeouts,
+        }
+    }
 }
 
+/// Retry policy for the provider selected by `from_env`
+fn retry_policy_from_env() -> Result<RetryPolicy> {
+    let mut policy = RetryPolicy::default();
+    if let Ok(value) = env::var("UPSTREAM_MAX_ATTEMPTS") {
+        policy.max_attempts = value.parse::<u32>().map_err(|e| {
+            ProxyError::ConfigError(format!("Invalid UPSTREAM_MAX_ATTEMPTS value: {}", e))
+        })?;
+    }
+    if let Ok(value) = env::var("UPSTREAM_RETRY_INITIAL_MS") {
+        policy.initial_backoff = Duration::from_millis(value.parse::<u64>().map_err(|e| {
+            ProxyError::ConfigError(format!("Invalid UPSTREAM_RETRY_INITIAL_MS value: {}", e))
+        })?);
+    }
+    if let Ok(value) = env::var("UPSTREAM_RETRY_MAX_MS") {
+        policy.max_backoff = Duration::from_millis(value.parse::<u64>().map_err(|e| {
+            ProxyError::ConfigError(format!("Invalid UPSTREAM_RETRY_MAX_MS value: {}", e))
+        })?);
+    }
+    Ok(policy)
+}
+
+/// Upstream timeouts for the provider selected by `from_env`
+fn timeouts_from_env() -> Result<Timeouts> {
+    let mut timeouts = Timeouts::default();
+    for (var, timeout) in [
+        ("UPSTREAM_CONNECT_TIMEOUT_SECS", &mut timeouts.connect),
+        ("UPSTREAM_FIRST_BYTE_TIMEOUT_SECS", &mut timeouts.first_byte),
+        ("UPSTREAM_IDLE_TIMEOUT_SECS", &mut timeouts.idle),
+    ] {
+        if let Ok(value) = env::var(var) {
+            *timeout =
+                Duration::from_secs(value.parse::<u64>().map_err(|e| {
+                    ProxyError::ConfigError(format!("Invalid {} value: {}", var, e))
+                })?);
+        }
+    }
+    Ok(timeouts)
+}
+
+fn default_prompt_cache() -> bool {
+    true
+}
+
+/// Shared by the env, TOML and serde loaders so they all agree
 fn default_auto_todo_prompt() -> bool {
-    false // Disabled by default - Gemini doesn't reliably respond to todo update prompts
+    true
 }
 
 impl ProxyConfig {
@@ -54,6 +195,19 @@ impl ProxyConfig {
             .parse::<usize>()
             .map_err(|e| ProxyError::ConfigError(format!("Invalid workers value: {}", e)))?;
 
+        let max_request_bytes = match env::var("PROXY_MAX_REQUEST_BYTES") {
+            Ok(value) => value.parse::<usize>().map_err(|e| {
+                ProxyError::ConfigError(format!("Invalid PROXY_MAX_REQUEST_BYTES value: {}", e))
+            })?,
+            Err(_) => DEFAULT_MAX_REQUEST_BYTES,
+        };
+
+        // Support UPSTREAM_MAX_ATTEMPTS, UPSTREAM_RETRY_INITIAL_MS and UPSTREAM_RETRY_MAX_MS
+        let retry = retry_policy_from_env()?;
+
+        // Support UPSTREAM_CONNECT_TIMEOUT_SECS, UPSTREAM_FIRST_BYTE_TIMEOUT_SECS and UPSTREAM_IDLE_TIMEOUT_SECS
+        let timeouts = timeouts_from_env()?;
+
         let provider = match provider_type {
             "gemini" => {
                 // Try ANTHROPIC_AUTH_TOKEN first (for Claude Code compatibility), then fall back to GEMINI_API_KEY
@@ -75,13 +229,22 @@ impl ProxyConfig {
                 let auto_todo_prompt = env::var("AUTO_TODO_PROMPT")
                     .ok()
                     .and_then(|v| v.parse::<bool>().ok())
-                    .unwrap_or(true);
+                    .unwrap_or_else(default_auto_todo_prompt);
+
+                // GEMINI_PROMPT_CACHE=false sends cache_control prefixes inline
+                let prompt_cache = env::var("GEMINI_PROMPT_CACHE")
+                    .ok()
+                    .and_then(|v| v.parse::<bool>().ok())
+                    .unwrap_or_else(default_prompt_cache);
 
                 ProviderConfig::Gemini(GeminiConfig {
                     api_key,
                     endpoint,
                     default_model,
                     auto_todo_prompt,
+                    prompt_cache,
+                    retry,
+                    timeouts,
                 })
             }
             "kimi" => {
@@ -103,56 +266,703 @@ impl ProxyConfig {
                     api_key,
                     endpoint,
                     model,
+                    retry,
+                    timeouts,
+                })
+            }
+            "openai" => {
+                let api_key = env::var("OPENAI_API_KEY")
+                    .or_else(|_| env::var("ANTHROPIC_AUTH_TOKEN"))
+                    .ok()
+                    .filter(|k| !k.is_empty());
+
+                let base_url = env::var("OPENAI_BASE_URL")
+                    .unwrap_or_else(|_| "https://api.openai.com/v1".to_string())
+                    .trim_end_matches('/')
+                    .to_string();
+
+                let model = env::var("OPENAI_MODEL")
+                    .or_else(|_| env::var("ANTHROPIC_MODEL"))
+                    .unwrap_or_else(|_| "gpt-4o".to_string());
+
+                ProviderConfig::OpenAI(OpenAIConfig {
+                    api_key,
+                    base_url,
+                    model,
+                    retry,
+                    timeouts,
                 })
             }
             _ => {
                 return Err(ProxyError::ConfigError(format!(
-                    "Unknown provider: {}. Supported: gemini, kimi",
+                    "Unknown provider: {}. Supported: gemini, kimi, openai",
                     provider_type
                 )));
             }
         };
 
+        // Support MODEL_ROUTES for mapping Claude models to backend models
+        let routes = match env::var("MODEL_ROUTES") {
+            Ok(spec) => ModelRouter::from_spec(&spec)?,
+            Err(_) => ModelRouter::default(),
+        };
+
+        // Support FALLBACK_MODELS for models to try, in order, when the target is overloaded
+        let fallback = env::var("FALLBACK_MODELS")
+            .map(|models| {
+                models
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|m| !m.is_empty())
+                    .map(|model| FallbackTarget::new(None, model))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // Support CONTEXT_MAX_MESSAGES for trimming long histories (0 or unset = keep all)
+        let context_strategy = match env::var("CONTEXT_MAX_MESSAGES") {
+            Ok(value) => {
+                ContextStrategy::from_max_messages(value.parse::<usize>().map_err(|e| {
+                    ProxyError::ConfigError(format!("Invalid CONTEXT_MAX_MESSAGES value: {}", e))
+                })?)
+            }
+            Err(_) => ContextStrategy::default(),
+        };
+
+        // Support SESSION_TTL_SECS for how long idle session state is kept
+        let session_ttl = env::var("SESSION_TTL_SECS")
+            .unwrap_or_else(|_| "3600".to_string())
+            .parse::<u64>()
+            .map(Duration::from_secs)
+            .map_err(|e| {
+                ProxyError::ConfigError(format!("Invalid SESSION_TTL_SECS value: {}", e))
+            })?;
+
+        // Support STATE_DIR for persisting conversation state across restarts
+        let state_storage = match env::var("STATE_DIR") {
+            Ok(dir) if !dir.is_empty() => StateStorage::Disk { dir: dir.into() },
+            _ => StateStorage::Memory,
+        };
+
+        // Support CLEAR_STATE_ON_STARTUP to start every run with empty state
+        let clear_state_on_startup = env::var("CLEAR_STATE_ON_STARTUP")
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+
+        // Support STATE_SWEEP_INTERVAL_SECS and STATE_MAX_ENTRIES for the state sweeper
+        let state_sweep_interval = env::var("STATE_SWEEP_INTERVAL_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()
+            .map(Duration::from_secs)
+            .map_err(|e| {
+                ProxyError::ConfigError(format!("Invalid STATE_SWEEP_INTERVAL_SECS value: {}", e))
+            })?;
+        let state_max_entries = env::var("STATE_MAX_ENTRIES")
+            .unwrap_or_else(|_| "10000".to_string())
+            .parse::<usize>()
+            .map_err(|e| {
+                ProxyError::ConfigError(format!("Invalid STATE_MAX_ENTRIES value: {}", e))
+            })?;
+
         Ok(ProxyConfig {
             server: ServerConfig {
                 listen_addr,
                 workers,
+                max_request_bytes,
             },
             provider,
+            default_provider: provider_type.to_string(),
+            providers: BTreeMap::new(),
+            routes,
+            fallback,
+            context_strategy,
+            session_ttl,
+            state_storage,
+            clear_state_on_startup,
+            state_sweep_interval,
+            state_max_entries,
         })
     }
 
+    /// Load providers, routes and server settings from a TOML file
+    ///
+    /// The whole file is checked before returning: every problem, including
+    /// unreadable API keys, is reported at once with its TOML path.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            ProxyError::ConfigError(format!("Cannot read {}: {}", path.display(), e))
+        })?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        Self::from_toml_str(&text, base_dir)
+    }
+
+    /// Parse a TOML configuration; relative `api_key_file` paths are resolved against `base_dir`
+    pub fn from_toml_str(text: &str, base_dir: &Path) -> Result<Self> {
+        let file: FileConfig = toml::from_str(text)
+            .map_err(|e| ProxyError::ConfigError(format!("Invalid config file: {}", e)))?;
+        file.into_config(base_dir)
+    }
+
+    /// Resolve the backend model for a requested Claude model
+    ///
+    /// Returns the target model together with the routing rule that produced it.
+    /// When no rule matches, Gemini falls back to `default_model` (or the built-in
+    /// default), Kimi keeps the requested model unchanged and OpenAI uses its
+    /// configured model.
+    pub fn resolve_model(&self, requested: &str) -> (String, Option<&ModelRoute>) {
+        if let Some(route) = self.routes.resolve(requested) {
+            return (route.target.clone(), Some(route));
+        }
+
+        let fallback = match &self.provider {
+            ProviderConfig::Gemini(config) => config
+                .default_model
+                .clone()
+                .unwrap_or_else(|| crate::transform::map_model_name(requested).to_string()),
+            ProviderConfig::Kimi(_) => requested.to_string(),
+            ProviderConfig::OpenAI(config) => config.model.clone(),
+        };
+        (fallback, None)
+    }
+
+    /// Fallback chain for a request resolved through `route`
+    ///
+    /// A route's own chain replaces the global one.
+    pub fn fallback_chain<'a>(&'a self, route: Option<&'a ModelRoute>) -> &'a [FallbackTarget] {
+        match route {
+            Some(route) if !route.fallback.is_empty() => &route.fallback,
+            _ => &self.fallback,
+        }
+    }
+
+    /// Configuration of a named provider; `None` or an unknown name gives the default
+    pub fn provider_config(&self, name: Option<&str>) -> &ProviderConfig {
+        name.and_then(|name| self.providers.get(name))
+            .unwrap_or(&self.provider)
+    }
+
+    /// Check whether `name` refers to a configured provider
+    pub fn has_provider(&self, name: &str) -> bool {
+        name == self.default_provider || self.providers.contains_key(name)
+    }
+
     /// Validate configuration
+    ///
+    /// Every problem is reported in one error, each prefixed with its TOML path.
     pub fn validate(&self) -> Result<()> {
-        match &self.provider {
-            ProviderConfig::Gemini(config) => {
-                if config.api_key.is_empty() {
-                    return Err(ProxyError::ConfigError("API key is empty".to_string()));
-                }
-                if config.endpoint.is_empty() {
-                    return Err(ProxyError::ConfigError("Endpoint is empty".to_string()));
-                }
+        problems_to_result(self.problems())
+    }
+
+    /// Every validation problem as `<path>: <message>`
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        check_provider(
+            &format!("providers.{}", self.default_provider),
+            &self.provider,
+            &mut problems,
+        );
+        for (name, provider) in &self.providers {
+            check_provider(&format!("providers.{}", name), provider, &mut problems);
+        }
+
+        for (i, route) in self.routes.routes().iter().enumerate() {
+            if route.target.is_empty() {
+                problems.push(format!("routes[{}].target: Target model is empty", i));
             }
-            ProviderConfig::Kimi(config) => {
-                if config.api_key.is_empty() {
-                    return Err(ProxyError::ConfigError("API key is empty".to_string()));
-                }
-                if config.endpoint.is_empty() {
-                    return Err(ProxyError::ConfigError("Endpoint is empty".to_string()));
+            if let Some(provider) = &route.provider
+                && !self.has_provider(provider)
+            {
+                problems.push(format!(
+                    "routes[{}].provider: Unknown provider '{}'",
+                    i, provider
+                ));
+            }
+            self.check_fallback(
+                &format!("routes[{}].fallback", i),
+                &route.fallback,
+                &mut problems,
+            );
+        }
+        self.check_fallback("fallback", &self.fallback, &mut problems);
+
+        if self.session_ttl.is_zero() {
+            problems.push("state.ttl_secs: Session TTL must be greater than 0".to_string());
+        }
+
+        if self.state_sweep_interval.is_zero() {
+            problems.push(
+                "state.sweep_interval_secs: State sweep interval must be greater than 0"
+                    .to_string(),
+            );
+        }
+
+        if self.server.workers == 0 {
+            problems.push("server.workers: Workers must be greater than 0".to_string());
+        }
+
+        if self.server.max_request_bytes == 0 {
+            problems.push(
+                "server.max_request_bytes: Request size limit must be greater than 0".to_string(),
+            );
+        }
+
+        problems
+    }
+
+    fn check_fallback(&self, path: &str, chain: &[FallbackTarget], problems: &mut Vec<String>) {
+        for (i, step) in chain.iter().enumerate() {
+            if step.model.is_empty() {
+                problems.push(format!("{}[{}].model: Model is empty", path, i));
+            }
+            if let Some(provider) = &step.provider
+                && !self.has_provider(provider)
+            {
+                problems.push(format!(
+                    "{}[{}].provider: Unknown provider '{}'",
+                    path, i, provider
+                ));
+            }
+        }
+    }
+}
+
+fn check_provider(path: &str, provider: &ProviderConfig, problems: &mut Vec<String>) {
+    let retry = provider.retry();
+    if retry.max_attempts == 0 {
+        problems.push(format!("{}.retry.max_attempts: Must be at least 1", path));
+    }
+    if retry.initial_backoff > retry.max_backoff {
+        problems.push(format!(
+            "{}.retry.initial_backoff_ms: Must not exceed max_backoff_ms",
+            path
+        ));
+    }
+
+    let timeouts = provider.timeouts();
+    for (key, timeout) in [
+        ("connect_secs", timeouts.connect),
+        ("first_byte_secs", timeouts.first_byte),
+        ("idle_secs", timeouts.idle),
+    ] {
+        if timeout.is_zero() {
+            problems.push(format!(
+                "{}.timeouts.{}: Timeout must be greater than 0",
+                path, key
+            ));
+        }
+    }
+
+    match provider {
+        ProviderConfig::Gemini(config) => {
+            if config.api_key.is_empty() {
+                problems.push(format!("{}.api_key: API key is empty", path));
+            }
+            if config.endpoint.is_empty() {
+                problems.push(format!("{}.endpoint: Endpoint is empty", path));
+            }
+        }
+        ProviderConfig::Kimi(config) => {
+            if config.api_key.is_empty() {
+                problems.push(format!("{}.api_key: API key is empty", path));
+            }
+            if config.endpoint.is_empty() {
+                problems.push(format!("{}.endpoint: Endpoint is empty", path));
+            }
+            if config.model.is_empty() {
+                problems.push(format!("{}.model: Model is empty", path));
+            }
+        }
+        ProviderConfig::OpenAI(config) => {
+            if !config.base_url.starts_with("http://") && !config.base_url.starts_with("https://") {
+                problems.push(format!(
+                    "{}.base_url: Base URL must start with http:// or https://: {}",
+                    path, config.base_url
+                ));
+            }
+            if config.model.is_empty() {
+                problems.push(format!("{}.model: Model is empty", path));
+            }
+        }
+    }
+}
+
+fn problems_to_result(problems: Vec<String>) -> Result<()> {
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(problems_error(&problems))
+}
+
+fn problems_error(problems: &[String]) -> ProxyError {
+    ProxyError::ConfigError(format!(
+        "{} configuration problem(s): {}",
+        problems.len(),
+        problems.join("; ")
+    ))
+}
+
+/// Layout of a `--config` TOML file
+///
+/// ```toml
+/// default_provider = "gemini"
+///
+/// [server]
+/// listen_addr = "127.0.0.1:8080"
+///
+/// [providers.gemini]
+/// type = "gemini"
+/// api_key_env = "GEMINI_API_KEY"
+/// models = { "claude-*haiku*" = "gemini-2.5-flash" }
+///
+/// [providers.local]
+/// type = "openai"
+/// base_url = "http://127.0.0.1:8000/v1"
+/// model = "qwen3-coder"
+///
+/// [[routes]]
+/// match = "prefix"
+/// pattern = "claude-3-5-haiku"
+/// target = "qwen3-coder"
+/// provider = "local"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    /// Required when more than one provider is defined
+    default_provider: Option<String>,
+    server: FileServer,
+    state: FileState,
+    context: FileContext,
+    providers: BTreeMap<String, FileProvider>,
+    routes: Vec<ModelRoute>,
+    fallback: Vec<FallbackTarget>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileServer {
+    listen_addr: String,
+    workers: usize,
+    max_request_bytes: usize,
+}
+
+impl Default for FileServer {
+    fn default() -> Self {
+        Self {
+            listen_addr: "127.0.0.1:8080".to_string(),
+            workers: 4,
+            max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileState {
+    ttl_secs: u64,
+    dir: Option<PathBuf>,
+    clear_on_startup: bool,
+    sweep_interval_secs: u64,
+    max_entries: usize,
+}
+
+impl Default for FileState {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 3600,
+            dir: None,
+            clear_on_startup: false,
+            sweep_interval_secs: 60,
+            max_entries: 10000,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileContext {
+    /// 0 keeps the whole history
+    max_messages: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ProviderKind {
+    Gemini,
+    Kimi,
+    OpenAI,
+}
+
+/// One `[providers.<name>]` table; which options apply depends on `type`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileProvider {
+    #[serde(rename = "type")]
+    kind: ProviderKind,
+    api_key: Option<String>,
+    /// Environment variable holding the API key
+    api_key_env: Option<String>,
+    /// File holding the API key, relative to the config file
+    api_key_file: Option<PathBuf>,
+    endpoint: Option<String>,
+    base_url: Option<String>,
+    model: Option<String>,
+    default_model: Option<String>,
+    auto_todo_prompt: Option<bool>,
+    prompt_cache: Option<bool>,
+    #[serde(default)]
+    retry: FileRetry,
+    #[serde(default)]
+    timeouts: FileTimeouts,
+    /// Claude model pattern -> backend model, served by this provider
+    #[serde(default)]
+    models: BTreeMap<String, String>,
+}
+
+/// `retry` table of a provider; unset keys keep the defaults
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileRetry {
+    max_attempts: Option<u32>,
+    initial_backoff_ms: Option<u64>,
+    max_backoff_ms: Option<u64>,
+}
+
+impl FileRetry {
+    fn policy(&self) -> RetryPolicy {
+        let default = RetryPolicy::default();
+        RetryPolicy {
+            max_attempts: self.max_attempts.unwrap_or(default.max_attempts),
+            initial_backoff: self
+                .initial_backoff_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.initial_backoff),
+            max_backoff: self
+                .max_backoff_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.max_backoff),
+        }
+    }
+}
+
+/// `timeouts` table of a provider, in seconds; unset keys keep the defaults
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileTimeouts {
+    connect_secs: Option<u64>,
+    first_byte_secs: Option<u64>,
+    idle_secs: Option<u64>,
+}
+
+impl FileTimeouts {
+    fn timeouts(&self) -> Timeouts {
+        let default = Timeouts::default();
+        let secs = |value: Option<u64>, default| value.map(Duration::from_secs).unwrap_or(default);
+        Timeouts {
+            connect: secs(self.connect_secs, default.connect),
+            first_byte: secs(self.first_byte_secs, default.first_byte),
+            idle: secs(self.idle_secs, default.idle),
+        }
+    }
+}
+
+impl FileConfig {
+    fn into_config(self, base_dir: &Path) -> Result<ProxyConfig> {
+        let mut problems = Vec::new();
+
+        let mut providers = BTreeMap::new();
+        let mut routes = self.routes;
+        for (name, provider) in self.providers {
+            let path = format!("providers.{}", name);
+            routes.extend(provider.models.iter().map(|(pattern, target)| {
+                ModelRoute::from_pattern(pattern, target).with_provider(&name)
+            }));
+            providers.insert(name, provider.build(&path, base_dir, &mut problems));
+        }
+
+        let default_provider = match self.default_provider {
+            Some(name) => {
+                if !providers.contains_key(&name) {
+                    problems.push(format!("default_provider: Unknown provider '{}'", name));
                 }
-                if config.model.is_empty() {
-                    return Err(ProxyError::ConfigError("Model is empty".to_string()));
+                name
+            }
+            None => {
+                if providers.len() > 1 {
+                    problems.push(
+                        "default_provider: Required when more than one provider is defined"
+                            .to_string(),
+                    );
                 }
+                providers.keys().next().cloned().unwrap_or_default()
+            }
+        };
+        let Some(provider) = providers
+            .remove(&default_provider)
+            .or_else(|| providers.values().next().cloned())
+        else {
+            problems.push("providers: At least one provider is required".to_string());
+            return Err(problems_error(&problems));
+        };
+
+        let config = ProxyConfig {
+            server: ServerConfig {
+                listen_addr: self.server.listen_addr,
+                workers: self.server.workers,
+                max_request_bytes: self.server.max_request_bytes,
+            },
+            provider,
+            default_provider,
+            providers,
+            routes: ModelRouter::new(routes),
+            fallback: self.fallback,
+            context_strategy: ContextStrategy::from_max_messages(self.context.max_messages),
+            session_ttl: Duration::from_secs(self.state.ttl_secs),
+            state_storage: match self.state.dir {
+                Some(dir) => StateStorage::Disk {
+                    dir: base_dir.join(dir),
+                },
+                None => StateStorage::Memory,
+            },
+            clear_state_on_startup: self.state.clear_on_startup,
+            state_sweep_interval: Duration::from_secs(self.state.sweep_interval_secs),
+            state_max_entries: self.state.max_entries,
+        };
+
+        // A key that failed to load is reported once, by the loader
+        for problem in config.problems() {
+            let path = problem.split(':').next().unwrap_or_default();
+            if !problems
+                .iter()
+                .any(|p| p.starts_with(&format!("{}:", path)))
+            {
+                problems.push(problem);
             }
         }
+        problems_to_result(problems).map(|_| config)
+    }
+}
 
-        if self.server.workers == 0 {
-            return Err(ProxyError::ConfigError(
-                "Workers must be greater than 0".to_string(),
+impl FileProvider {
+    /// Build the provider config, recording problems instead of stopping at the first
+    fn build(&self, path: &str, base_dir: &Path, problems: &mut Vec<String>) -> ProviderConfig {
+        let unsupported: &[(&str, bool)] = match self.kind {
+            ProviderKind::Gemini => &[
+                ("base_url", self.base_url.is_some()),
+                ("model", self.model.is_some()),
+            ],
+            ProviderKind::Kimi => &[
+                ("base_url", self.base_url.is_some()),
+                ("default_model", self.default_model.is_some()),
+                ("auto_todo_prompt", self.auto_todo_prompt.is_some()),
+                ("prompt_cache", self.prompt_cache.is_some()),
+            ],
+            ProviderKind::OpenAI => &[
+                ("endpoint", self.endpoint.is_some()),
+                ("default_model", self.default_model.is_some()),
+                ("auto_todo_prompt", self.auto_todo_prompt.is_some()),
+                ("prompt_cache", self.prompt_cache.is_some()),
+            ],
+        };
+        for (key, _) in unsupported.iter().filter(|(_, set)| *set) {
+            problems.push(format!(
+                "{}.{}: Not supported by {:?} providers",
+                path, key, self.kind
             ));
         }
 
-        Ok(())
+        let api_key = self.api_key(path, base_dir, problems);
+
+        match self.kind {
+            ProviderKind::Gemini => ProviderConfig::Gemini(GeminiConfig {
+                api_key: api_key.unwrap_or_default(),
+                endpoint: self
+                    .endpoint
+                    .clone()
+                    .unwrap_or_else(|| "generativelanguage.googleapis.com".to_string()),
+                default_model: self.default_model.clone(),
+                auto_todo_prompt: self
+                    .auto_todo_prompt
+                    .unwrap_or_else(default_auto_todo_prompt),
+                prompt_cache: self.prompt_cache.unwrap_or_else(default_prompt_cache),
+                retry: self.retry.policy(),
+                timeouts: self.timeouts.timeouts(),
+            }),
+            ProviderKind::Kimi => ProviderConfig::Kimi(KimiConfig {
+                api_key: api_key.unwrap_or_default(),
+                endpoint: self
+                    .endpoint
+                    .clone()
+                    .unwrap_or_else(|| "https://api.moonshot.ai/anthropic".to_string()),
+                model: self
+                    .model
+                    .clone()
+                    .unwrap_or_else(|| "kimi-k2-thinking-turbo".to_string()),
+                retry: self.retry.policy(),
+                timeouts: self.timeouts.timeouts(),
+            }),
+            ProviderKind::OpenAI => ProviderConfig::OpenAI(OpenAIConfig {
+                api_key: api_key.filter(|k| !k.is_empty()),
+                base_url: self
+                    .base_url
+                    .as_deref()
+                    .unwrap_or("https://api.openai.com/v1")
+                    .trim_end_matches('/')
+                    .to_string(),
+                model: self.model.clone().unwrap_or_else(|| "gpt-4o".to_string()),
+                retry: self.retry.policy(),
+                timeouts: self.timeouts.timeouts(),
+            }),
+        }
+    }
+
+    /// Read the API key from whichever of `api_key`, `api_key_env` or `api_key_file` is set
+    fn api_key(&self, path: &str, base_dir: &Path, problems: &mut Vec<String>) -> Option<String> {
+        let sources = [
+            self.api_key.is_some(),
+            self.api_key_env.is_some(),
+            self.api_key_file.is_some(),
+        ];
+        if sources.iter().filter(|set| **set).count() > 1 {
+            problems.push(format!(
+                "{}.api_key: Set only one of api_key, api_key_env and api_key_file",
+                path
+            ));
+            return None;
+        }
+
+        if let Some(key) = &self.api_key {
+            return Some(key.clone());
+        }
+        if let Some(var) = &self.api_key_env {
+            return match env::var(var) {
+                Ok(key) => Some(key),
+                Err(_) => {
+                    problems.push(format!(
+                        "{}.api_key: Environment variable {} is not set",
+                        path, var
+                    ));
+                    None
+                }
+            };
+        }
+        if let Some(file) = &self.api_key_file {
+            let file = base_dir.join(file);
+            return match std::fs::read_to_string(&file) {
+                Ok(key) => Some(key.trim().to_string()),
+                Err(e) => {
+                    problems.push(format!(
+                        "{}.api_key: Cannot read {}: {}",
+                        path,
+                        file.display(),
+                        e
+                    ));
+                    None
+                }
+            };
+        }
+        None
     }
 }
 
@@ -166,13 +976,27 @@ mod tests {
             server: ServerConfig {
                 listen_addr: "127.0.0.1:8080".to_string(),
                 workers: 4,
+                max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
             },
             provider: ProviderConfig::Gemini(GeminiConfig {
                 api_key: "test-key".to_string(),
                 endpoint: "test.googleapis.com".to_string(),
                 default_model: None,
                 auto_todo_prompt: true,
+                prompt_cache: true,
+                retry: RetryPolicy::default(),
+                timeouts: Timeouts::default(),
             }),
+            default_provider: "gemini".to_string(),
+            providers: BTreeMap::new(),
+            routes: ModelRouter::default(),
+            fallback: Vec::new(),
+            context_strategy: ContextStrategy::default(),
+            session_ttl: Duration::from_secs(3600),
+            state_storage: StateStorage::default(),
+            clear_state_on_startup: false,
+            state_sweep_interval: Duration::from_secs(60),
+            state_max_entries: 10000,
         };
 
         assert!(valid_config.validate().is_ok());
@@ -181,13 +1005,27 @@ mod tests {
             server: ServerConfig {
                 listen_addr: "127.0.0.1:8080".to_string(),
                 workers: 0,
+                max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
             },
             provider: ProviderConfig::Gemini(GeminiConfig {
                 api_key: "test-key".to_string(),
                 endpoint: "test.googleapis.com".to_string(),
                 default_model: None,
                 auto_todo_prompt: true,
+                prompt_cache: true,
+                retry: RetryPolicy::default(),
+                timeouts: Timeouts::default(),
             }),
+            default_provider: "gemini".to_string(),
+            providers: BTreeMap::new(),
+            routes: ModelRouter::default(),
+            fallback: Vec::new(),
+            context_strategy: ContextStrategy::default(),
+            session_ttl: Duration::from_secs(3600),
+            state_storage: StateStorage::default(),
+            clear_state_on_startup: false,
+            state_sweep_interval: Duration::from_secs(60),
+            state_max_entries: 10000,
         };
 
         assert!(invalid_config.validate().is_err());
@@ -199,14 +1037,262 @@ mod tests {
             server: ServerConfig {
                 listen_addr: "127.0.0.1:8080".to_string(),
                 workers: 4,
+                max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
             },
             provider: ProviderConfig::Kimi(KimiConfig {
                 api_key: "test-key".to_string(),
                 endpoint: "https://api.moonshot.ai/anthropic".to_string(),
                 model: "kimi-k2-thinking-turbo".to_string(),
+                retry: RetryPolicy::default(),
+                timeouts: Timeouts::default(),
             }),
+            default_provider: "kimi".to_string(),
+            providers: BTreeMap::new(),
+            routes: ModelRouter::default(),
+            fallback: Vec::new(),
+            context_strategy: ContextStrategy::default(),
+            session_ttl: Duration::from_secs(3600),
+            state_storage: StateStorage::default(),
+            clear_state_on_startup: false,
+            state_sweep_interval: Duration::from_secs(60),
+            state_max_entries: 10000,
         };
 
         assert!(valid_config.validate().is_ok());
     }
+
+    #[test]
+    fn test_openai_config_validation() {
+        let config = |base_url: &str| ProxyConfig {
+            server: ServerConfig {
+                listen_addr: "127.0.0.1:8080".to_string(),
+                workers: 4,
+                max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
+            },
+            provider: ProviderConfig::OpenAI(OpenAIConfig {
+                api_key: None,
+                base_url: base_url.to_string(),
+                model: "qwen3-coder".to_string(),
+                retry: RetryPolicy::default(),
+                timeouts: Timeouts::default(),
+            }),
+            default_provider: "openai".to_string(),
+            providers: BTreeMap::new(),
+            routes: ModelRouter::default(),
+            fallback: Vec::new(),
+            context_strategy: ContextStrategy::default(),
+            session_ttl: Duration::from_secs(3600),
+            state_storage: StateStorage::default(),
+            clear_state_on_startup: false,
+            state_sweep_interval: Duration::from_secs(60),
+            state_max_entries: 10000,
+        };
+
+        assert!(config("http://127.0.0.1:8000/v1").validate().is_ok());
+        assert!(config("127.0.0.1:8000/v1").validate().is_err());
+        assert_eq!(
+            config("http://127.0.0.1:8000/v1")
+                .resolve_model("claude-sonnet-4")
+                .0,
+            "qwen3-coder"
+        );
+    }
+
+    #[test]
+    fn test_resolve_model_with_routes() {
+        let config = ProxyConfig {
+            server: ServerConfig {
+                listen_addr: "127.0.0.1:8080".to_string(),
+                workers: 4,
+                max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
+            },
+            provider: ProviderConfig::Gemini(GeminiConfig {
+                api_key: "test-key".to_string(),
+                endpoint: "test.googleapis.com".to_string(),
+                default_model: Some("gemini-2.5-pro".to_string()),
+                auto_todo_prompt: true,
+                prompt_cache: true,
+                retry: RetryPolicy::default(),
+                timeouts: Timeouts::default(),
+            }),
+            default_provider: "gemini".to_string(),
+            providers: BTreeMap::new(),
+            routes: ModelRouter::from_spec("claude-*haiku*=gemini-2.5-flash").unwrap(),
+            fallback: Vec::new(),
+            context_strategy: ContextStrategy::default(),
+            session_ttl: Duration::from_secs(3600),
+            state_storage: StateStorage::default(),
+            clear_state_on_startup: false,
+            state_sweep_interval: Duration::from_secs(60),
+            state_max_entries: 10000,
+        };
+
+        let (model, route) = config.resolve_model("claude-3-5-haiku-20241022");
+        assert_eq!(model, "gemini-2.5-flash");
+        assert!(route.is_some());
+
+        let (model, route) = config.resolve_model("claude-opus-4");
+        assert_eq!(model, "gemini-2.5-pro");
+        assert!(route.is_none());
+    }
+
+    #[test]
+    fn test_from_toml_with_named_providers() {
+        let dir = std::env::temp_dir().join(format!("proxy-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("gemini.key"), "file-key\n").unwrap();
+
+        let config = ProxyConfig::from_toml_str(
+            r#"
+            default_provider = "gemini"
+            fallback = [{ provider = "local", model = "qwen3-coder" }]
+
+            [server]
+            listen_addr = "0.0.0.0:9000"
+            max_request_bytes = 1048576
+
+            [state]
+            ttl_secs = 600
+
+            [providers.gemini]
+            type = "gemini"
+            api_key_file = "gemini.key"
+            default_model = "gemini-2.5-pro"
+            models = { "claude-*haiku*" = "gemini-2.5-flash" }
+
+            [providers.local]
+            type = "openai"
+            base_url = "http://127.0.0.1:8000/v1/"
+            model = "qwen3-coder"
+            retry = { max_attempts = 5, initial_backoff_ms = 250 }
+            timeouts = { idle_secs = 300 }
+
+            [[routes]]
+            match = "prefix"
+            pattern = "claude-opus"
+            target = "qwen3-coder"
+            provider = "local"
+            fallback = [{ model = "gemini-2.5-flash" }]
+            "#,
+            &dir,
+        )
+        .unwrap();
+
+        assert_eq!(config.server.listen_addr, "0.0.0.0:9000");
+        assert_eq!(config.server.workers, 4);
+        assert_eq!(config.server.max_request_bytes, 1048576);
+        assert_eq!(config.session_ttl, Duration::from_secs(600));
+        assert_eq!(config.default_provider, "gemini");
+        match &config.provider {
+            ProviderConfig::Gemini(gemini) => {
+                assert_eq!(gemini.api_key, "file-key");
+                assert_eq!(gemini.auto_todo_prompt, default_auto_todo_prompt());
+            }
+            other => panic!("unexpected default provider: {:?}", other),
+        }
+        match config.provider_config(Some("local")) {
+            ProviderConfig::OpenAI(openai) => {
+                assert_eq!(openai.base_url, "http://127.0.0.1:8000/v1");
+                assert!(openai.api_key.is_none());
+                assert_eq!(openai.retry.max_attempts, 5);
+                assert_eq!(openai.retry.initial_backoff, Duration::from_millis(250));
+                assert_eq!(openai.retry.max_backoff, RetryPolicy::default().max_backoff);
+                assert_eq!(openai.timeouts.idle, Duration::from_secs(300));
+                assert_eq!(openai.timeouts.connect, Timeouts::default().connect);
+            }
+            other => panic!("unexpected provider: {:?}", other),
+        }
+
+        let (model, route) = config.resolve_model("claude-opus-4");
+        assert_eq!(model, "qwen3-coder");
+        assert_eq!(route.unwrap().provider.as_deref(), Some("local"));
+        // A route's own fallback chain replaces the global one
+        assert_eq!(
+            config.fallback_chain(route),
+            &[FallbackTarget::new(None, "gemini-2.5-flash")]
+        );
+        assert_eq!(
+            config.fallback_chain(None),
+            &[FallbackTarget::new(
+                Some("local".to_string()),
+                "qwen3-coder"
+            )]
+        );
+
+        let (model, route) = config.resolve_model("claude-3-5-haiku-20241022");
+        assert_eq!(model, "gemini-2.5-flash");
+        assert_eq!(route.unwrap().provider.as_deref(), Some("gemini"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_gemini_config_deserializes_with_shared_defaults() {
+        let gemini: GeminiConfig =
+            toml::from_str("api_key = \"key\"\nendpoint = \"example.com\"").unwrap();
+        assert_eq!(gemini.auto_todo_prompt, default_auto_todo_prompt());
+    }
+
+    #[test]
+    fn test_from_toml_reports_every_problem() {
+        let err = ProxyConfig::from_toml_str(
+            r#"
+            fallback = [{ provider = "nope", model = "gemini-2.5-flash" }]
+
+            [server]
+            workers = 0
+
+            [providers.gemini]
+            type = "gemini"
+            api_key_env = "CLAUDE_CODE_PROXY_TEST_UNSET_KEY"
+            retry = { max_attempts = 0 }
+
+            [providers.local]
+            type = "openai"
+            base_url = "localhost:8000"
+            prompt_cache = true
+            timeouts = { first_byte_secs = 0 }
+
+            [[routes]]
+            match = "exact"
+            pattern = "claude-opus-4"
+            target = "gpt-4o"
+            provider = "missing"
+            "#,
+            Path::new("."),
+        )
+        .unwrap_err()
+        .to_string();
+
+        for expected in [
+            "default_provider: Required when more than one provider is defined",
+            "providers.gemini.api_key: Environment variable CLAUDE_CODE_PROXY_TEST_UNSET_KEY is not set",
+            "providers.gemini.retry.max_attempts: Must be at least 1",
+            "providers.local.prompt_cache: Not supported",
+            "providers.local.timeouts.first_byte_secs: Timeout must be greater than 0",
+            "providers.local.base_url: Base URL must start with http:// or https://",
+            "routes[0].provider: Unknown provider 'missing'",
+            "fallback[0].provider: Unknown provider 'nope'",
+            "server.workers: Workers must be greater than 0",
+        ] {
+            assert!(err.contains(expected), "missing '{}' in: {}", expected, err);
+        }
+        // The unreadable key is reported once, not again as an empty key
+        assert_eq!(err.matches("providers.gemini.api_key").count(), 1);
+    }
+
+    #[test]
+    fn test_from_toml_rejects_unknown_keys() {
+        let err = ProxyConfig::from_toml_str(
+            r#"
+            [providers.gemini]
+            type = "gemini"
+            api_key = "k"
+            endpont = "typo.example.com"
+            "#,
+            Path::new("."),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("endpont"));
+    }
 }
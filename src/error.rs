@@ -1,3 +1,8 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -11,6 +16,10 @@ pub enum ProxyError {
     #[error("Transformation error: {0}")]
     TransformationError(String),
 
+    /// Request exceeds a backend size limit (e.g. Gemini's inline data limit)
+    #[error("Request too large: {0}")]
+    RequestTooLarge(String),
+
     #[error("Configuration error: {0}")]
     ConfigError(String),
 
@@ -20,6 +29,14 @@ pub enum ProxyError {
     #[error("Upstream error: {0}")]
     UpstreamError(String),
 
+    /// Upstream took too long to respond or went quiet mid-stream
+    #[error("Upstream timeout: {0}")]
+    UpstreamTimeout(String),
+
+    /// Upstream answered with a non-success HTTP status
+    #[error("Upstream error {status}: {message}")]
+    UpstreamStatus { status: u16, message: String },
+
     #[error("JSON error: {0}")]
     JsonError(#[from] serde_json::Error),
 
@@ -31,3 +48,229 @@ pub enum ProxyError {
 }
 
 pub type Result<T> = std::result::Result<T, ProxyError>;
+
+impl ProxyError {
+    /// Build an [`ProxyError::UpstreamStatus`] from a raw upstream error body
+    ///
+    /// Both Gemini and Anthropic-compatible backends wrap errors as
+    /// `{"error": {"message": ...}}`; the inner message is extracted when present.
+    pub fn upstream_status(provider: &str, status: u16, body: &str) -> Self {
+        let detail = serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .and_then(|v| v["error"]["message"].as_str().map(String::from))
+            .unwrap_or_else(|| body.to_string());
+
+        ProxyError::UpstreamStatus {
+            status,
+            message: format!("{} API error: {}", provider, detail),
+        }
+    }
+
+    /// HTTP status returned to the client for this error
+    ///
+    /// Upstream 429 and 529 are passed through so client retry logic kicks in;
+    /// Gemini's 503 (model overloaded) is reported as Anthropic's 529.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ProxyError::InvalidClaudeRequest(_) | ProxyError::TransformationError(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            ProxyError::RequestTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ProxyError::UpstreamStatus { status, .. } => match *status {
+                400 | 401 | 403 | 404 | 413 | 429 | 529 => {
+                    StatusCode::from_u16(*status).unwrap_or(StatusCode::BAD_GATEWAY)
+                }
+                503 => StatusCode::from_u16(529).unwrap_or(StatusCode::SERVICE_UNAVAILABLE),
+                _ => StatusCode::BAD_GATEWAY,
+            },
+            ProxyError::InvalidGeminiResponse(_) | ProxyError::UpstreamError(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+            ProxyError::UpstreamTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            ProxyError::ConfigError(_)
+            | ProxyError::InternalError(_)
+            | ProxyError::JsonError(_)
+            | ProxyError::IoError(_)
+            | ProxyError::EnvVarError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Whether another backend may succeed where this one failed
+    ///
+    /// True for rate limits and quota exhaustion (429), overload (503, 529),
+    /// requests that never reached the upstream and upstreams that never answered.
+    pub fn is_backend_unavailable(&self) -> bool {
+        match self {
+            ProxyError::UpstreamStatus { status, .. } => matches!(status, 429 | 503 | 529),
+            ProxyError::UpstreamError(_) | ProxyError::UpstreamTimeout(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Anthropic error type (`error.type`) for this error
+    pub fn error_type(&self) -> &'static str {
+        anthropic_error_type(self.status_code())
+    }
+
+    /// Message shown to the client in `error.message`
+    ///
+    /// Upstream errors already name the provider, so the status prefix is dropped.
+    pub fn client_message(&self) -> String {
+        match self {
+            ProxyError::UpstreamStatus { message, .. } => message.clone(),
+            other => other.to_string(),
+        }
+    }
+
+    /// Rebuild an error from an Anthropic error event (`error.type` + `error.message`)
+    pub fn from_error_event(error_type: &str, message: impl Into<String>) -> Self {
+        let status = match error_type {
+            "invalid_request_error" => 400,
+            "authentication_error" => 401,
+            "permission_error" => 403,
+            "not_found_error" => 404,
+            "request_too_large" => 413,
+            "rate_limit_error" => 429,
+            "overloaded_error" => 529,
+            _ => 502,
+        };
+        ProxyError::UpstreamStatus {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+/// Map an HTTP status to the matching Anthropic error type
+pub fn anthropic_error_type(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        400 => "invalid_request_error",
+        401 => "authentication_error",
+        403 => "permission_error",
+        404 => "not_found_error",
+        413 => "request_too_large",
+        429 => "rate_limit_error",
+        529 => "overloaded_error",
+        _ => "api_error",
+    }
+}
+
+impl IntoResponse for ProxyError {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        let body = serde_json::json!({
+            "type": "error",
+            "error": {
+                "type": anthropic_error_type(status),
+                "message": self.client_message()
+            }
+        });
+
+        (status, Json(body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_and_type_mapping() {
+        let cases = vec![
+            (
+                ProxyError::InvalidClaudeRequest("bad".into()),
+                400,
+                "invalid_request_error",
+            ),
+            (
+                ProxyError::TransformationError("bad role".into()),
+                400,
+                "invalid_request_error",
+            ),
+            (
+                ProxyError::RequestTooLarge("30 MB".into()),
+                413,
+                "request_too_large",
+            ),
+            (ProxyError::UpstreamError("reset".into()), 502, "api_error"),
+            (ProxyError::UpstreamTimeout("idle".into()), 504, "api_error"),
+            (ProxyError::InternalError("oops".into()), 500, "api_error"),
+            (
+                ProxyError::upstream_status("Gemini", 429, "quota"),
+                429,
+                "rate_limit_error",
+            ),
+            (
+                ProxyError::upstream_status("Kimi", 529, "busy"),
+                529,
+                "overloaded_error",
+            ),
+            (
+                ProxyError::upstream_status("Gemini", 503, "overloaded"),
+                529,
+                "overloaded_error",
+            ),
+            (
+                ProxyError::upstream_status("Gemini", 500, "internal"),
+                502,
+                "api_error",
+            ),
+        ];
+
+        for (err, status, error_type) in cases {
+            assert_eq!(err.status_code().as_u16(), status, "{}", err);
+            assert_eq!(err.error_type(), error_type, "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_upstream_status_extracts_message() {
+        let body = r#"{"error":{"code":429,"message":"Resource has been exhausted","status":"RESOURCE_EXHAUSTED"}}"#;
+        let err = ProxyError::upstream_status("Gemini", 429, body);
+        assert_eq!(
+            err.to_string(),
+            "Upstream error 429: Gemini API error: Resource has been exhausted"
+        );
+
+        let err = ProxyError::upstream_status("Gemini", 500, "plain text");
+        assert!(err.to_string().ends_with("plain text"));
+    }
+
+    #[test]
+    fn test_backend_unavailable() {
+        assert!(ProxyError::upstream_status("Gemini", 429, "quota").is_backend_unavailable());
+        assert!(ProxyError::upstream_status("Gemini", 503, "overloaded").is_backend_unavailable());
+        assert!(ProxyError::UpstreamError("connection refused".into()).is_backend_unavailable());
+        assert!(ProxyError::UpstreamTimeout("no response".into()).is_backend_unavailable());
+        assert!(!ProxyError::upstream_status("Gemini", 400, "bad").is_backend_unavailable());
+        assert!(!ProxyError::InvalidClaudeRequest("bad".into()).is_backend_unavailable());
+    }
+
+    #[test]
+    fn test_error_event_round_trip() {
+        let err = ProxyError::upstream_status("Gemini", 429, "quota");
+        let rebuilt = ProxyError::from_error_event(err.error_type(), err.client_message());
+        assert_eq!(rebuilt.status_code(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(rebuilt.to_string(), err.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_into_response_body() {
+        let response =
+            ProxyError::InvalidClaudeRequest("No messages provided".into()).into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["type"], "error");
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert!(
+            body["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("No messages provided")
+        );
+    }
+}
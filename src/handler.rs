@@ -1,39 +1,241 @@
+use arc_swap::ArcSwap;
 use axum::{
-    Json,
+    Json, Router,
     body::Body,
-    extract::State,
-    http::{Response, StatusCode},
+    extract::{DefaultBodyLimit, State, rejection::JsonRejection},
+    http::{HeaderMap, Response, StatusCode},
     response::IntoResponse,
+    routing::post,
 };
 use bytes::{BufMut, Bytes, BytesMut};
 use futures::{Stream, StreamExt};
+use std::collections::HashMap;
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::pin::Pin;
 use std::sync::Arc;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
+use crate::client::build_provider;
 use crate::config::{ProviderConfig, ProxyConfig};
+use crate::error::ProxyError;
+use crate::metrics::BACKEND_METRICS;
 use crate::models::claude::ClaudeRequest;
-use crate::provider::Provider;
-use crate::state::GLOBAL_STATE;
-use crate::streaming::{SSEEventGenerator, StreamingJsonParser};
-use crate::transform::{map_model_name, transform_request_with_state, validate_claude_request};
+use crate::prompt_cache::PromptCache;
+use crate::provider::{Provider, ProviderStream, WireFormat};
+use crate::state::{ConversationState, SESSION_HEADER, SessionStore, session_key};
+use crate::streaming::{
+    GeminiSseConverter, MessageAggregator, OpenAISseConverter, SSEEventGenerator, SseConverter,
+};
+use crate::transform::{openai, transform_request_with_state, validate_claude_request};
 use crate::validation::validate_tools;
 
-pub struct AppState {
-    pub provider: Arc<dyn Provider>,
+/// Configuration and the provider clients built from it
+///
+/// Swapped as a whole on reload; a request keeps the snapshot it started
+/// with, so in-flight streams finish on the old config.
+pub struct Backends {
     pub config: ProxyConfig,
+    /// Client for the default provider
+    pub provider: Arc<dyn Provider>,
+    /// Clients for the named providers in `config.providers`
+    pub providers: HashMap<String, Arc<dyn Provider>>,
+}
+
+impl Backends {
+    /// Build a client for every provider in `config`
+    pub fn from_config(config: ProxyConfig) -> crate::error::Result<Self> {
+        let provider = build_provider(&config.provider)?;
+        let providers = config
+            .providers
+            .iter()
+            .map(|(name, provider)| Ok((name.clone(), build_provider(provider)?)))
+            .collect::<crate::error::Result<_>>()?;
+        Ok(Self {
+            config,
+            provider,
+            providers,
+        })
+    }
+
+    /// Resolve the backend model through the routing table and log the decision
+    ///
+    /// The first target is the routed backend, followed by its fallback chain.
+    fn resolve_targets(&self, requested: &str) -> Vec<Target> {
+        let (model, route) = self.config.resolve_model(requested);
+        let primary = self.target(route.and_then(|route| route.provider.as_deref()), model);
+
+        match route {
+            Some(route) => info!(
+                "{}: Request for model: {} -> {} (route {})",
+                primary.provider.name(),
+                requested,
+                primary.model,
+                route
+            ),
+            None => info!(
+                "{}: Request for model: {} -> {} (no route matched, using default)",
+                primary.provider.name(),
+                requested,
+                primary.model
+            ),
+        }
+
+        let fallbacks = self
+            .config
+            .fallback_chain(route)
+            .iter()
+            .map(|step| self.target(step.provider.as_deref(), step.model.clone()));
+        std::iter::once(primary).chain(fallbacks).collect()
+    }
+
+    /// Target for a named provider (`None` for the default) and backend model
+    fn target(&self, name: Option<&str>, model: String) -> Target {
+        let (name, provider) = match name.and_then(|name| self.providers.get_key_value(name)) {
+            Some((name, provider)) => (name.as_str(), provider),
+            None => (self.config.default_provider.as_str(), &self.provider),
+        };
+        Target {
+            provider: provider.clone(),
+            config: self.config.provider_config(Some(name)).clone(),
+            backend: format!("{}/{}", name, model),
+            model,
+        }
+    }
+}
+
+pub struct AppState {
+    /// Live configuration, replaced by [`AppState::reload`]
+    pub backends: ArcSwap<Backends>,
+    /// Conversation state partitioned by session
+    pub sessions: SessionStore,
+    /// Gemini cached contents for `cache_control` prefixes
+    pub prompt_cache: PromptCache,
+}
+
+impl AppState {
+    pub fn new(backends: Backends, sessions: SessionStore) -> Self {
+        Self {
+            backends: ArcSwap::from_pointee(backends),
+            sessions,
+            prompt_cache: PromptCache::new(),
+        }
+    }
+
+    /// Snapshot of the live configuration
+    pub fn config(&self) -> Arc<Backends> {
+        self.backends.load_full()
+    }
+
+    /// Validate `config`, build its clients and make it live
+    ///
+    /// On error nothing changes. Settings bound at startup (listen address,
+    /// workers, body limit and state storage) are logged and keep their old values.
+    pub fn reload(&self, config: ProxyConfig) -> crate::error::Result<()> {
+        config.validate()?;
+        let backends = Backends::from_config(config)?;
+
+        let current = self.backends.load();
+        for setting in restart_only_changes(&current.config, &backends.config) {
+            warn!(
+                "Config reload: {} changed; restart the proxy to apply it",
+                setting
+            );
+        }
+
+        info!(
+            "Config reloaded: default provider '{}', {} named provider(s), {} route(s)",
+            backends.config.default_provider,
+            backends.providers.len(),
+            backends.config.routes.routes().len()
+        );
+        self.backends.store(Arc::new(backends));
+        Ok(())
+    }
+
+    /// Conversation state for the session a request belongs to
+    fn session_state(&self, headers: &HeaderMap, claude_req: &ClaudeRequest) -> ConversationState {
+        let header = headers
+            .get(SESSION_HEADER)
+            .and_then(|value| value.to_str().ok());
+        let key = session_key(header, claude_req);
+        tracing::debug!(session = %key, "Resolved session");
+        self.sessions.get(&key)
+    }
+}
+
+/// Settings that only take effect at startup and differ between `old` and `new`
+fn restart_only_changes(old: &ProxyConfig, new: &ProxyConfig) -> Vec<&'static str> {
+    [
+        (
+            "server.listen_addr",
+            old.server.listen_addr != new.server.listen_addr,
+        ),
+        ("server.workers", old.server.workers != new.server.workers),
+        (
+            "server.max_request_bytes",
+            old.server.max_request_bytes != new.server.max_request_bytes,
+        ),
+        ("state.dir", old.state_storage != new.state_storage),
+        ("state.ttl_secs", old.session_ttl != new.session_ttl),
+        (
+            "state.sweep_interval_secs",
+            old.state_sweep_interval != new.state_sweep_interval,
+        ),
+        (
+            "state.max_entries",
+            old.state_max_entries != new.state_max_entries,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(setting, changed)| changed.then_some(setting))
+    .collect()
+}
+
+/// Provider and backend model a request is sent to
+struct Target {
+    provider: Arc<dyn Provider>,
+    config: ProviderConfig,
+    model: String,
+    /// `<provider name>/<model>`, as reported in logs and metrics
+    backend: String,
+}
+
+/// Upstream stream opened on the first backend that accepted the request
+struct OpenedStream {
+    target: Target,
+    stream: ProviderStream,
+    cache_creation_tokens: u32,
+}
+
+/// Routes served by the proxy
+///
+/// `max_request_bytes` replaces axum's 2 MiB body limit; it must leave room for
+/// base64 attachments up to Gemini's inline data limit, or those requests are
+/// rejected before the clearer inline data check can run.
+pub fn router(state: Arc<AppState>, max_request_bytes: usize) -> Router {
+    Router::new()
+        .route("/v1/messages", post(handle_messages))
+        .route("/v1/messages/count_tokens", post(handle_count_tokens))
+        .layer(DefaultBodyLimit::max(max_request_bytes))
+        .with_state(state)
 }
 
 pub async fn handle_messages(
     State(state): State<Arc<AppState>>,
-    Json(claude_req): Json<ClaudeRequest>,
+    headers: HeaderMap,
+    payload: Result<Json<ClaudeRequest>, JsonRejection>,
 ) -> impl IntoResponse {
+    let claude_req = match parse_request(payload) {
+        Ok(req) => req,
+        Err(e) => return e.into_response(),
+    };
+    let conversation = state.session_state(&headers, &claude_req);
+    let backends = state.config();
+
     // Validate request
     if let Err(e) = validate_claude_request(&claude_req) {
         error!("Validation failed: {}", e);
-        return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+        return e.into_response();
     }
 
     // Validate tools if present
@@ -41,89 +243,59 @@ pub async fn handle_messages(
         && let Err(e) = validate_tools(tools)
     {
         error!("Tool validation failed: {}", e);
-        return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+        return e.into_response();
     }
 
-    let body: Bytes;
-    let target_model: String;
-    let needs_transformation = state.provider.needs_transformation();
-
-    if needs_transformation {
-        // For Gemini: Transform request
-        let mapped_model = map_model_name(&claude_req.model);
-        info!(
-            "{}: Request for model: {} -> {}",
-            state.provider.name(),
-            claude_req.model,
-            mapped_model
-        );
-        target_model = mapped_model.to_string();
-
-        // Get auto_todo_prompt flag from config
-        let auto_todo_prompt = match &state.config.provider {
-            ProviderConfig::Gemini(cfg) => cfg.auto_todo_prompt,
-            _ => false,
-        };
+    let stream_requested = claude_req.stream;
+    let targets = backends.resolve_targets(&claude_req.model);
 
-        // Transform to Gemini format with state tracking
-        let gemini_req = match transform_request_with_state(
-            claude_req,
-            Some(&*GLOBAL_STATE),
-            auto_todo_prompt,
-        ) {
-            Ok(req) => req,
-            Err(e) => {
-                error!("Transformation failed: {}", e);
-                return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
-            }
-        };
+    let OpenedStream {
+        target:
+            Target {
+                provider,
+                model: target_model,
+                ..
+            },
+        stream,
+        cache_creation_tokens,
+    } = match open_stream(&state, &backends, targets, claude_req, &conversation).await {
+        Ok(opened) => opened,
+        Err(e) => return e.into_response(),
+    };
 
-        // Serialize transformed request
-        body = match serde_json::to_vec(&gemini_req) {
-            Ok(b) => Bytes::from(b),
-            Err(e) => {
-                error!("Serialization failed: {}", e);
-                return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
-            }
-        };
+    let converter = sse_converter(
+        provider.wire_format(),
+        target_model,
+        conversation,
+        cache_creation_tokens,
+    );
 
-        info!("Transformed to {} bytes", body.len());
-    } else {
-        // For Kimi: Pure forwarding, no transformation needed
-        info!(
-            "{}: Pure forwarding for model: {}",
-            state.provider.name(),
-            claude_req.model
-        );
-        target_model = claude_req.model.clone();
+    // Non-streaming requests get a single Message JSON object
+    if !stream_requested {
+        if let Some(converter) = converter {
+            return match aggregate_message(stream, converter).await {
+                Ok(message) => Json(message).into_response(),
+                Err(e) => {
+                    error!("{} response aggregation failed: {}", provider.name(), e);
+                    e.into_response()
+                }
+            };
+        }
 
-        // Serialize original Claude request
-        body = match serde_json::to_vec(&claude_req) {
-            Ok(b) => Bytes::from(b),
-            Err(e) => {
-                error!("Serialization failed: {}", e);
-                return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
-            }
-        };
+        // Kimi honors `stream: false` itself and answers with JSON
+        let passthrough_stream = stream.map(|chunk| chunk.map_err(std::io::Error::other));
+        return Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(Body::from_stream(passthrough_stream))
+            .unwrap();
     }
 
-    // Make request to provider
-    let stream = match state
-        .provider
-        .stream_generate_content(&target_model, body)
-        .await
-    {
-        Ok(s) => s,
-        Err(e) => {
-            error!("{} request failed: {}", state.provider.name(), e);
-            return (StatusCode::BAD_GATEWAY, e.to_string()).into_response();
-        }
-    };
-
-    // For providers needing transformation, convert streaming JSON to SSE
+    // For providers needing transformation, convert the upstream stream to SSE
     // For Kimi (pure forwarding), just pass through the stream
-    if needs_transformation {
-        let sse_stream = transform_to_sse(stream, target_model);
+    if let Some(converter) = converter {
+        let log_path = format!("/tmp/{}.log", provider.name().to_lowercase());
+        let sse_stream = transform_to_sse(stream, converter, log_path);
         Response::builder()
             .status(StatusCode::OK)
             .header("Content-Type", "text/event-stream")
@@ -133,7 +305,7 @@ pub async fn handle_messages(
             .unwrap()
     } else {
         // Pure forwarding: pass through the stream as-is with logging
-        let passthrough_stream = stream.map(|chunk_result| {
+        let passthrough_stream = stream.map(move |chunk_result| {
             match chunk_result {
                 Ok(chunk) => {
                     // Log Kimi streaming response chunks
@@ -147,9 +319,16 @@ pub async fn handle_messages(
                         let _ = writeln!(file, "{}", chunk_str);
                         let _ = writeln!(file);
                     }
-                    Ok(chunk)
+                    Ok::<_, std::io::Error>(chunk)
+                }
+                // Read failures and idle timeouts end the stream with an error event
+                Err(e) => {
+                    error!("{} stream failed: {}", provider.name(), e);
+                    Ok(Bytes::from(SSEEventGenerator::format_error(
+                        e.error_type(),
+                        &e.client_message(),
+                    )))
                 }
-                Err(e) => Err(std::io::Error::other(e)),
             }
         });
 
@@ -163,60 +342,459 @@ pub async fn handle_messages(
     }
 }
 
-fn transform_to_sse(
-    stream: Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>,
+/// Send the request to the first target that accepts it
+///
+/// The next target in the chain is tried only when a backend is rate limited
+/// or unavailable. That is always decided before the upstream response starts,
+/// so nothing has been streamed to the client yet.
+async fn open_stream(
+    state: &AppState,
+    backends: &Backends,
+    targets: Vec<Target>,
+    claude_req: ClaudeRequest,
+    conversation: &ConversationState,
+) -> crate::error::Result<OpenedStream> {
+    let mut claude_req = Some(claude_req);
+    let mut targets = targets.into_iter().enumerate().peekable();
+
+    while let Some((attempt, target)) = targets.next() {
+        let next = targets.peek().map(|(_, next)| next.backend.clone());
+
+        // Keep the original for the next target; the last one takes it
+        let request = match next {
+            Some(_) => claude_req.clone(),
+            None => claude_req.take(),
+        }
+        .ok_or_else(|| {
+            ProxyError::InternalError("Request consumed before the last backend".into())
+        })?;
+
+        let (body, cache_creation_tokens) =
+            prepare_body(state, backends, &target, request, conversation).await?;
+        info!("Sending {} bytes to {}", body.len(), target.backend);
+
+        match target
+            .provider
+            .stream_generate_content(&target.model, body)
+            .await
+        {
+            Ok(stream) => {
+                if attempt > 0 {
+                    info!("Request served by fallback backend {}", target.backend);
+                }
+                BACKEND_METRICS.record_served(&target.backend, attempt > 0);
+                return Ok(OpenedStream {
+                    target,
+                    stream,
+                    cache_creation_tokens,
+                });
+            }
+            Err(e) => {
+                error!("{} request failed: {}", target.backend, e);
+                BACKEND_METRICS.record_failure(&target.backend);
+                match next {
+                    Some(next) if e.is_backend_unavailable() => {
+                        warn!("{} unavailable, falling back to {}", target.backend, next);
+                    }
+                    _ => return Err(e),
+                }
+            }
+        }
+    }
+
+    Err(ProxyError::InternalError("No backend configured".into()))
+}
+
+/// Translate the request for a target's wire format
+///
+/// Returns the serialized body and the number of prompt tokens written to a
+/// new Gemini cached content.
+async fn prepare_body(
+    state: &AppState,
+    backends: &Backends,
+    target: &Target,
+    mut claude_req: ClaudeRequest,
+    conversation: &ConversationState,
+) -> crate::error::Result<(Bytes, u32)> {
+    let provider = &target.provider;
+    let target_model = &target.model;
+
+    // Anthropic-compatible backends manage their own context window
+    if provider.needs_transformation() {
+        backends
+            .config
+            .context_strategy
+            .apply(&mut claude_req.messages);
+    }
+
+    let cache_ttl = PromptCache::requested_ttl(&claude_req);
+    let mut cache_creation_tokens = 0;
+
+    let body = match provider.wire_format() {
+        WireFormat::Gemini => {
+            // For Gemini: Transform request
+            // Get auto_todo_prompt and prompt_cache flags from config; cached
+            // contents are only visible to the endpoint and key that created them
+            let (auto_todo_prompt, prompt_cache, cache_scope) = match &target.config {
+                ProviderConfig::Gemini(cfg) => (
+                    cfg.auto_todo_prompt,
+                    cfg.prompt_cache,
+                    format!("{}\n{}", cfg.endpoint, cfg.api_key),
+                ),
+                _ => (false, false, String::new()),
+            };
+
+            // Transform to Gemini format with state tracking
+            let mut gemini_req =
+                transform_request_with_state(claude_req, Some(conversation), auto_todo_prompt)
+                    .inspect_err(|e| error!("Transformation failed: {}", e))?;
+
+            // Move a cache_control prefix into a Gemini cached content
+            if prompt_cache && let Some(ttl) = cache_ttl {
+                cache_creation_tokens = state
+                    .prompt_cache
+                    .apply(
+                        provider.as_ref(),
+                        &cache_scope,
+                        target_model,
+                        &mut gemini_req,
+                        ttl,
+                    )
+                    .await;
+            }
+            serde_json::to_vec(&gemini_req)
+        }
+        WireFormat::OpenAI => {
+            // For OpenAI-compatible servers: Transform to Chat Completions
+            let openai_req = openai::transform_request(claude_req, target_model)
+                .inspect_err(|e| error!("Transformation failed: {}", e))?;
+            serde_json::to_vec(&openai_req)
+        }
+        WireFormat::Anthropic => {
+            // For Kimi: Pure forwarding, no transformation needed
+            info!(
+                "{}: Pure forwarding for model: {}",
+                provider.name(),
+                target_model
+            );
+            claude_req.model = target_model.clone();
+            serde_json::to_vec(&claude_req)
+        }
+    };
+
+    // Serialize transformed (or original) request
+    let body = body.inspect_err(|e| error!("Serialization failed: {}", e))?;
+    Ok((Bytes::from(body), cache_creation_tokens))
+}
+
+/// Pick the SSE converter for a provider's wire format
+///
+/// Returns `None` for Claude-compatible providers whose stream is forwarded as-is.
+fn sse_converter(
+    format: WireFormat,
     model: String,
+    conversation: ConversationState,
+    cache_creation_tokens: u32,
+) -> Option<Box<dyn SseConverter>> {
+    match format {
+        WireFormat::Gemini => Some(Box::new(
+            GeminiSseConverter::new(model, conversation)
+                .with_cache_creation_tokens(cache_creation_tokens),
+        )),
+        WireFormat::OpenAI => Some(Box::new(OpenAISseConverter::new(model))),
+        WireFormat::Anthropic => None,
+    }
+}
+
+/// Turn axum's body rejection into an Anthropic-shaped error
+///
+/// Bodies over the size limit are `request_too_large` (413); everything else
+/// is a malformed request (400 `invalid_request_error`).
+fn parse_request(
+    payload: Result<Json<ClaudeRequest>, JsonRejection>,
+) -> crate::error::Result<ClaudeRequest> {
+    payload.map(|Json(req)| req).map_err(|rejection| {
+        error!("Request body rejected: {}", rejection.body_text());
+        if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            ProxyError::RequestTooLarge(rejection.body_text())
+        } else {
+            ProxyError::InvalidClaudeRequest(rejection.body_text())
+        }
+    })
+}
+
+/// Drain the upstream stream and fold the generated SSE events into one Message
+///
+/// Goes through the same converter as streaming mode so that content blocks,
+/// tool_use registration and stop reasons are identical.
+async fn aggregate_message(
+    mut stream: ProviderStream,
+    mut converter: Box<dyn SseConverter>,
+) -> crate::error::Result<serde_json::Value> {
+    let mut aggregator = MessageAggregator::new();
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+
+        for event in converter.feed(&chunk)? {
+            aggregator.push_event(&event);
+        }
+        if converter.has_failed() {
+            break;
+        }
+    }
+
+    aggregator.finish()
+}
+
+/// State threaded through [`transform_to_sse`]
+struct SseStream {
+    upstream: ProviderStream,
+    converter: Box<dyn SseConverter>,
+    log_path: String,
+    outgoing_events: BytesMut,
+    done: bool,
+}
+
+impl SseStream {
+    /// Convert one upstream chunk into SSE bytes
+    fn process_chunk(&mut self, chunk: &[u8]) -> Bytes {
+        // Log raw upstream response
+        if let Ok(mut file) = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.log_path)
+        {
+            let _ = writeln!(file, "\n=== RESPONSE CHUNK ===");
+            let _ = writeln!(file, "{}", String::from_utf8_lossy(chunk));
+        }
+
+        // Parse upstream chunks into SSE events
+        let events = match self.converter.feed(chunk) {
+            Ok(events) => events,
+            Err(e) => {
+                return self.fail(&ProxyError::InvalidGeminiResponse(format!(
+                    "Failed to parse upstream stream: {}",
+                    e
+                )));
+            }
+        };
+
+        for event in events {
+            if event.contains("message_start") || event.contains("message_stop") {
+                info!("SSE: {}", event.lines().next().unwrap_or(""));
+            }
+            // Log SSE output
+            if let Ok(mut file) = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.log_path)
+            {
+                let _ = writeln!(file, "\n=== SSE EVENT ===");
+                let _ = writeln!(file, "{}", event);
+            }
+            self.outgoing_events.put(event.as_bytes());
+        }
+
+        // An in-stream upstream error has been turned into an error event
+        if self.converter.has_failed() {
+            self.done = true;
+        }
+
+        self.outgoing_events.split().freeze()
+    }
+
+    /// Flush pending events followed by a terminal `error` event
+    fn fail(&mut self, err: &ProxyError) -> Bytes {
+        error!("Upstream stream failed: {}", err);
+        let event = self.converter.fail(err);
+        self.outgoing_events.put(event.as_bytes());
+        self.done = true;
+        self.outgoing_events.split().freeze()
+    }
+}
+
+/// Convert the upstream response stream into Claude SSE events
+///
+/// Upstream read or parse failures are reported as an SSE `error` event and the
+/// stream is then closed cleanly, so clients never see a silently truncated response.
+fn transform_to_sse(
+    stream: ProviderStream,
+    converter: Box<dyn SseConverter>,
+    log_path: String,
 ) -> impl Stream<Item = Result<Bytes, std::io::Error>> {
-    let mut parser = StreamingJsonParser::new();
-    let mut generator = SSEEventGenerator::with_state(model, GLOBAL_STATE.clone());
-    let mut outgoing_events = BytesMut::new();
-
-    stream.map(move |chunk_result| {
-        match chunk_result {
-            Ok(chunk) => {
-                // Log raw Gemini response
-                if let Ok(mut file) = OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open("/tmp/gemini.log")
-                {
-                    let _ = writeln!(file, "\n=== RESPONSE CHUNK ===");
-                    let _ = writeln!(file, "{}", String::from_utf8_lossy(&chunk));
-                }
+    let state = SseStream {
+        upstream: stream,
+        converter,
+        log_path,
+        outgoing_events: BytesMut::new(),
+        done: false,
+    };
+
+    futures::stream::unfold(state, |mut state| async move {
+        if state.done {
+            return None;
+        }
+
+        let bytes = match state.upstream.next().await {
+            Some(Ok(chunk)) => state.process_chunk(&chunk),
+            Some(Err(e)) => state.fail(&e),
+            None if state.converter.is_finished() || state.converter.has_failed() => {
+                return None;
+            }
+            None => state.fail(&ProxyError::UpstreamError(
+                "Upstream stream ended before the response completed".to_string(),
+            )),
+        };
+
+        Some((Ok(bytes), state))
+    })
+}
 
-                // Parse Gemini JSON chunks
-                let parsed_chunks = parser.feed(&chunk).map_err(|e| {
-                    std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
-                })?;
-
-                // Generate SSE events
-                for gemini_chunk in parsed_chunks {
-                    let events = generator.generate_events(gemini_chunk);
-                    for event in events {
-                        if event.contains("message_start") || event.contains("message_stop") {
-                            info!("SSE: {}", event.lines().next().unwrap_or(""));
-                        }
-                        // Log SSE output
-                        if let Ok(mut file) = OpenOptions::new()
-                            .create(true)
-                            .append(true)
-                            .open("/tmp/gemini.log")
-                        {
-                            let _ = writeln!(file, "\n=== SSE EVENT ===");
-                            let _ = writeln!(file, "{}", event);
-                        }
-                        outgoing_events.put(event.as_bytes());
+/// Handle `/v1/messages/count_tokens`
+///
+/// Gemini requests are transformed exactly like `/v1/messages` and sent to
+/// `countTokens`; Claude-compatible providers get the request forwarded as-is.
+pub async fn handle_count_tokens(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    payload: Result<Json<ClaudeRequest>, JsonRejection>,
+) -> impl IntoResponse {
+    let mut claude_req = match parse_request(payload) {
+        Ok(req) => req,
+        Err(e) => return e.into_response(),
+    };
+    let conversation = state.session_state(&headers, &claude_req);
+    let backends = state.config();
+
+    if let Err(e) = validate_claude_request(&claude_req) {
+        error!("Validation failed: {}", e);
+        return e.into_response();
+    }
+
+    let Target {
+        provider,
+        model: target_model,
+        ..
+    } = backends.resolve_targets(&claude_req.model).swap_remove(0);
+
+    let body = match provider.wire_format() {
+        WireFormat::Gemini => {
+            let gemini_req =
+                match transform_request_with_state(claude_req, Some(&conversation), false) {
+                    Ok(req) => req,
+                    Err(e) => {
+                        error!("Transformation failed: {}", e);
+                        return e.into_response();
                     }
+                };
+            serde_json::to_vec(&gemini_req).map(Bytes::from)
+        }
+        WireFormat::OpenAI => {
+            let openai_req = match openai::transform_request(claude_req, &target_model) {
+                Ok(req) => req,
+                Err(e) => {
+                    error!("Transformation failed: {}", e);
+                    return e.into_response();
                 }
+            };
+            serde_json::to_vec(&openai_req).map(Bytes::from)
+        }
+        WireFormat::Anthropic => {
+            claude_req.model = target_model.clone();
 
-                // Always return what we have (even if empty)
-                if !outgoing_events.is_empty() {
-                    Ok(outgoing_events.split().freeze())
-                } else {
-                    Ok(Bytes::new())
+            // count_tokens rejects generation-only fields
+            serde_json::to_value(&claude_req).map(|mut value| {
+                if let Some(obj) = value.as_object_mut() {
+                    obj.remove("stream");
+                    obj.remove("max_tokens");
                 }
-            }
-            Err(e) => Err(std::io::Error::other(e.to_string())),
+                Bytes::from(value.to_string())
+            })
         }
-    })
+    };
+
+    let body = match body {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Serialization failed: {}", e);
+            return ProxyError::from(e).into_response();
+        }
+    };
+
+    match provider.count_tokens(&target_model, body).await {
+        Ok(input_tokens) => {
+            info!(
+                "{}: count_tokens for {} -> {}",
+                provider.name(),
+                target_model,
+                input_tokens
+            );
+            Json(serde_json::json!({ "input_tokens": input_tokens })).into_response()
+        }
+        Err(e) => {
+            error!("{} count_tokens failed: {}", provider.name(), e);
+            e.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn collect_sse(chunks: Vec<&'static [u8]>) -> String {
+        let upstream = futures::stream::iter(chunks.into_iter().map(|c| Ok(Bytes::from(c))));
+        let converter = sse_converter(
+            WireFormat::Gemini,
+            "gemini-test".to_string(),
+            ConversationState::new(),
+            0,
+        )
+        .unwrap();
+        let output: Vec<_> = transform_to_sse(
+            Box::pin(upstream),
+            converter,
+            "/tmp/gemini-test.log".to_string(),
+        )
+        .collect()
+        .await;
+
+        output
+            .into_iter()
+            .map(|chunk| String::from_utf8(chunk.unwrap().to_vec()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_truncated_stream_ends_with_error_event() {
+        let sse = collect_sse(vec![
+            br#"[{"candidates":[{"content":{"parts":[{"text":"Hello"}],"role":"model"}}]}"#,
+        ])
+        .await;
+
+        assert!(sse.contains("text_delta"));
+        assert!(!sse.contains("message_stop"));
+        assert!(sse.trim_end().starts_with("event: message_start"));
+        assert!(sse.contains("event: error\ndata: "));
+        assert!(sse.ends_with("\n\n"));
+    }
+
+    #[tokio::test]
+    async fn test_in_stream_gemini_error_closes_stream() {
+        let sse = collect_sse(vec![
+            br#"[{"candidates":[{"content":{"parts":[{"text":"Hi"}],"role":"model"}}]},"#,
+            br#"{"error":{"code":503,"message":"The model is overloaded.","status":"UNAVAILABLE"}}]"#,
+        ])
+        .await;
+
+        let error_line = sse
+            .lines()
+            .find(|l| l.starts_with("data: ") && l.contains("\"error\""))
+            .unwrap();
+        let data: serde_json::Value =
+            serde_json::from_str(error_line.trim_start_matches("data: ")).unwrap();
+        assert_eq!(data["error"]["type"], "overloaded_error");
+        assert_eq!(sse.matches("event: error").count(), 1);
+    }
 }
@@ -37,7 +37,10 @@
 //! - [`config`] - Configuration loading and validation
 //! - [`error`] - Error types and handling
 //! - [`models`] - Data structures for Claude and Gemini APIs
+//! - [`prompt_cache`] - Gemini cached contents for `cache_control` prefixes
 //! - [`proxy`] - Pingora proxy implementation
+//! - [`reload`] - Config file reloading on change or SIGHUP
+//! - [`routing`] - Model routing table (exact, prefix and glob rules)
 //! - [`streaming`] - JSON parser and SSE event generator
 //! - [`transform`] - Request/response transformation logic
 
@@ -48,7 +51,10 @@ pub mod error;
 pub mod handler;
 pub mod metrics;
 pub mod models;
+pub mod prompt_cache;
 pub mod provider;
+pub mod reload;
+pub mod routing;
 pub mod state;
 pub mod streaming;
 pub mod transform;
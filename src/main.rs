@@ -1,20 +1,24 @@
-use axum::{Router, routing::post};
 use clap::{Parser, Subcommand};
 use claude_code_proxy::{
-    client::{GeminiClient, KimiClient},
     config::{ProviderConfig, ProxyConfig},
-    handler::{AppState, handle_messages},
-    provider::Provider,
+    handler::{AppState, Backends, router},
+    reload::{DEFAULT_WATCH_INTERVAL, spawn_config_reloader},
+    state::{SessionStore, StateStorage},
 };
+use std::path::PathBuf;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{error, info};
 
 #[derive(Parser)]
 #[command(name = "claude-code-proxy")]
 #[command(about = "Proxy Claude Code requests to various AI providers", long_about = None)]
 struct Cli {
+    /// Load providers, routes and server settings from a TOML file instead of the environment
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
 }
 
 #[derive(Subcommand)]
@@ -23,6 +27,8 @@ enum Commands {
     Gemini,
     /// Use Kimi as the backend provider (pure forwarding, Claude-compatible)
     Kimi,
+    /// Use an OpenAI Chat Completions compatible server (OpenAI, vLLM, llama.cpp, OpenRouter)
+    Openai,
 }
 
 #[tokio::main]
@@ -31,55 +37,150 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let cli = Cli::parse();
 
-    // Determine provider type from subcommand
-    let provider_type = match cli.command {
-        Commands::Gemini => "gemini",
-        Commands::Kimi => "kimi",
+    // Load configuration from --config, or from the environment for the subcommand's provider
+    let config = match (&cli.config, cli.command) {
+        (Some(path), command) => {
+            if command.is_some() {
+                info!("--config given, ignoring the provider subcommand");
+            }
+            info!("Loading configuration from {}", path.display());
+            ProxyConfig::from_file(path)?
+        }
+        (None, Some(command)) => {
+            let provider_type = match command {
+                Commands::Gemini => "gemini",
+                Commands::Kimi => "kimi",
+                Commands::Openai => "openai",
+            };
+            ProxyConfig::from_env(provider_type)?
+        }
+        (None, None) => {
+            return Err(
+                "Specify a provider subcommand (gemini, kimi, openai) or --config <FILE>".into(),
+            );
+        }
     };
-
-    // Load configuration
-    let config = ProxyConfig::from_env(provider_type)?;
     config.validate()?;
 
     // Create appropriate provider client
-    let provider: Arc<dyn Provider> = match &config.provider {
+    match &config.provider {
         ProviderConfig::Gemini(gemini_config) => {
             info!("Starting Claude-to-Gemini proxy...");
             info!("  Listen: {}", config.server.listen_addr);
             info!("  Gemini endpoint: {}", gemini_config.endpoint);
-            Arc::new(GeminiClient::new(gemini_config.clone())?)
         }
         ProviderConfig::Kimi(kimi_config) => {
             info!("Starting Claude-to-Kimi proxy...");
             info!("  Listen: {}", config.server.listen_addr);
             info!("  Kimi endpoint: {}", kimi_config.endpoint);
             info!("  Kimi model: {}", kimi_config.model);
-            Arc::new(KimiClient::new(kimi_config.clone())?)
         }
-    };
+        ProviderConfig::OpenAI(openai_config) => {
+            info!("Starting Claude-to-OpenAI proxy...");
+            info!("  Listen: {}", config.server.listen_addr);
+            info!("  OpenAI base URL: {}", openai_config.base_url);
+            info!("  OpenAI model: {}", openai_config.model);
+        }
+    }
+
+    // Named providers from the config file, selected per route
+    for (name, provider_config) in &config.providers {
+        info!("  Provider '{}': {}", name, provider_config.kind());
+    }
+    for route in config.routes.routes() {
+        info!("  Route: {}", route);
+    }
+    if !config.fallback.is_empty() {
+        let chain: Vec<_> = config
+            .fallback
+            .iter()
+            .map(|step| match &step.provider {
+                Some(provider) => format!("{}/{}", provider, step.model),
+                None => step.model.clone(),
+            })
+            .collect();
+        info!("  Fallback: {}", chain.join(" -> "));
+    }
+
+    let sessions = SessionStore::with_storage(config.session_ttl, config.state_storage.open()?)
+        .with_max_entries(config.state_max_entries);
+    if let StateStorage::Disk { dir } = &config.state_storage {
+        info!("  State dir: {}", dir.display());
+    }
+
+    // Optionally clear stale state from previous runs
+    if config.clear_state_on_startup {
+        sessions.clear();
+        claude_code_proxy::cache::TOOL_CACHE.clear();
+        claude_code_proxy::metrics::TOOL_METRICS.reset();
+        info!("  State cleared");
+    }
 
-    // Clear any stale state from previous runs
-    claude_code_proxy::state::GLOBAL_STATE.clear();
-    claude_code_proxy::cache::TOOL_CACHE.clear();
-    claude_code_proxy::metrics::TOOL_METRICS.reset();
-    info!("  State cleared");
+    sessions.spawn_sweeper(config.state_sweep_interval);
+    info!(
+        "  State sweep: every {}s, max {} entries",
+        config.state_sweep_interval.as_secs(),
+        config.state_max_entries
+    );
 
     // Create app state
-    let state = Arc::new(AppState {
-        provider,
-        config: config.clone(),
-    });
+    let state = Arc::new(AppState::new(
+        Backends::from_config(config.clone())?,
+        sessions.clone(),
+    ));
+
+    // With a config file, pick up edits and SIGHUP without dropping active streams
+    if let Some(path) = cli.config {
+        spawn_config_reloader(state.clone(), path, DEFAULT_WATCH_INTERVAL);
+        info!("  Config reload: on SIGHUP or file change");
+    }
 
     // Build router
-    let app = Router::new()
-        .route("/v1/messages", post(handle_messages))
-        .with_state(state);
+    let app = router(state, config.server.max_request_bytes);
 
     info!("Proxy ready!");
 
     // Start server
     let listener = tokio::net::TcpListener::bind(&config.server.listen_addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    // Last-used times are only written back on sweeps; keep them for the next start
+    sessions.flush();
+    info!("State flushed, shutting down");
 
     Ok(())
 }
+
+/// Resolve on Ctrl-C or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Cannot listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Cannot listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    info!("Shutdown signal received, finishing in-flight requests");
+}
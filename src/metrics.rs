@@ -1,3 +1,4 @@
+use dashmap::DashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
@@ -23,6 +24,12 @@ pub struct ToolMetrics {
 
     /// Total transformation time in microseconds
     pub total_transform_time_us: AtomicU64,
+
+    /// Tool mappings removed by the state sweeper (expired or over capacity)
+    pub state_entries_evicted: AtomicU64,
+
+    /// Idle sessions removed by the state sweeper
+    pub sessions_evicted: AtomicU64,
 }
 
 impl ToolMetrics {
@@ -55,11 +62,19 @@ impl ToolMetrics {
         self.state_lookup_failures.fetch_add(1, Ordering::Relaxed);
     }
 
+    /// Record the result of one state sweep
+    pub fn record_state_sweep(&self, sessions: usize, entries: usize) {
+        self.sessions_evicted
+            .fetch_add(sessions as u64, Ordering::Relaxed);
+        self.state_entries_evicted
+            .fetch_add(entries as u64, Ordering::Relaxed);
+    }
+
     /// Get average transformation time in microseconds
     pub fn avg_transform_time_us(&self) -> u64 {
         let total = self.total_transform_time_us.load(Ordering::Relaxed);
         let count = self.successful_transformations.load(Ordering::Relaxed);
-        if count > 0 { total / count } else { 0 }
+        total.checked_div(count).unwrap_or(0)
     }
 
     /// Get success rate as percentage
@@ -81,6 +96,8 @@ impl ToolMetrics {
             failed_transformations: self.failed_transformations.load(Ordering::Relaxed),
             tool_results_processed: self.tool_results_processed.load(Ordering::Relaxed),
             state_lookup_failures: self.state_lookup_failures.load(Ordering::Relaxed),
+            state_entries_evicted: self.state_entries_evicted.load(Ordering::Relaxed),
+            sessions_evicted: self.sessions_evicted.load(Ordering::Relaxed),
             avg_transform_time_us: self.avg_transform_time_us(),
             success_rate: self.success_rate(),
         }
@@ -94,6 +111,8 @@ impl ToolMetrics {
         self.tool_results_processed.store(0, Ordering::Relaxed);
         self.state_lookup_failures.store(0, Ordering::Relaxed);
         self.total_transform_time_us.store(0, Ordering::Relaxed);
+        self.state_entries_evicted.store(0, Ordering::Relaxed);
+        self.sessions_evicted.store(0, Ordering::Relaxed);
     }
 }
 
@@ -105,6 +124,8 @@ pub struct MetricsSnapshot {
     pub failed_transformations: u64,
     pub tool_results_processed: u64,
     pub state_lookup_failures: u64,
+    pub state_entries_evicted: u64,
+    pub sessions_evicted: u64,
     pub avg_transform_time_us: u64,
     pub success_rate: f64,
 }
@@ -113,19 +134,75 @@ impl std::fmt::Display for MetricsSnapshot {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Tool Metrics: {} calls ({:.1}% success), {} results, {} state failures, avg {:.2}ms",
+            "Tool Metrics: {} calls ({:.1}% success), {} results, {} state failures, {} state evictions, avg {:.2}ms",
             self.total_calls,
             self.success_rate,
             self.tool_results_processed,
             self.state_lookup_failures,
+            self.state_entries_evicted,
             self.avg_transform_time_us as f64 / 1000.0
         )
     }
 }
 
+/// Request outcomes per backend (`<provider>/<model>`)
+#[derive(Default)]
+pub struct BackendMetrics {
+    backends: DashMap<String, BackendCounts>,
+}
+
+/// Counters for one backend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BackendCounts {
+    /// Requests this backend answered
+    pub served: u64,
+    /// Of those, requests it answered as a fallback for another backend
+    pub served_as_fallback: u64,
+    /// Requests that failed before streaming started
+    pub failed: u64,
+}
+
+impl BackendMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the backend that ended up serving a request
+    pub fn record_served(&self, backend: &str, fallback: bool) {
+        let mut counts = self.backends.entry(backend.to_string()).or_default();
+        counts.served += 1;
+        if fallback {
+            counts.served_as_fallback += 1;
+        }
+    }
+
+    /// Record a backend that failed before streaming started
+    pub fn record_failure(&self, backend: &str) {
+        self.backends.entry(backend.to_string()).or_default().failed += 1;
+    }
+
+    /// Counters for every backend seen so far, sorted by name
+    pub fn snapshot(&self) -> Vec<(String, BackendCounts)> {
+        let mut backends: Vec<_> = self
+            .backends
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect();
+        backends.sort_by(|a, b| a.0.cmp(&b.0));
+        backends
+    }
+
+    pub fn reset(&self) {
+        self.backends.clear();
+    }
+}
+
 lazy_static::lazy_static! {
     /// Global metrics instance
     pub static ref TOOL_METRICS: ToolMetrics = ToolMetrics::new();
+
+    /// Global per-backend request metrics
+    pub static ref BACKEND_METRICS: BackendMetrics = BackendMetrics::new();
 }
 
 #[cfg(test)]
@@ -190,6 +267,18 @@ mod tests {
         assert_eq!(metrics.state_lookup_failures.load(Ordering::Relaxed), 1);
     }
 
+    #[test]
+    fn test_state_sweep() {
+        let metrics = ToolMetrics::new();
+
+        metrics.record_state_sweep(1, 4);
+        metrics.record_state_sweep(0, 2);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.sessions_evicted, 1);
+        assert_eq!(snapshot.state_entries_evicted, 6);
+    }
+
     #[test]
     fn test_snapshot() {
         let metrics = ToolMetrics::new();
@@ -240,6 +329,30 @@ mod tests {
         assert_eq!(metrics.total_calls.load(Ordering::Relaxed), 10);
     }
 
+    #[test]
+    fn test_backend_metrics() {
+        let metrics = BackendMetrics::new();
+
+        metrics.record_failure("gemini/gemini-3-pro-preview");
+        metrics.record_served("gemini/gemini-2.5-flash", true);
+        metrics.record_served("gemini/gemini-3-pro-preview", false);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot[0].0, "gemini/gemini-2.5-flash");
+        assert_eq!(snapshot[0].1.served_as_fallback, 1);
+        assert_eq!(
+            snapshot[1].1,
+            BackendCounts {
+                served: 1,
+                served_as_fallback: 0,
+                failed: 1
+            }
+        );
+
+        metrics.reset();
+        assert!(metrics.snapshot().is_empty());
+    }
+
     #[test]
     fn test_display_format() {
         let snapshot = MetricsSnapshot {
@@ -248,6 +361,8 @@ mod tests {
             failed_transformations: 5,
             tool_results_processed: 90,
             state_lookup_failures: 2,
+            state_entries_evicted: 7,
+            sessions_evicted: 1,
             avg_transform_time_us: 1500,
             success_rate: 95.0,
         };
@@ -256,6 +371,7 @@ mod tests {
         assert!(output.contains("100 calls"));
         assert!(output.contains("95.0% success"));
         assert!(output.contains("90 results"));
+        assert!(output.contains("7 state evictions"));
         assert!(output.contains("1.50ms"));
     }
 }
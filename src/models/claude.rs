@@ -41,6 +41,75 @@ pub struct ClaudeRequest {
     /// Tool definitions for function calling
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<ClaudeTool>>,
+
+    /// How the model should use the provided tools
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+
+    /// Extended thinking configuration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingConfig>,
+
+    /// Request metadata (Claude Code sets a per-session `user_id`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<RequestMetadata>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RequestMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+}
+
+/// Extended thinking configuration (`{"type": "enabled", "budget_tokens": N}`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ThinkingConfig {
+    Enabled { budget_tokens: u32 },
+    Disabled,
+}
+
+/// Tool choice (`{"type": "auto" | "any" | "tool" | "none"}`)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolChoice {
+    /// The model decides whether to call a tool
+    Auto {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        disable_parallel_tool_use: Option<bool>,
+    },
+    /// The model must call one of the tools
+    Any {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        disable_parallel_tool_use: Option<bool>,
+    },
+    /// The model must call the named tool
+    Tool {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        disable_parallel_tool_use: Option<bool>,
+    },
+    /// The model must not call tools
+    None,
+}
+
+impl ToolChoice {
+    /// Whether the client asked for at most one tool call per turn
+    pub fn disable_parallel_tool_use(&self) -> bool {
+        match self {
+            ToolChoice::Auto {
+                disable_parallel_tool_use,
+            }
+            | ToolChoice::Any {
+                disable_parallel_tool_use,
+            }
+            | ToolChoice::Tool {
+                disable_parallel_tool_use,
+                ..
+            } => disable_parallel_tool_use.unwrap_or(false),
+            ToolChoice::None => false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -65,6 +134,9 @@ pub enum ContentType {
 pub enum ContentBlock {
     Text {
         text: String,
+        /// Marks the end of a cacheable prompt prefix
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
     },
     ToolUse {
         id: String,
@@ -73,10 +145,117 @@ pub enum ContentBlock {
     },
     ToolResult {
         tool_use_id: String,
-        content: String,
+        #[serde(default)]
+        content: ToolResultContent,
         #[serde(skip_serializing_if = "Option::is_none")]
         is_error: Option<bool>,
     },
+    Image {
+        source: ImageSource,
+    },
+    Document {
+        source: DocumentSource,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        context: Option<String>,
+    },
+    /// Model reasoning; sent back by clients as part of assistant history
+    Thinking {
+        thinking: String,
+        #[serde(default)]
+        signature: String,
+    },
+    RedactedThinking {
+        data: String,
+    },
+}
+
+/// Content of a `tool_result` block: a plain string or an array of blocks
+///
+/// Tools such as Read (on images) or MCP servers return arrays mixing text
+/// and image blocks.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ToolResultContent {
+    Text(String),
+    Blocks(Vec<ContentBlock>),
+}
+
+impl Default for ToolResultContent {
+    fn default() -> Self {
+        ToolResultContent::Text(String::new())
+    }
+}
+
+impl From<String> for ToolResultContent {
+    fn from(text: String) -> Self {
+        ToolResultContent::Text(text)
+    }
+}
+
+impl From<&str> for ToolResultContent {
+    fn from(text: &str) -> Self {
+        ToolResultContent::Text(text.to_string())
+    }
+}
+
+impl ToolResultContent {
+    /// All text parts, joined with newlines
+    pub fn text(&self) -> String {
+        match self {
+            ToolResultContent::Text(text) => text.clone(),
+            ToolResultContent::Blocks(blocks) => blocks
+                .iter()
+                .filter_map(|b| match b {
+                    ContentBlock::Text { text, .. } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// Non-text blocks (images, documents) in order
+    pub fn attachments(&self) -> impl Iterator<Item = &ContentBlock> {
+        let blocks = match self {
+            ToolResultContent::Text(_) => &[][..],
+            ToolResultContent::Blocks(blocks) => blocks.as_slice(),
+        };
+        blocks
+            .iter()
+            .filter(|b| !matches!(b, ContentBlock::Text { .. }))
+    }
+}
+
+/// Image data for an `image` content block
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageSource {
+    /// Inline base64 data, e.g. a pasted screenshot
+    Base64 { media_type: String, data: String },
+    /// Publicly reachable image URL
+    Url { url: String },
+}
+
+/// Document data for a `document` content block
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DocumentSource {
+    /// Inline base64 data (`application/pdf`)
+    Base64 { media_type: String, data: String },
+    /// Plain text document
+    Text {
+        #[serde(default = "default_text_media_type")]
+        media_type: String,
+        data: String,
+    },
+    /// Publicly reachable PDF URL
+    Url { url: String },
+}
+
+fn default_text_media_type() -> String {
+    "text/plain".to_string()
 }
 
 /// Claude tool definition
@@ -85,12 +264,49 @@ pub struct ClaudeTool {
     pub name: String,
     pub description: String,
     pub input_schema: JsonSchema,
+    /// Marks the tool list up to this tool as cacheable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+/// Prompt caching marker (`{"type": "ephemeral", "ttl": "5m"}`)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CacheControl {
+    #[serde(rename = "type")]
+    pub cache_type: String,
+
+    /// `"5m"` (default) or `"1h"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<String>,
+}
+
+impl CacheControl {
+    /// Requested cache lifetime; unknown values fall back to the 5 minute default
+    pub fn ttl(&self) -> std::time::Duration {
+        const DEFAULT: std::time::Duration = std::time::Duration::from_secs(300);
+
+        let Some(ttl) = self.ttl.as_deref() else {
+            return DEFAULT;
+        };
+        let (value, unit) = ttl.split_at(ttl.len().saturating_sub(1));
+        let seconds = match (value.parse::<u64>(), unit) {
+            (Ok(value), "s") => value,
+            (Ok(value), "m") => value * 60,
+            (Ok(value), "h") => value * 3600,
+            _ => return DEFAULT,
+        };
+        std::time::Duration::from_secs(seconds)
+    }
 }
 
 /// JSON Schema definition (supports OpenAPI-compatible subset)
+///
+/// A `type` that is not a single string (e.g. `["string", "null"]`) or is
+/// missing leaves `schema_type` empty; an array `type` is kept in `additional`
+/// for [`crate::transform::schema::lower_schema`] to translate.
 #[derive(Debug, Clone, Deserialize, Default)]
+#[serde(from = "RawJsonSchema")]
 pub struct JsonSchema {
-    #[serde(rename = "type")]
     pub schema_type: String,
 
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -120,11 +336,128 @@ pub struct JsonSchema {
     pub pattern: Option<String>,
 
     // Catch-all for additional schema fields from Claude
-    // Note: We don't serialize these to Gemini (they're not supported)
-    #[serde(flatten, skip_serializing)]
+    // Note: Only the keywords in GEMINI_SCHEMA_KEYWORDS are serialized
     pub additional: HashMap<String, serde_json::Value>,
 }
 
+/// Wire form of [`JsonSchema`], accepting any `type` value
+#[derive(Deserialize)]
+struct RawJsonSchema {
+    #[serde(rename = "type", default)]
+    schema_type: Option<serde_json::Value>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    properties: Option<HashMap<String, Box<JsonSchema>>>,
+    #[serde(default)]
+    required: Option<Vec<String>>,
+    #[serde(rename = "enum", default)]
+    enum_values: Option<Vec<serde_json::Value>>,
+    #[serde(default)]
+    items: Option<Box<JsonSchema>>,
+    #[serde(default)]
+    minimum: Option<f64>,
+    #[serde(default)]
+    maximum: Option<f64>,
+    #[serde(default)]
+    pattern: Option<String>,
+    #[serde(flatten)]
+    additional: HashMap<String, serde_json::Value>,
+}
+
+impl From<RawJsonSchema> for JsonSchema {
+    fn from(raw: RawJsonSchema) -> Self {
+        let mut additional = raw.additional;
+        let schema_type = match raw.schema_type {
+            Some(serde_json::Value::String(schema_type)) => schema_type,
+            Some(other) => {
+                additional.insert("type".to_string(), other);
+                String::new()
+            }
+            None => String::new(),
+        };
+
+        JsonSchema {
+            schema_type,
+            description: raw.description,
+            properties: raw.properties,
+            required: raw.required,
+            enum_values: raw.enum_values,
+            items: raw.items,
+            minimum: raw.minimum,
+            maximum: raw.maximum,
+            pattern: raw.pattern,
+            additional,
+        }
+    }
+}
+
+impl JsonSchema {
+    /// The complete schema as JSON, including keywords Gemini does not accept
+    ///
+    /// Keys come out sorted, so the result is stable enough to hash.
+    pub fn to_json_schema(&self) -> serde_json::Value {
+        use serde_json::{Map, Value, json};
+
+        let mut map: Map<String, Value> = self
+            .additional
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        if !self.schema_type.is_empty() {
+            map.insert("type".to_string(), json!(self.schema_type));
+        }
+        if let Some(ref desc) = self.description {
+            map.insert("description".to_string(), json!(desc));
+        }
+        if let Some(ref props) = self.properties {
+            let props = props
+                .iter()
+                .map(|(name, schema)| (name.clone(), schema.to_json_schema()))
+                .collect();
+            map.insert("properties".to_string(), Value::Object(props));
+        }
+        if let Some(ref req) = self.required {
+            map.insert("required".to_string(), json!(req));
+        }
+        if let Some(ref enums) = self.enum_values {
+            map.insert("enum".to_string(), json!(enums));
+        }
+        if let Some(ref items) = self.items {
+            map.insert("items".to_string(), items.to_json_schema());
+        }
+        if let Some(min) = self.minimum {
+            map.insert("minimum".to_string(), json!(min));
+        }
+        if let Some(max) = self.maximum {
+            map.insert("maximum".to_string(), json!(max));
+        }
+        if let Some(ref pat) = self.pattern {
+            map.insert("pattern".to_string(), json!(pat));
+        }
+
+        Value::Object(map)
+    }
+}
+
+/// Keywords from [`JsonSchema::additional`] that Gemini's OpenAPI schema subset accepts
+pub const GEMINI_SCHEMA_KEYWORDS: &[&str] = &[
+    "nullable",
+    "format",
+    "title",
+    "default",
+    "example",
+    "anyOf",
+    "minItems",
+    "maxItems",
+    "minLength",
+    "maxLength",
+    "minProperties",
+    "maxProperties",
+    "propertyOrdering",
+];
+
 // Custom Serialize implementation to ensure only supported fields go to Gemini
 impl Serialize for JsonSchema {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -135,7 +468,9 @@ impl Serialize for JsonSchema {
 
         let mut map = serializer.serialize_map(None)?;
 
-        map.serialize_entry("type", &self.schema_type)?;
+        if !self.schema_type.is_empty() {
+            map.serialize_entry("type", &self.schema_type)?;
+        }
 
         if let Some(ref desc) = self.description {
             map.serialize_entry("description", desc)?;
@@ -161,6 +496,11 @@ impl Serialize for JsonSchema {
         if let Some(ref pat) = self.pattern {
             map.serialize_entry("pattern", pat)?;
         }
+        for keyword in GEMINI_SCHEMA_KEYWORDS {
+            if let Some(value) = self.additional.get(*keyword) {
+                map.serialize_entry(keyword, value)?;
+            }
+        }
 
         map.end()
     }
@@ -352,12 +692,95 @@ mod tests {
                 ..
             } => {
                 assert_eq!(tool_use_id, "toolu_123");
-                assert_eq!(content, "Sunny, 72°F");
+                assert_eq!(content.text(), "Sunny, 72°F");
             }
             _ => panic!("Expected ToolResult"),
         }
     }
 
+    #[test]
+    fn test_parse_structured_tool_result_block() {
+        let json = r#"{
+            "type": "tool_result",
+            "tool_use_id": "toolu_456",
+            "content": [
+                {"type": "text", "text": "Screenshot of the login page"},
+                {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo="}},
+                {"type": "text", "text": "Viewport: 1280x720"}
+            ]
+        }"#;
+
+        let block: ContentBlock = serde_json::from_str(json).unwrap();
+        let ContentBlock::ToolResult { content, .. } = block else {
+            panic!("Expected ToolResult");
+        };
+        assert_eq!(
+            content.text(),
+            "Screenshot of the login page\nViewport: 1280x720"
+        );
+        assert_eq!(content.attachments().count(), 1);
+
+        // Missing content is allowed
+        let block: ContentBlock =
+            serde_json::from_str(r#"{"type": "tool_result", "tool_use_id": "toolu_789"}"#).unwrap();
+        assert!(
+            matches!(block, ContentBlock::ToolResult { content, .. } if content.text().is_empty())
+        );
+    }
+
+    #[test]
+    fn test_parse_image_blocks() {
+        let json = r#"[
+            {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo="}},
+            {"type": "image", "source": {"type": "url", "url": "https://example.com/screenshot.png"}}
+        ]"#;
+
+        let blocks: Vec<ContentBlock> = serde_json::from_str(json).unwrap();
+        match &blocks[0] {
+            ContentBlock::Image {
+                source: ImageSource::Base64 { media_type, data },
+            } => {
+                assert_eq!(media_type, "image/png");
+                assert_eq!(data, "iVBORw0KGgo=");
+            }
+            _ => panic!("Expected base64 Image"),
+        }
+        assert!(matches!(
+            &blocks[1],
+            ContentBlock::Image {
+                source: ImageSource::Url { .. }
+            }
+        ));
+    }
+
+    #[test]
+    fn test_parse_document_blocks() {
+        let json = r#"[
+            {"type": "document", "source": {"type": "base64", "media_type": "application/pdf", "data": "JVBERi0="}, "title": "Spec"},
+            {"type": "document", "source": {"type": "text", "media_type": "text/plain", "data": "Plain notes"}}
+        ]"#;
+
+        let blocks: Vec<ContentBlock> = serde_json::from_str(json).unwrap();
+        match &blocks[0] {
+            ContentBlock::Document {
+                source: DocumentSource::Base64 { media_type, .. },
+                title,
+                ..
+            } => {
+                assert_eq!(media_type, "application/pdf");
+                assert_eq!(title.as_deref(), Some("Spec"));
+            }
+            _ => panic!("Expected base64 Document"),
+        }
+        assert!(matches!(
+            &blocks[1],
+            ContentBlock::Document {
+                source: DocumentSource::Text { .. },
+                ..
+            }
+        ));
+    }
+
     #[test]
     fn test_parse_request_with_tools() {
         let json = r#"{
@@ -22,6 +22,53 @@ pub struct GeminiRequest {
     /// Tool/function declarations
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<GeminiTool>>,
+
+    /// How the declared functions may be used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_config: Option<ToolConfig>,
+
+    /// Name of a cached prefix (`cachedContents/...`); when set, the system
+    /// instruction, tools and tool config live in the cache instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached_content: Option<String>,
+}
+
+/// Body of a `cachedContents.create` call
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateCachedContent {
+    /// `models/<model>`
+    pub model: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<GeminiSystemInstruction>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<GeminiTool>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_config: Option<ToolConfig>,
+
+    /// Duration such as `"300s"`
+    pub ttl: String,
+}
+
+/// A created cached content resource
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedContent {
+    /// Resource name, `cachedContents/<id>`
+    pub name: String,
+
+    #[serde(default)]
+    pub usage_metadata: Option<CachedContentUsage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedContentUsage {
+    #[serde(default)]
+    pub total_token_count: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,18 +92,36 @@ pub struct GeminiSystemInstruction {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum GeminiPart {
-    Text {
+    // Thought summary, returned when `includeThoughts` is set. Must come first
+    // since untagged parsing ignores unknown fields
+    Thought {
         text: String,
+        thought: bool,
+        #[serde(
+            rename = "thoughtSignature",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        thought_signature: Option<String>,
     },
-    // Gemini 3 Pro Preview includes thinking signature - we ignore it
+    // Gemini 3 Pro Preview includes thinking signature on text parts. Listed
+    // before `Text` so the signature isn't silently discarded when parsing
     TextWithThought {
         text: String,
         #[serde(rename = "thoughtSignature")]
         thought_signature: String,
     },
+    Text {
+        text: String,
+    },
     InlineData {
+        #[serde(rename = "inlineData")]
         inline_data: InlineData,
     },
+    FileData {
+        #[serde(rename = "fileData")]
+        file_data: FileData,
+    },
     // Function call WITH thought signature (Gemini 3 Pro)
     FunctionCallWithThought {
         #[serde(rename = "functionCall")]
@@ -98,6 +163,14 @@ pub struct InlineData {
     pub data: String, // base64
 }
 
+/// Reference to file content by URI (used for image URLs)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileData {
+    pub mime_type: String,
+    pub file_uri: String,
+}
+
 /// Tool declaration wrapper
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -114,6 +187,31 @@ pub struct GeminiFunctionDeclaration {
     pub parameters: crate::models::claude::JsonSchema,
 }
 
+/// Tool configuration (`toolConfig`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolConfig {
+    pub function_calling_config: FunctionCallingConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionCallingConfig {
+    pub mode: FunctionCallingMode,
+
+    /// Restricts `ANY` mode to these functions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_function_names: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FunctionCallingMode {
+    Auto,
+    Any,
+    None,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GenerationConfig {
@@ -131,6 +229,21 @@ pub struct GenerationConfig {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stop_sequences: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking_config: Option<GeminiThinkingConfig>,
+}
+
+/// Thinking configuration (Gemini 2.5+ / 3)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiThinkingConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking_budget: Option<u32>,
+
+    /// Return thought summaries as `thought: true` parts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_thoughts: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -152,6 +265,35 @@ pub struct GeminiStreamChunk {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub prompt_feedback: Option<PromptFeedback>,
+
+    /// Error object Gemini may emit inside the array stream after a 200 OK
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<GeminiError>,
+}
+
+/// Gemini API error (`{"code": 429, "message": ..., "status": "RESOURCE_EXHAUSTED"}`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GeminiError {
+    #[serde(default)]
+    pub code: u16,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+impl GeminiError {
+    /// Convert into a [`ProxyError`](crate::error::ProxyError) carrying the upstream status
+    pub fn to_proxy_error(&self) -> crate::error::ProxyError {
+        let message = match &self.status {
+            Some(status) => format!("Gemini API error ({}): {}", status, self.message),
+            None => format!("Gemini API error: {}", self.message),
+        };
+        crate::error::ProxyError::UpstreamStatus {
+            status: self.code,
+            message,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -176,6 +318,10 @@ pub struct UsageMetadata {
     pub prompt_token_count: Option<u32>,
     pub candidates_token_count: Option<u32>,
     pub total_token_count: Option<u32>,
+    /// Tokens spent on thinking; billed as output but not part of candidates
+    pub thoughts_token_count: Option<u32>,
+    /// Prompt tokens served from cached content (included in `prompt_token_count`)
+    pub cached_content_token_count: Option<u32>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -211,9 +357,12 @@ mod tests {
                 top_p: None,
                 top_k: None,
                 stop_sequences: None,
+                thinking_config: None,
             }),
             safety_settings: None,
             tools: None,
+            tool_config: None,
+            cached_content: None,
         };
 
         let json = serde_json::to_string(&req).unwrap();
@@ -260,6 +409,49 @@ mod tests {
         assert_eq!(chunk.candidates[0].finish_reason.as_ref().unwrap(), "STOP");
     }
 
+    #[test]
+    fn test_parse_thought_part() {
+        let json = r#"{
+            "candidates": [{
+                "content": {
+                    "parts": [
+                        {"text": "**Planning** the answer", "thought": true},
+                        {"text": "Paris", "thoughtSignature": "c2lnLTE="}
+                    ],
+                    "role": "model"
+                }
+            }],
+            "usageMetadata": {"candidatesTokenCount": 3, "thoughtsTokenCount": 40}
+        }"#;
+
+        let chunk: GeminiStreamChunk = serde_json::from_str(json).unwrap();
+        let parts = &chunk.candidates[0].content.as_ref().unwrap().parts;
+        assert!(matches!(
+            &parts[0],
+            GeminiPart::Thought { thought: true, .. }
+        ));
+        assert!(matches!(&parts[1], GeminiPart::TextWithThought { .. }));
+        assert_eq!(chunk.usage_metadata.unwrap().thoughts_token_count, Some(40));
+    }
+
+    #[test]
+    fn test_parse_gemini_error_chunk() {
+        let json = r#"{
+            "error": {
+                "code": 503,
+                "message": "The model is overloaded. Please try again later.",
+                "status": "UNAVAILABLE"
+            }
+        }"#;
+
+        let chunk: GeminiStreamChunk = serde_json::from_str(json).unwrap();
+        assert!(chunk.candidates.is_empty());
+
+        let err = chunk.error.unwrap().to_proxy_error();
+        assert_eq!(err.error_type(), "overloaded_error");
+        assert!(err.to_string().contains("UNAVAILABLE"));
+    }
+
     #[test]
     fn test_serialize_gemini_request_with_tools() {
         use crate::models::claude::JsonSchema;
@@ -297,6 +489,8 @@ mod tests {
                     },
                 }],
             }]),
+            tool_config: None,
+            cached_content: None,
         };
 
         let json = serde_json::to_string(&req).unwrap();
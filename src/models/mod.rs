@@ -1,5 +1,6 @@
 pub mod claude;
 pub mod gemini;
+pub mod openai;
 
 pub use claude::*;
 pub use gemini::*;
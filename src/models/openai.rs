@@ -0,0 +1,293 @@
+use serde::{Deserialize, Serialize};
+
+/// OpenAI Chat Completions request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatCompletionRequest {
+    pub model: String,
+
+    pub messages: Vec<ChatMessage>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+
+    #[serde(default)]
+    pub stream: bool,
+
+    /// Ask for a final usage chunk when streaming
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ChatTool>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ChatToolChoice>,
+
+    /// `false` limits the model to one tool call per turn
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+}
+
+/// `"auto"`, `"required"`, `"none"`, or a specific function
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ChatToolChoice {
+    Mode(String),
+    Function {
+        #[serde(rename = "type")]
+        choice_type: String,
+        function: ToolChoiceFunction,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolChoiceFunction {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamOptions {
+    pub include_usage: bool,
+}
+
+/// A single chat message (`system`, `user`, `assistant` or `tool`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+
+    /// Assistant messages that only carry tool calls send `null` content
+    pub content: Option<ChatContent>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+
+    /// Set on `tool` messages to link the result to its call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn text(role: &str, content: impl Into<String>) -> Self {
+        Self {
+            role: role.to_string(),
+            content: Some(ChatContent::Text(content.into())),
+            tool_calls: None,
+            tool_call_id: None,
+        }
+    }
+}
+
+/// Message content: a plain string, or typed parts when images are attached
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ChatContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+impl ChatContent {
+    /// Get the content if it is plain text
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            ChatContent::Text(text) => Some(text),
+            ChatContent::Parts(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+    File { file: FileContent },
+}
+
+/// Image reference; base64 images are sent as `data:` URLs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageUrl {
+    pub url: String,
+}
+
+/// Inline file (PDF) sent as a `data:` URL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileContent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    pub file_data: String,
+}
+
+/// Tool call made by the assistant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub call_type: String,
+    pub function: FunctionCall,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    /// JSON-encoded arguments
+    pub arguments: String,
+}
+
+/// Tool definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatTool {
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    pub function: FunctionDefinition,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+/// One `data:` chunk of a streaming Chat Completions response
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChatCompletionChunk {
+    #[serde(default)]
+    pub id: Option<String>,
+
+    #[serde(default)]
+    pub choices: Vec<ChunkChoice>,
+
+    /// Only present on the final chunk when `stream_options.include_usage` is set
+    #[serde(default)]
+    pub usage: Option<ChatUsage>,
+
+    /// Some OpenAI-compatible servers (e.g. OpenRouter) report errors mid-stream
+    #[serde(default)]
+    pub error: Option<ChatError>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChunkChoice {
+    #[serde(default)]
+    pub index: u32,
+
+    #[serde(default)]
+    pub delta: ChunkDelta,
+
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ChunkDelta {
+    #[serde(default)]
+    pub content: Option<String>,
+
+    #[serde(default)]
+    pub tool_calls: Option<Vec<ToolCallDelta>>,
+}
+
+/// Incremental tool call; `id` and `name` arrive first, `arguments` in fragments
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ToolCallDelta {
+    #[serde(default)]
+    pub index: u32,
+
+    #[serde(default)]
+    pub id: Option<String>,
+
+    #[serde(default)]
+    pub function: Option<FunctionCallDelta>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct FunctionCallDelta {
+    #[serde(default)]
+    pub name: Option<String>,
+
+    #[serde(default)]
+    pub arguments: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChatUsage {
+    #[serde(default)]
+    pub prompt_tokens: u32,
+
+    #[serde(default)]
+    pub completion_tokens: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChatError {
+    #[serde(default)]
+    pub message: String,
+
+    /// Numeric HTTP-like code on OpenRouter, string code on OpenAI
+    #[serde(default)]
+    pub code: Option<serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tool_call_chunk() {
+        let json = r#"{
+            "id": "chatcmpl-1",
+            "object": "chat.completion.chunk",
+            "choices": [{
+                "index": 0,
+                "delta": {
+                    "tool_calls": [{
+                        "index": 0,
+                        "id": "call_abc",
+                        "type": "function",
+                        "function": {"name": "get_weather", "arguments": ""}
+                    }]
+                },
+                "finish_reason": null
+            }]
+        }"#;
+
+        let chunk: ChatCompletionChunk = serde_json::from_str(json).unwrap();
+        let calls = chunk.choices[0].delta.tool_calls.as_ref().unwrap();
+        assert_eq!(calls[0].id.as_deref(), Some("call_abc"));
+        assert_eq!(
+            calls[0].function.as_ref().unwrap().name.as_deref(),
+            Some("get_weather")
+        );
+    }
+
+    #[test]
+    fn test_serialize_assistant_tool_call_message() {
+        let message = ChatMessage {
+            role: "assistant".to_string(),
+            content: None,
+            tool_calls: Some(vec![ToolCall {
+                id: "call_1".to_string(),
+                call_type: "function".to_string(),
+                function: FunctionCall {
+                    name: "get_weather".to_string(),
+                    arguments: r#"{"location":"Paris"}"#.to_string(),
+                },
+            }]),
+            tool_call_id: None,
+        };
+
+        let json = serde_json::to_value(&message).unwrap();
+        assert!(json["content"].is_null());
+        assert_eq!(json["tool_calls"][0]["type"], "function");
+        assert!(json.get("tool_call_id").is_none());
+    }
+}
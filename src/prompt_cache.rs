@@ -0,0 +1,480 @@
+use bytes::Bytes;
+use dashmap::DashMap;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::models::claude::{ClaudeRequest, ContentBlock, SystemPrompt};
+use crate::models::gemini::{CreateCachedContent, GeminiRequest};
+use crate::provider::Provider;
+
+/// Prefixes shorter than this are not worth caching (and Gemini rejects them)
+pub const MIN_CACHE_TOKENS: usize = 1024;
+
+/// Entries this close to expiry are recreated rather than referenced
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// How long a prefix is sent inline after creating its cache failed
+const FAILURE_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Remembered prefixes; the entries expiring soonest make room beyond this
+pub const DEFAULT_MAX_ENTRIES: usize = 1024;
+
+/// SHA-256 of the provider scope, the model and the serialized system
+/// instruction, tools and tool config
+type PrefixKey = [u8; 32];
+
+#[derive(Debug, Clone)]
+enum CacheEntry {
+    Ready {
+        name: String,
+        expires_at: Instant,
+    },
+    /// Creation failed; don't retry before `until`
+    Failed {
+        until: Instant,
+    },
+}
+
+impl CacheEntry {
+    /// When the entry stops being useful
+    fn deadline(&self) -> Instant {
+        match self {
+            CacheEntry::Ready { expires_at, .. } => *expires_at,
+            CacheEntry::Failed { until } => *until,
+        }
+    }
+}
+
+/// Maps Claude `cache_control` prefixes onto Gemini `cachedContents`
+///
+/// The stable part of a request (system instruction, tools and tool config) is
+/// hashed; the first request with a given prefix creates a cached content with
+/// the TTL the client asked for, and later requests reference it by name
+/// instead of resending the prefix. Two concurrent first requests may both
+/// create a cache; the later one simply wins.
+///
+/// Cached contents belong to the API key that created them, so every key is
+/// scoped to the provider (see [`PromptCache::apply`]).
+///
+/// Expired entries are dropped when looked up and whenever the map is full;
+/// beyond `max_entries` the entries expiring soonest are evicted.
+#[derive(Clone)]
+pub struct PromptCache {
+    entries: Arc<DashMap<PrefixKey, CacheEntry>>,
+    max_entries: usize,
+}
+
+impl Default for PromptCache {
+    fn default() -> Self {
+        Self {
+            entries: Arc::default(),
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+}
+
+impl PromptCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries.max(1);
+        self
+    }
+
+    /// Longest TTL among `cache_control` markers on system blocks and tools
+    ///
+    /// Returns `None` when the client did not ask for caching.
+    pub fn requested_ttl(request: &ClaudeRequest) -> Option<Duration> {
+        let system = match &request.system {
+            Some(SystemPrompt::Blocks(blocks)) => blocks
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::Text { cache_control, .. } => cache_control.as_ref(),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let tools = request
+            .tools
+            .iter()
+            .flatten()
+            .filter_map(|tool| tool.cache_control.as_ref());
+
+        system.into_iter().chain(tools).map(|cc| cc.ttl()).max()
+    }
+
+    /// Move the request's prefix into a cached content if possible
+    ///
+    /// `scope` identifies the account the cache is created under (endpoint and
+    /// API key); caches are only reused within the same scope. On success
+    /// `request.cached_content` is set and the cached fields are cleared.
+    /// Returns the number of tokens written to a new cache, which is 0 when an
+    /// existing cache was reused or nothing was cached.
+    pub async fn apply(
+        &self,
+        provider: &dyn Provider,
+        scope: &str,
+        model: &str,
+        request: &mut GeminiRequest,
+        ttl: Duration,
+    ) -> u32 {
+        let Some((key, size)) = prefix_key(scope, model, request) else {
+            return 0;
+        };
+        if size / 4 < MIN_CACHE_TOKENS {
+            tracing::debug!(bytes = size, "Prompt prefix too small to cache");
+            return 0;
+        }
+
+        let now = Instant::now();
+        match self.entries.get(&key).map(|entry| entry.value().clone()) {
+            Some(CacheEntry::Ready { name, expires_at }) if expires_at > now + EXPIRY_MARGIN => {
+                tracing::debug!(cache = %name, "Reusing cached prompt prefix");
+                use_cache(request, name);
+                return 0;
+            }
+            Some(CacheEntry::Failed { until }) if until > now => return 0,
+            Some(_) => {
+                self.entries.remove(&key);
+            }
+            None => {}
+        }
+
+        let create = CreateCachedContent {
+            model: format!("models/{}", model),
+            system_instruction: request.system_instruction.clone(),
+            tools: request.tools.clone(),
+            tool_config: request.tool_config.clone(),
+            ttl: format!("{}s", ttl.as_secs()),
+        };
+        let body = match serde_json::to_vec(&create) {
+            Ok(body) => Bytes::from(body),
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to serialize cached content");
+                return 0;
+            }
+        };
+
+        match provider.create_cached_content(body).await {
+            Ok(cached) => {
+                let tokens = cached
+                    .usage_metadata
+                    .and_then(|usage| usage.total_token_count)
+                    .unwrap_or_default();
+                tracing::info!(cache = %cached.name, tokens, ttl_secs = ttl.as_secs(), "Created cached prompt prefix");
+                self.insert(
+                    key,
+                    CacheEntry::Ready {
+                        name: cached.name.clone(),
+                        expires_at: now + ttl,
+                    },
+                );
+                use_cache(request, cached.name);
+                tokens
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to create cached content, sending prefix inline");
+                self.insert(
+                    key,
+                    CacheEntry::Failed {
+                        until: now + FAILURE_BACKOFF,
+                    },
+                );
+                0
+            }
+        }
+    }
+
+    /// Remember an entry, making room first if the map is full
+    fn insert(&self, key: PrefixKey, entry: CacheEntry) {
+        if self.entries.len() >= self.max_entries && !self.entries.contains_key(&key) {
+            self.prune();
+            while self.entries.len() >= self.max_entries {
+                let soonest = self
+                    .entries
+                    .iter()
+                    .min_by_key(|entry| entry.value().deadline())
+                    .map(|entry| *entry.key());
+                match soonest {
+                    Some(soonest) => self.entries.remove(&soonest),
+                    None => break,
+                };
+            }
+        }
+        self.entries.insert(key, entry);
+    }
+
+    /// Drop expired caches and lapsed failures; returns how many were removed
+    pub fn prune(&self) -> usize {
+        let now = Instant::now();
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| entry.deadline() > now);
+        before.saturating_sub(self.entries.len())
+    }
+
+    /// Number of remembered prefixes, including failed ones
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&self) {
+        self.entries.clear();
+    }
+}
+
+/// Hash of the cacheable prefix and its serialized size; `None` if there is none
+fn prefix_key(scope: &str, model: &str, request: &GeminiRequest) -> Option<(PrefixKey, usize)> {
+    if request.system_instruction.is_none() && request.tools.is_none() {
+        return None;
+    }
+
+    let parts = [
+        serde_json::to_vec(&request.system_instruction).ok()?,
+        serde_json::to_vec(&request.tools).ok()?,
+        serde_json::to_vec(&request.tool_config).ok()?,
+    ];
+
+    let mut hasher = Sha256::new();
+    hasher.update(scope.as_bytes());
+    hasher.update([0]);
+    hasher.update(model.as_bytes());
+    for part in &parts {
+        hasher.update([0]);
+        hasher.update(part);
+    }
+    let size = parts.iter().map(Vec::len).sum();
+    Some((hasher.finalize().into(), size))
+}
+
+fn use_cache(request: &mut GeminiRequest, name: String) {
+    request.cached_content = Some(name);
+    request.system_instruction = None;
+    request.tools = None;
+    request.tool_config = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ProxyError, Result};
+    use crate::models::gemini::{CachedContent, GeminiPart, GeminiSystemInstruction};
+    use crate::provider::{CachedContentFuture, CountTokensFuture, StreamFuture, WireFormat};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct MockProvider {
+        creates: AtomicUsize,
+        fail: bool,
+    }
+
+    impl Provider for MockProvider {
+        fn stream_generate_content(&self, _model: &str, _body: Bytes) -> StreamFuture {
+            Box::pin(async { Err(ProxyError::UpstreamError("unused".into())) })
+        }
+
+        fn count_tokens(&self, _model: &str, _body: Bytes) -> CountTokensFuture {
+            Box::pin(async { Ok(0) })
+        }
+
+        fn create_cached_content(&self, body: Bytes) -> CachedContentFuture {
+            let n = self.creates.fetch_add(1, Ordering::SeqCst);
+            let fail = self.fail;
+            Box::pin(async move {
+                let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                assert_eq!(body["model"], "models/gemini-test");
+                assert_eq!(body["ttl"], "3600s");
+                if fail {
+                    return Err(ProxyError::UpstreamError("too small".into()));
+                }
+                let cached: Result<CachedContent> = serde_json::from_value(serde_json::json!({
+                    "name": format!("cachedContents/{}", n),
+                    "usageMetadata": {"totalTokenCount": 2048}
+                }))
+                .map_err(ProxyError::from);
+                cached
+            })
+        }
+
+        fn wire_format(&self) -> WireFormat {
+            WireFormat::Gemini
+        }
+
+        fn name(&self) -> &str {
+            "Mock"
+        }
+    }
+
+    fn gemini_request(system: &str) -> GeminiRequest {
+        GeminiRequest {
+            contents: vec![],
+            system_instruction: Some(GeminiSystemInstruction {
+                parts: vec![GeminiPart::Text {
+                    text: system.to_string(),
+                }],
+            }),
+            generation_config: None,
+            safety_settings: None,
+            tools: None,
+            tool_config: None,
+            cached_content: None,
+        }
+    }
+
+    #[test]
+    fn test_requested_ttl_takes_longest_marker() {
+        let request: ClaudeRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4",
+            "messages": [{"role": "user", "content": "hi"}],
+            "system": [
+                {"type": "text", "text": "a", "cache_control": {"type": "ephemeral"}},
+                {"type": "text", "text": "b"}
+            ],
+            "tools": [{
+                "name": "t",
+                "description": "d",
+                "input_schema": {"type": "object"},
+                "cache_control": {"type": "ephemeral", "ttl": "1h"}
+            }]
+        }))
+        .unwrap();
+        assert_eq!(
+            PromptCache::requested_ttl(&request),
+            Some(Duration::from_secs(3600))
+        );
+
+        let plain: ClaudeRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4",
+            "messages": [{"role": "user", "content": "hi"}],
+            "system": "plain"
+        }))
+        .unwrap();
+        assert_eq!(PromptCache::requested_ttl(&plain), None);
+    }
+
+    #[tokio::test]
+    async fn test_prefix_is_created_once_then_reused() {
+        let cache = PromptCache::new();
+        let provider = MockProvider::default();
+        let ttl = Duration::from_secs(3600);
+        let system = "x".repeat(8 * MIN_CACHE_TOKENS);
+
+        let mut first = gemini_request(&system);
+        let created = cache
+            .apply(&provider, "scope", "gemini-test", &mut first, ttl)
+            .await;
+        assert_eq!(created, 2048);
+        assert_eq!(first.cached_content.as_deref(), Some("cachedContents/0"));
+        assert!(first.system_instruction.is_none());
+
+        let mut second = gemini_request(&system);
+        let created = cache
+            .apply(&provider, "scope", "gemini-test", &mut second, ttl)
+            .await;
+        assert_eq!(created, 0);
+        assert_eq!(second.cached_content.as_deref(), Some("cachedContents/0"));
+
+        // A different prefix gets its own cache
+        let mut other = gemini_request(&format!("{}y", system));
+        cache
+            .apply(&provider, "scope", "gemini-test", &mut other, ttl)
+            .await;
+        assert_eq!(other.cached_content.as_deref(), Some("cachedContents/1"));
+        assert_eq!(provider.creates.load(Ordering::SeqCst), 2);
+
+        // So does the same prefix under another provider's API key
+        let mut elsewhere = gemini_request(&system);
+        cache
+            .apply(&provider, "other-scope", "gemini-test", &mut elsewhere, ttl)
+            .await;
+        assert_eq!(
+            elsewhere.cached_content.as_deref(),
+            Some("cachedContents/2")
+        );
+        assert_eq!(provider.creates.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_small_or_failed_prefix_is_sent_inline() {
+        let cache = PromptCache::new();
+        let provider = MockProvider {
+            fail: true,
+            ..Default::default()
+        };
+        let ttl = Duration::from_secs(3600);
+
+        let mut small = gemini_request("short");
+        cache
+            .apply(&provider, "scope", "gemini-test", &mut small, ttl)
+            .await;
+        assert!(small.cached_content.is_none());
+        assert_eq!(provider.creates.load(Ordering::SeqCst), 0);
+
+        let system = "x".repeat(8 * MIN_CACHE_TOKENS);
+        for _ in 0..2 {
+            let mut request = gemini_request(&system);
+            cache
+                .apply(&provider, "scope", "gemini-test", &mut request, ttl)
+                .await;
+            assert!(request.cached_content.is_none());
+            assert!(request.system_instruction.is_some());
+        }
+        // The failure is remembered instead of retried on every request
+        assert_eq!(provider.creates.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failures_back_off_briefly_and_map_is_bounded() {
+        let cache = PromptCache::new().with_max_entries(2);
+        let provider = MockProvider::default();
+        let ttl = Duration::from_secs(3600);
+        let system = "x".repeat(8 * MIN_CACHE_TOKENS);
+
+        for suffix in ["a", "b", "c"] {
+            let mut request = gemini_request(&format!("{}{}", system, suffix));
+            cache
+                .apply(&provider, "scope", "gemini-test", &mut request, ttl)
+                .await;
+        }
+        assert_eq!(cache.len(), 2);
+
+        // A failure is retried after a short backoff, not after the cache TTL
+        let failing = MockProvider {
+            fail: true,
+            ..Default::default()
+        };
+        let mut request = gemini_request(&format!("{}d", system));
+        cache
+            .apply(&failing, "scope", "gemini-test", &mut request, ttl)
+            .await;
+        let key = prefix_key(
+            "scope",
+            "gemini-test",
+            &gemini_request(&format!("{}d", system)),
+        )
+        .unwrap()
+        .0;
+        match cache.entries.get(&key).unwrap().value() {
+            CacheEntry::Failed { until } => assert!(*until <= Instant::now() + FAILURE_BACKOFF),
+            other => panic!("unexpected entry: {:?}", other),
+        }
+        assert_eq!(cache.len(), 2);
+
+        // Lapsed entries are pruned
+        cache.entries.insert(
+            key,
+            CacheEntry::Failed {
+                until: Instant::now(),
+            },
+        );
+        assert_eq!(cache.prune(), 1);
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -3,14 +3,34 @@ use futures::Stream;
 use std::future::Future;
 use std::pin::Pin;
 
-use crate::error::Result;
+use crate::error::{ProxyError, Result};
+use crate::models::gemini::CachedContent;
 
 /// Type alias for the streaming response from a provider
-pub type ProviderStream = Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>;
+///
+/// An `Err` item (read failure or idle timeout) is always the last one.
+pub type ProviderStream = Pin<Box<dyn Stream<Item = Result<Bytes>> + Send>>;
 
 /// Type alias for the future returned by stream_generate_content
 pub type StreamFuture = Pin<Box<dyn Future<Output = Result<ProviderStream>> + Send>>;
 
+/// Type alias for the future returned by count_tokens
+pub type CountTokensFuture = Pin<Box<dyn Future<Output = Result<u32>> + Send>>;
+
+/// Type alias for the future returned by create_cached_content
+pub type CachedContentFuture = Pin<Box<dyn Future<Output = Result<CachedContent>> + Send>>;
+
+/// Request/response format spoken by a provider's API
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    /// Google GenerateContent (JSON array stream)
+    Gemini,
+    /// Claude Messages API (requests forwarded as-is)
+    Anthropic,
+    /// OpenAI Chat Completions (`data:` SSE stream)
+    OpenAI,
+}
+
 /// Trait for AI provider clients that support streaming content generation
 pub trait Provider: Send + Sync {
     /// Stream generate content from the provider
@@ -23,10 +43,42 @@ pub trait Provider: Send + Sync {
     /// A stream of bytes from the provider's response
     fn stream_generate_content(&self, model: &str, body: Bytes) -> StreamFuture;
 
+    /// Count the input tokens of a request without generating content
+    ///
+    /// # Arguments
+    /// * `model` - The model name to use
+    /// * `body` - The request body as bytes (same format as `stream_generate_content`)
+    ///
+    /// # Returns
+    /// The number of input tokens reported by the provider
+    fn count_tokens(&self, model: &str, body: Bytes) -> CountTokensFuture;
+
+    /// Store a prompt prefix server-side so later requests can reference it
+    ///
+    /// # Arguments
+    /// * `body` - A serialized [`crate::models::gemini::CreateCachedContent`]
+    ///
+    /// Only Gemini supports this; other providers return an error.
+    fn create_cached_content(&self, body: Bytes) -> CachedContentFuture {
+        let _ = body;
+        let name = self.name().to_string();
+        Box::pin(async move {
+            Err(ProxyError::UpstreamError(format!(
+                "{} does not support cached content",
+                name
+            )))
+        })
+    }
+
+    /// Wire format this provider expects requests in and streams responses back in
+    fn wire_format(&self) -> WireFormat;
+
     /// Whether this provider needs request transformation
-    /// Returns true for providers like Gemini that need Claude->Gemini transformation
+    /// Returns true for providers like Gemini or OpenAI that need translation
     /// Returns false for providers like Kimi that are Claude-compatible
-    fn needs_transformation(&self) -> bool;
+    fn needs_transformation(&self) -> bool {
+        self.wire_format() != WireFormat::Anthropic
+    }
 
     /// Get the provider name for logging
     fn name(&self) -> &str;
@@ -0,0 +1,170 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+use crate::config::ProxyConfig;
+use crate::error::Result;
+use crate::handler::AppState;
+
+/// How often the config file's modification time is checked
+pub const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Re-read the config file and make it live
+///
+/// A file that fails to parse or validate is rejected and the current
+/// configuration stays in place.
+pub fn reload_from_file(state: &AppState, path: &Path) -> Result<()> {
+    let config = ProxyConfig::from_file(path)?;
+    state.reload(config)
+}
+
+/// Reload the config file on SIGHUP or whenever it changes on disk
+pub fn spawn_config_reloader(
+    state: Arc<AppState>,
+    path: PathBuf,
+    interval: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut hangup = Hangup::new();
+        let mut last_seen = fingerprint(&path);
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+
+        loop {
+            let trigger = tokio::select! {
+                _ = ticker.tick() => {
+                    let current = fingerprint(&path);
+                    if current == last_seen {
+                        continue;
+                    }
+                    last_seen = current;
+                    "file change"
+                }
+                _ = hangup.recv() => "SIGHUP",
+            };
+
+            info!("Reloading {} ({})", path.display(), trigger);
+            if let Err(e) = reload_from_file(&state, &path) {
+                error!("Config reload rejected, keeping the current config: {}", e);
+            }
+        }
+    })
+}
+
+/// Modification time and size, to notice edits without reading the file
+fn fingerprint(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// SIGHUP listener; never fires on platforms without it
+struct Hangup {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl Hangup {
+    fn new() -> Self {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{SignalKind, signal};
+            let signal = signal(SignalKind::hangup())
+                .map_err(|e| error!("Cannot listen for SIGHUP: {}", e))
+                .ok();
+            Self { signal }
+        }
+        #[cfg(not(unix))]
+        Self {}
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = &mut self.signal {
+            signal.recv().await;
+            return;
+        }
+        std::future::pending::<()>().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::Backends;
+    use crate::state::SessionStore;
+
+    const CONFIG: &str = r#"
+        [providers.gemini]
+        type = "gemini"
+        api_key = "key"
+
+        [[routes]]
+        match = "exact"
+        pattern = "claude-opus-4"
+        target = "gemini-2.5-pro"
+    "#;
+
+    fn write_config(dir: &Path, text: &str) -> PathBuf {
+        let path = dir.join("proxy.toml");
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_reload_swaps_config_and_keeps_snapshots() {
+        let dir = std::env::temp_dir().join(format!("proxy-reload-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = write_config(&dir, CONFIG);
+
+        let config = ProxyConfig::from_file(&path).unwrap();
+        let state = AppState::new(
+            Backends::from_config(config).unwrap(),
+            SessionStore::default(),
+        );
+        let in_flight = state.config();
+
+        write_config(&dir, &CONFIG.replace("gemini-2.5-pro", "gemini-2.5-flash"));
+        reload_from_file(&state, &path).unwrap();
+
+        assert_eq!(
+            state.config().config.resolve_model("claude-opus-4").0,
+            "gemini-2.5-flash"
+        );
+        // A request that started before the reload still sees the old routes
+        assert_eq!(
+            in_flight.config.resolve_model("claude-opus-4").0,
+            "gemini-2.5-pro"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_reload_keeps_current_config() {
+        let dir = std::env::temp_dir().join(format!("proxy-reload-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = write_config(&dir, CONFIG);
+
+        let config = ProxyConfig::from_file(&path).unwrap();
+        let state = AppState::new(
+            Backends::from_config(config).unwrap(),
+            SessionStore::default(),
+        );
+
+        write_config(&dir, &CONFIG.replace("api_key = \"key\"", "api_key = \"\""));
+        let err = reload_from_file(&state, &path).unwrap_err();
+        assert!(err.to_string().contains("providers.gemini.api_key"));
+
+        write_config(&dir, "not = [valid");
+        assert!(reload_from_file(&state, &path).is_err());
+
+        assert_eq!(
+            state.config().config.resolve_model("claude-opus-4").0,
+            "gemini-2.5-pro"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
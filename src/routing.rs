@@ -0,0 +1,263 @@
+use serde::Deserialize;
+use std::fmt;
+
+use crate::error::{ProxyError, Result};
+
+/// How a route pattern is compared against the requested model name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchKind {
+    /// Model name must equal the pattern
+    Exact,
+    /// Model name must start with the pattern
+    Prefix,
+    /// Pattern may contain `*` (any run of characters) and `?` (any single character)
+    Glob,
+}
+
+/// A single model routing rule
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ModelRoute {
+    #[serde(rename = "match")]
+    pub kind: MatchKind,
+    pub pattern: String,
+    /// Backend model name to use when this rule matches
+    pub target: String,
+    /// Named provider serving this route; the default provider when unset
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Backends to try, in order, when the target is rate limited or unavailable
+    #[serde(default)]
+    pub fallback: Vec<FallbackTarget>,
+}
+
+/// One step of a fallback chain
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FallbackTarget {
+    /// Named provider; the default provider when unset
+    #[serde(default)]
+    pub provider: Option<String>,
+    pub model: String,
+}
+
+impl FallbackTarget {
+    pub fn new(provider: Option<String>, model: impl Into<String>) -> Self {
+        Self {
+            provider,
+            model: model.into(),
+        }
+    }
+}
+
+impl ModelRoute {
+    pub fn new(kind: MatchKind, pattern: impl Into<String>, target: impl Into<String>) -> Self {
+        Self {
+            kind,
+            pattern: pattern.into(),
+            target: target.into(),
+            provider: None,
+            fallback: Vec::new(),
+        }
+    }
+
+    /// Build a rule from a pattern, inferring the match kind
+    ///
+    /// Patterns without wildcards are exact matches, a single trailing `*`
+    /// makes a prefix match, and any other use of `*` or `?` makes a glob.
+    pub fn from_pattern(pattern: &str, target: impl Into<String>) -> Self {
+        let wildcards = pattern.matches(['*', '?']).count();
+        if wildcards == 0 {
+            Self::new(MatchKind::Exact, pattern, target)
+        } else if wildcards == 1 && pattern.ends_with('*') {
+            Self::new(MatchKind::Prefix, &pattern[..pattern.len() - 1], target)
+        } else {
+            Self::new(MatchKind::Glob, pattern, target)
+        }
+    }
+
+    /// Send requests matching this rule to the named provider
+    pub fn with_provider(mut self, provider: impl Into<String>) -> Self {
+        self.provider = Some(provider.into());
+        self
+    }
+
+    /// Check whether this rule applies to the given model name
+    pub fn matches(&self, model: &str) -> bool {
+        match self.kind {
+            MatchKind::Exact => model == self.pattern,
+            MatchKind::Prefix => model.starts_with(&self.pattern),
+            MatchKind::Glob => glob_match(self.pattern.as_bytes(), model.as_bytes()),
+        }
+    }
+}
+
+impl fmt::Display for ModelRoute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            MatchKind::Exact => "exact",
+            MatchKind::Prefix => "prefix",
+            MatchKind::Glob => "glob",
+        };
+        write!(f, "{}:{} -> {}", kind, self.pattern, self.target)?;
+        if let Some(provider) = &self.provider {
+            write!(f, " @{}", provider)?;
+        }
+        Ok(())
+    }
+}
+
+/// Routing table mapping Claude model names to backend models
+///
+/// Rules are evaluated by specificity rather than declaration order:
+/// exact matches win, then the longest matching prefix, then the first
+/// matching glob. Requests that match nothing fall back to the provider's
+/// default model.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModelRouter {
+    routes: Vec<ModelRoute>,
+}
+
+impl ModelRouter {
+    pub fn new(routes: Vec<ModelRoute>) -> Self {
+        Self { routes }
+    }
+
+    /// Parse a compact rule list such as `claude-*haiku*=gemini-2.5-flash,claude-opus*=gemini-3-pro-preview`
+    ///
+    /// Match kinds are inferred as in [`ModelRoute::from_pattern`].
+    pub fn from_spec(spec: &str) -> Result<Self> {
+        let mut routes = Vec::new();
+
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (pattern, target) = entry
+                .split_once('=')
+                .map(|(p, t)| (p.trim(), t.trim()))
+                .filter(|(p, t)| !p.is_empty() && !t.is_empty())
+                .ok_or_else(|| {
+                    ProxyError::ConfigError(format!(
+                        "Invalid model route '{}': expected <pattern>=<target>",
+                        entry
+                    ))
+                })?;
+
+            routes.push(ModelRoute::from_pattern(pattern, target));
+        }
+
+        Ok(Self { routes })
+    }
+
+    /// Find the rule that applies to a model name, if any
+    pub fn resolve(&self, model: &str) -> Option<&ModelRoute> {
+        let matching = || self.routes.iter().filter(|r| r.matches(model));
+
+        matching()
+            .find(|r| r.kind == MatchKind::Exact)
+            .or_else(|| {
+                matching()
+                    .filter(|r| r.kind == MatchKind::Prefix)
+                    .max_by_key(|r| r.pattern.len())
+            })
+            .or_else(|| matching().find(|r| r.kind == MatchKind::Glob))
+    }
+
+    /// Get all configured rules
+    pub fn routes(&self) -> &[ModelRoute] {
+        &self.routes
+    }
+
+    /// Check if no rules are configured
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+}
+
+/// Match `text` against a glob pattern supporting `*` and `?`
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` in the pattern and the text index it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            // Let the last `*` swallow one more character and retry
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&b| b == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"claude-*haiku*", b"claude-3-5-haiku-20241022"));
+        assert!(glob_match(b"claude-?-opus", b"claude-3-opus"));
+        assert!(glob_match(b"*", b""));
+        assert!(!glob_match(b"claude-*haiku", b"claude-3-haiku-20240307"));
+        assert!(!glob_match(b"claude-?-opus", b"claude-35-opus"));
+    }
+
+    #[test]
+    fn test_from_spec_infers_match_kind() {
+        let router = ModelRouter::from_spec(
+            "claude-3-opus-20240229=gemini-3-pro-preview, claude-sonnet*=gemini-2.5-pro,claude-*haiku*=gemini-2.5-flash",
+        )
+        .unwrap();
+
+        let kinds: Vec<_> = router.routes().iter().map(|r| r.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![MatchKind::Exact, MatchKind::Prefix, MatchKind::Glob]
+        );
+        assert_eq!(router.routes()[1].pattern, "claude-sonnet");
+    }
+
+    #[test]
+    fn test_from_spec_rejects_malformed_entries() {
+        assert!(ModelRouter::from_spec("claude-opus").is_err());
+        assert!(ModelRouter::from_spec("=gemini-2.5-pro").is_err());
+        assert!(ModelRouter::from_spec("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_precedence() {
+        let router = ModelRouter::new(vec![
+            ModelRoute::new(MatchKind::Glob, "claude-*", "glob-target"),
+            ModelRoute::new(MatchKind::Prefix, "claude-3", "short-prefix"),
+            ModelRoute::new(MatchKind::Prefix, "claude-3-haiku", "long-prefix"),
+            ModelRoute::new(MatchKind::Exact, "claude-3-haiku-20240307", "exact"),
+        ]);
+
+        assert_eq!(
+            router.resolve("claude-3-haiku-20240307").unwrap().target,
+            "exact"
+        );
+        assert_eq!(
+            router.resolve("claude-3-haiku-latest").unwrap().target,
+            "long-prefix"
+        );
+        assert_eq!(
+            router.resolve("claude-3-opus").unwrap().target,
+            "short-prefix"
+        );
+        assert_eq!(
+            router.resolve("claude-opus-4").unwrap().target,
+            "glob-target"
+        );
+        assert!(router.resolve("gpt-4o").is_none());
+    }
+}
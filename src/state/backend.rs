@@ -0,0 +1,220 @@
+use super::ToolCallMetadata;
+use crate::error::{ProxyError, Result};
+use dashmap::DashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Storage for one session's tool call mappings, keyed by `tool_use_id`
+///
+/// Backends are best-effort: a failed write is logged rather than failing the
+/// request, since a missing mapping only degrades to the id-based fallbacks.
+pub trait StateBackend: Send + Sync {
+    fn get(&self, tool_use_id: &str) -> Option<ToolCallMetadata>;
+
+    fn insert(&self, tool_use_id: &str, metadata: &ToolCallMetadata);
+
+    /// Remove a mapping, returning whether it existed
+    fn remove(&self, tool_use_id: &str) -> bool;
+
+    /// Snapshot of every mapping
+    fn entries(&self) -> Vec<(String, ToolCallMetadata)>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn clear(&self);
+}
+
+/// In-memory backend; mappings are lost when the proxy exits
+#[derive(Default)]
+pub struct MemoryBackend {
+    entries: DashMap<String, ToolCallMetadata>,
+}
+
+impl StateBackend for MemoryBackend {
+    fn get(&self, tool_use_id: &str) -> Option<ToolCallMetadata> {
+        self.entries
+            .get(tool_use_id)
+            .map(|entry| entry.value().clone())
+    }
+
+    fn insert(&self, tool_use_id: &str, metadata: &ToolCallMetadata) {
+        self.entries
+            .insert(tool_use_id.to_string(), metadata.clone());
+    }
+
+    fn remove(&self, tool_use_id: &str) -> bool {
+        self.entries.remove(tool_use_id).is_some()
+    }
+
+    fn entries(&self) -> Vec<(String, ToolCallMetadata)> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn clear(&self) {
+        self.entries.clear();
+    }
+}
+
+/// On-disk backend: one sled tree per session, entries stored as JSON
+///
+/// Mappings and thought signatures survive a proxy restart, so a session can
+/// continue its tool loop across an upgrade.
+pub struct DiskBackend {
+    tree: sled::Tree,
+}
+
+impl DiskBackend {
+    fn decode(key: &[u8], value: &[u8]) -> Option<(String, ToolCallMetadata)> {
+        let id = String::from_utf8(key.to_vec()).ok()?;
+        match serde_json::from_slice(value) {
+            Ok(metadata) => Some((id, metadata)),
+            Err(e) => {
+                tracing::warn!(tool_use_id = %id, error = %e, "Skipping unreadable state entry");
+                None
+            }
+        }
+    }
+}
+
+impl StateBackend for DiskBackend {
+    fn get(&self, tool_use_id: &str) -> Option<ToolCallMetadata> {
+        match self.tree.get(tool_use_id) {
+            Ok(value) => value.and_then(|value| Self::decode(tool_use_id.as_bytes(), &value)),
+            Err(e) => {
+                tracing::warn!(tool_use_id, error = %e, "Failed to read state entry");
+                None
+            }
+        }
+        .map(|(_, metadata)| metadata)
+    }
+
+    fn insert(&self, tool_use_id: &str, metadata: &ToolCallMetadata) {
+        let result = serde_json::to_vec(metadata)
+            .map_err(|e| e.to_string())
+            .and_then(|value| {
+                self.tree
+                    .insert(tool_use_id, value)
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            tracing::warn!(tool_use_id, error = %e, "Failed to persist state entry");
+        }
+    }
+
+    fn remove(&self, tool_use_id: &str) -> bool {
+        matches!(self.tree.remove(tool_use_id), Ok(Some(_)))
+    }
+
+    fn entries(&self) -> Vec<(String, ToolCallMetadata)> {
+        self.tree
+            .iter()
+            .filter_map(|item| item.ok())
+            .filter_map(|(key, value)| Self::decode(&key, &value))
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    fn clear(&self) {
+        if let Err(e) = self.tree.clear() {
+            tracing::warn!(error = %e, "Failed to clear persisted state");
+        }
+    }
+}
+
+/// Where conversation state is kept
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum StateStorage {
+    /// Process memory (default)
+    #[default]
+    Memory,
+    /// Embedded store under `dir`
+    Disk { dir: PathBuf },
+}
+
+impl StateStorage {
+    /// Open the storage, creating the on-disk store if needed
+    pub fn open(&self) -> Result<Storage> {
+        match self {
+            StateStorage::Memory => Ok(Storage::Memory),
+            StateStorage::Disk { dir } => {
+                let db = sled::open(dir).map_err(|e| {
+                    ProxyError::ConfigError(format!(
+                        "Failed to open state store at {}: {}",
+                        dir.display(),
+                        e
+                    ))
+                })?;
+                Ok(Storage::Disk(db))
+            }
+        }
+    }
+}
+
+/// Opened storage that hands out one [`StateBackend`] per session
+#[derive(Clone, Default)]
+pub enum Storage {
+    #[default]
+    Memory,
+    Disk(sled::Db),
+}
+
+impl Storage {
+    /// Backend for a session's mappings; on disk, reopening a session sees its earlier entries
+    pub fn session_backend(&self, session_id: &str) -> Arc<dyn StateBackend> {
+        match self {
+            Storage::Memory => Arc::new(MemoryBackend::default()),
+            Storage::Disk(db) => match db.open_tree(session_id) {
+                Ok(tree) => Arc::new(DiskBackend { tree }),
+                Err(e) => {
+                    tracing::warn!(session_id, error = %e, "Failed to open session state, keeping it in memory");
+                    Arc::new(MemoryBackend::default())
+                }
+            },
+        }
+    }
+
+    /// Sessions with persisted state
+    pub fn session_ids(&self) -> Vec<String> {
+        match self {
+            Storage::Memory => Vec::new(),
+            Storage::Disk(db) => db
+                .tree_names()
+                .into_iter()
+                .filter(|name| name.as_ref() != b"__sled__default")
+                .filter_map(|name| String::from_utf8(name.to_vec()).ok())
+                .collect(),
+        }
+    }
+
+    /// Delete a session's persisted state
+    pub fn remove_session(&self, session_id: &str) {
+        if let Storage::Disk(db) = self
+            && let Err(e) = db.drop_tree(session_id)
+        {
+            tracing::warn!(session_id, error = %e, "Failed to drop session state");
+        }
+    }
+
+    /// Flush pending writes to disk
+    pub fn flush(&self) {
+        if let Storage::Disk(db) = self
+            && let Err(e) = db.flush()
+        {
+            tracing::warn!(error = %e, "Failed to flush state store");
+        }
+    }
+}
@@ -0,0 +1,1102 @@
+mod backend;
+
+pub use backend::{DiskBackend, MemoryBackend, StateBackend, StateStorage, Storage};
+
+use crate::models::claude::ClaudeRequest;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Tool call metadata stored in state with enhanced context tracking
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ToolCallMetadata {
+    pub function_name: String,
+    pub thought_signature: Option<String>,
+    pub args: serde_json::Value,
+    /// Wall-clock time so retention still works after a restart
+    pub timestamp: SystemTime,
+    /// Last time the mapping was looked up, for LRU eviction
+    #[serde(default = "SystemTime::now")]
+    pub last_used: SystemTime,
+    /// Request sequence number for this tool call (for debugging multi-turn)
+    pub request_index: usize,
+    /// Session key of the conversation this tool call belongs to
+    pub conversation_id: String,
+    /// Original tool_use_id for round-trip verification
+    pub original_id: String,
+}
+
+/// Conversation state for tracking tool calls across multi-turn interactions
+///
+/// This maintains the mapping between Claude's tool_use_id and Gemini's function metadata,
+/// which is necessary because:
+/// - Claude generates unique IDs for each tool call
+/// - When Claude sends tool results, it references these IDs
+/// - Gemini needs function names AND thought signatures for function responses
+///
+/// Enhanced with conversation context tracking for better debugging and round-trip verification.
+///
+/// Thread-safe and designed for concurrent access across async tasks.
+#[derive(Clone)]
+pub struct ConversationState {
+    /// Maps tool_use_id -> ToolCallMetadata
+    tool_mappings: Arc<dyn StateBackend>,
+
+    /// How long to keep mappings before cleanup (default: 1 hour)
+    retention_duration: Duration,
+
+    /// Request counter for tracking conversation flow
+    request_counter: Arc<AtomicUsize>,
+
+    /// Session this state belongs to, recorded as each tool call's `conversation_id`
+    session_id: Arc<str>,
+
+    /// Lookup times not yet written back to the backend (tool_use_id -> last used)
+    touched: Arc<DashMap<String, SystemTime>>,
+}
+
+impl ConversationState {
+    /// Create a new conversation state with default retention (1 hour)
+    pub fn new() -> Self {
+        Self::with_retention(Duration::from_secs(3600))
+    }
+
+    /// Create with custom retention duration
+    pub fn with_retention(retention_duration: Duration) -> Self {
+        Self::for_session(DEFAULT_SESSION, retention_duration)
+    }
+
+    /// Create the state partition for one session
+    pub fn for_session(session_id: &str, retention_duration: Duration) -> Self {
+        Self::with_backend(
+            session_id,
+            retention_duration,
+            Arc::new(MemoryBackend::default()),
+        )
+    }
+
+    /// Create the state for one session on top of a storage backend
+    ///
+    /// The request counter resumes after the highest index already stored, so
+    /// entries restored from disk keep their ordering.
+    pub fn with_backend(
+        session_id: &str,
+        retention_duration: Duration,
+        backend: Arc<dyn StateBackend>,
+    ) -> Self {
+        let next_index = backend
+            .entries()
+            .iter()
+            .map(|(_, metadata)| metadata.request_index + 1)
+            .max()
+            .unwrap_or(0);
+
+        Self {
+            tool_mappings: backend,
+            retention_duration,
+            request_counter: Arc::new(AtomicUsize::new(next_index)),
+            session_id: Arc::from(session_id),
+            touched: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Session this state belongs to
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Increment and get the next request index
+    pub fn next_request_index(&self) -> usize {
+        self.request_counter.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Get current request count
+    pub fn current_request_count(&self) -> usize {
+        self.request_counter.load(Ordering::SeqCst)
+    }
+
+    /// Register a tool use mapping when transforming Gemini function call to Claude tool_use
+    ///
+    /// This should be called when:
+    /// - Gemini responds with a function call
+    /// - We transform it to a Claude tool_use block
+    /// - We need to remember the mapping (including thought signature and args) for when it comes back in history
+    ///
+    /// Enhanced version with conversation context tracking
+    pub fn register_tool_use_with_context(
+        &self,
+        tool_use_id: String,
+        function_name: String,
+        thought_signature: Option<String>,
+        args: serde_json::Value,
+        conversation_id: Option<String>,
+    ) {
+        let request_index = self.next_request_index();
+        let conv_id = conversation_id.unwrap_or_else(|| self.session_id.to_string());
+        let original_id = tool_use_id.clone();
+
+        tracing::debug!(
+            tool_use_id = %tool_use_id,
+            function_name = %function_name,
+            request_index = request_index,
+            conversation_id = %conv_id,
+            has_signature = thought_signature.is_some(),
+            "Registering tool use mapping with context"
+        );
+
+        self.tool_mappings.insert(
+            &tool_use_id,
+            &ToolCallMetadata {
+                function_name,
+                thought_signature,
+                args,
+                timestamp: SystemTime::now(),
+                last_used: SystemTime::now(),
+                request_index,
+                conversation_id: conv_id,
+                original_id,
+            },
+        );
+    }
+
+    /// Legacy method for backward compatibility
+    pub fn register_tool_use(
+        &self,
+        tool_use_id: String,
+        function_name: String,
+        thought_signature: Option<String>,
+        args: serde_json::Value,
+    ) {
+        self.register_tool_use_with_context(
+            tool_use_id,
+            function_name,
+            thought_signature,
+            args,
+            None,
+        );
+    }
+
+    /// Retrieve function name for a given tool_use_id
+    ///
+    /// This should be called when:
+    /// - Claude sends a tool_result block
+    /// - We need to transform it to a Gemini function response
+    /// - We need to look up the original function name
+    pub fn get_function_name(&self, tool_use_id: &str) -> Option<String> {
+        self.get_metadata(tool_use_id)
+            .map(|metadata| metadata.function_name)
+    }
+
+    /// Retrieve complete metadata (name + thought signature) for a given tool_use_id
+    ///
+    /// Marks the mapping as recently used. The access time is kept in memory
+    /// until [`ConversationState::persist_last_used`] writes it back, so reads
+    /// don't hit the backend's write path.
+    pub fn get_metadata(&self, tool_use_id: &str) -> Option<ToolCallMetadata> {
+        let mut metadata = self.tool_mappings.get(tool_use_id)?;
+        metadata.last_used = SystemTime::now();
+        self.touched
+            .insert(tool_use_id.to_string(), metadata.last_used);
+        Some(metadata)
+    }
+
+    /// Write pending access times back to the backend
+    ///
+    /// Returns the number of mappings updated.
+    pub fn persist_last_used(&self) -> usize {
+        let touched: Vec<(String, SystemTime)> = self
+            .touched
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect();
+
+        touched
+            .into_iter()
+            .filter(|(id, last_used)| {
+                self.touched
+                    .remove_if(id, |_, pending| pending == last_used);
+                let Some(mut metadata) = self.tool_mappings.get(id) else {
+                    return false;
+                };
+                metadata.last_used = metadata.last_used.max(*last_used);
+                self.tool_mappings.insert(id, &metadata);
+                true
+            })
+            .count()
+    }
+
+    /// Remove one mapping, returning whether it existed
+    pub fn remove(&self, tool_use_id: &str) -> bool {
+        self.touched.remove(tool_use_id);
+        self.tool_mappings.remove(tool_use_id)
+    }
+
+    /// Get the number of tracked tool calls
+    pub fn len(&self) -> usize {
+        self.tool_mappings.len()
+    }
+
+    /// Check if state is empty
+    pub fn is_empty(&self) -> bool {
+        self.tool_mappings.is_empty()
+    }
+
+    /// Clean up old mappings that exceed retention duration
+    ///
+    /// This should be called periodically to prevent unbounded memory growth.
+    /// Returns the number of entries removed.
+    pub fn cleanup_old_entries(&self) -> usize {
+        let retention = self.retention_duration;
+
+        let to_remove: Vec<String> = self
+            .tool_mappings
+            .entries()
+            .into_iter()
+            .filter_map(|(id, metadata)| {
+                // A timestamp in the future (clock went backwards) counts as fresh
+                let age = metadata.timestamp.elapsed().unwrap_or_default();
+                (age > retention).then_some(id)
+            })
+            .collect();
+
+        let count = to_remove.iter().filter(|id| self.remove(id)).count();
+
+        if count > 0 {
+            tracing::info!(
+                removed = count,
+                total_remaining = self.len(),
+                "Cleaned up old tool mappings"
+            );
+        }
+
+        count
+    }
+
+    /// Clear all mappings (useful for testing)
+    pub fn clear(&self) {
+        self.touched.clear();
+        self.tool_mappings.clear();
+        self.request_counter.store(0, Ordering::SeqCst);
+    }
+
+    /// Get all tool calls for a specific conversation (for debugging)
+    pub fn get_by_conversation(&self, conversation_id: &str) -> Vec<(String, ToolCallMetadata)> {
+        self.tool_mappings
+            .entries()
+            .into_iter()
+            .filter(|(_, metadata)| metadata.conversation_id == conversation_id)
+            .collect()
+    }
+
+    /// Get tool calls sorted by request index (for debugging conversation flow)
+    pub fn get_sorted_by_request_index(&self) -> Vec<(String, ToolCallMetadata)> {
+        let mut entries = self.tool_mappings.entries();
+
+        entries.sort_by_key(|(_, metadata)| metadata.request_index);
+        entries
+    }
+
+    /// Verify round-trip: check if tool_use_id matches original_id
+    pub fn verify_round_trip(&self, tool_use_id: &str) -> bool {
+        self.tool_mappings
+            .get(tool_use_id)
+            .map(|metadata| metadata.original_id == tool_use_id)
+            .unwrap_or(false)
+    }
+}
+
+impl Default for ConversationState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Session id used when no session information is available
+pub const DEFAULT_SESSION: &str = "default";
+
+/// Header clients can set to pick their session explicitly
+pub const SESSION_HEADER: &str = "x-session-id";
+
+/// Derive the session key for a request
+///
+/// In order of preference: the [`SESSION_HEADER`] value, `metadata.user_id`
+/// (which Claude Code sets per session), or a hash of the first user message,
+/// which stays the same for every turn of a conversation.
+pub fn session_key(header: Option<&str>, request: &ClaudeRequest) -> String {
+    if let Some(id) = header.map(str::trim).filter(|id| !id.is_empty()) {
+        return format!("header:{}", id);
+    }
+
+    if let Some(user_id) = request
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.user_id.as_deref())
+        .filter(|id| !id.is_empty())
+    {
+        return format!("user:{}", user_id);
+    }
+
+    match request.messages.iter().find(|m| m.role == "user") {
+        Some(first) => {
+            let content = serde_json::to_vec(&first.content).unwrap_or_default();
+            let digest = Sha256::digest(&content);
+            let hex: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+            format!("msg:{}", hex)
+        }
+        None => DEFAULT_SESSION.to_string(),
+    }
+}
+
+/// Conversation state partitioned by session
+///
+/// Each session gets its own [`ConversationState`], so concurrent Claude Code
+/// windows sharing one proxy never see each other's tool mappings. Sessions
+/// idle for longer than the TTL are evicted, and entries inside a live
+/// session expire after the same TTL.
+///
+/// With [`Storage::Disk`] each session's mappings are persisted, and a session
+/// seen again after a restart picks up where it left off.
+///
+/// [`SessionStore::spawn_sweeper`] runs the cleanup in the background and, when
+/// a maximum entry count is set, evicts the least recently used mappings.
+#[derive(Clone)]
+pub struct SessionStore {
+    sessions: Arc<DashMap<String, Session>>,
+    storage: Storage,
+    ttl: Duration,
+    /// Cap on tool mappings across all sessions (`None` = unbounded)
+    max_entries: Option<usize>,
+}
+
+struct Session {
+    state: ConversationState,
+    last_seen: Instant,
+}
+
+/// What one [`SessionStore::sweep`] removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SweepStats {
+    /// Idle sessions evicted
+    pub sessions: usize,
+    /// Tool mappings removed, whether expired, over capacity or in an evicted session
+    pub entries: usize,
+}
+
+impl SessionStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self::with_storage(ttl, Storage::Memory)
+    }
+
+    /// Create a store on top of opened storage
+    ///
+    /// Persisted entries older than the TTL are dropped, as are sessions left
+    /// with no entries. The remaining sessions are tracked like live ones, idle
+    /// since their most recent lookup, so sweeps and the entry cap cover them.
+    pub fn with_storage(ttl: Duration, storage: Storage) -> Self {
+        let store = Self {
+            sessions: Arc::new(DashMap::new()),
+            storage,
+            ttl,
+            max_entries: None,
+        };
+
+        let now = Instant::now();
+        for session_id in store.storage.session_ids() {
+            let state = store.open_session(&session_id);
+            state.cleanup_old_entries();
+            if state.is_empty() {
+                store.storage.remove_session(&session_id);
+                continue;
+            }
+
+            let idle = state
+                .tool_mappings
+                .entries()
+                .iter()
+                .filter_map(|(_, metadata)| metadata.last_used.elapsed().ok())
+                .min()
+                .unwrap_or_default();
+            let last_seen = now.checked_sub(idle).unwrap_or(now);
+            store
+                .sessions
+                .insert(session_id, Session { state, last_seen });
+        }
+        store
+    }
+
+    /// Cap the total number of tool mappings; `0` means unbounded
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = (max_entries > 0).then_some(max_entries);
+        self
+    }
+
+    fn open_session(&self, session_id: &str) -> ConversationState {
+        ConversationState::with_backend(
+            session_id,
+            self.ttl,
+            self.storage.session_backend(session_id),
+        )
+    }
+
+    /// Get the state for a session, creating it on first use
+    pub fn get(&self, session_id: &str) -> ConversationState {
+        let now = Instant::now();
+        let mut session = self
+            .sessions
+            .entry(session_id.to_string())
+            .or_insert_with(|| {
+                tracing::debug!(session_id, "Creating conversation state for new session");
+                Session {
+                    state: self.open_session(session_id),
+                    last_seen: now,
+                }
+            });
+
+        if now.duration_since(session.last_seen) > self.ttl {
+            tracing::debug!(session_id, "Session expired, starting fresh state");
+            self.storage.remove_session(session_id);
+            session.state = self.open_session(session_id);
+        }
+        session.last_seen = now;
+        session.state.clone()
+    }
+
+    /// Total tool mappings across live sessions
+    pub fn entry_count(&self) -> usize {
+        self.sessions
+            .iter()
+            .map(|session| session.state.len())
+            .sum()
+    }
+
+    /// Number of live sessions
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Check if there are no sessions
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Drop every session, including persisted state
+    pub fn clear(&self) {
+        self.sessions.clear();
+        for session_id in self.storage.session_ids() {
+            self.storage.remove_session(&session_id);
+        }
+    }
+
+    /// Write back pending access times and flush persisted state
+    pub fn flush(&self) {
+        for session in self.sessions.iter() {
+            session.state.persist_last_used();
+        }
+        self.storage.flush();
+    }
+
+    /// Evict sessions idle for longer than the TTL
+    ///
+    /// Returns the number of sessions removed. Unlike [`SessionStore::sweep`]
+    /// this leaves live sessions' entries alone.
+    pub fn cleanup_expired(&self) -> usize {
+        self.evict_idle_sessions().sessions
+    }
+
+    /// Run one full cleanup pass
+    ///
+    /// Evicts idle sessions, expires old entries in the remaining ones and then
+    /// enforces the maximum entry count by dropping the least recently used
+    /// mappings.
+    pub fn sweep(&self) -> SweepStats {
+        let mut stats = self.evict_idle_sessions();
+
+        for session in self.sessions.iter() {
+            stats.entries += session.state.cleanup_old_entries();
+            session.state.persist_last_used();
+        }
+        stats.entries += self.evict_least_recently_used();
+        stats
+    }
+
+    fn evict_idle_sessions(&self) -> SweepStats {
+        let mut stats = SweepStats::default();
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .sessions
+            .iter()
+            .filter(|session| now.duration_since(session.last_seen) > self.ttl)
+            .map(|session| session.key().clone())
+            .collect();
+
+        for session_id in expired {
+            if let Some((_, session)) = self.sessions.remove_if(&session_id, |_, session| {
+                now.duration_since(session.last_seen) > self.ttl
+            }) {
+                stats.entries += session.state.len();
+                self.storage.remove_session(&session_id);
+                stats.sessions += 1;
+            }
+        }
+
+        if stats.sessions > 0 {
+            tracing::info!(
+                removed = stats.sessions,
+                remaining = self.sessions.len(),
+                "Evicted idle sessions"
+            );
+        }
+        stats
+    }
+
+    /// Drop the least recently used mappings until the entry cap is met
+    fn evict_least_recently_used(&self) -> usize {
+        let Some(max_entries) = self.max_entries else {
+            return 0;
+        };
+        let total = self.entry_count();
+        if total <= max_entries {
+            return 0;
+        }
+
+        let states: Vec<ConversationState> = self
+            .sessions
+            .iter()
+            .map(|session| session.state.clone())
+            .collect();
+        let mut candidates: Vec<(SystemTime, usize, String)> = states
+            .iter()
+            .enumerate()
+            .flat_map(|(index, state)| {
+                state
+                    .tool_mappings
+                    .entries()
+                    .into_iter()
+                    .map(move |(id, metadata)| (metadata.last_used, index, id))
+            })
+            .collect();
+        candidates.sort();
+
+        let evicted = candidates
+            .into_iter()
+            .take(total - max_entries)
+            .filter(|(_, index, id)| states[*index].remove(id))
+            .count();
+
+        tracing::info!(
+            evicted,
+            max_entries,
+            "Evicted least recently used tool mappings"
+        );
+        evicted
+    }
+
+    /// Spawn a background task that calls [`SessionStore::sweep`] every `interval`
+    ///
+    /// Eviction counts are logged and added to [`crate::metrics::TOOL_METRICS`].
+    pub fn spawn_sweeper(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let store = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // The first tick completes immediately
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                let stats = store.sweep();
+                crate::metrics::TOOL_METRICS.record_state_sweep(stats.sessions, stats.entries);
+                if stats.entries > 0 || stats.sessions > 0 {
+                    tracing::info!(
+                        sessions = stats.sessions,
+                        entries = stats.entries,
+                        remaining = store.entry_count(),
+                        "State sweep evicted entries"
+                    );
+                } else {
+                    tracing::debug!(
+                        remaining = store.entry_count(),
+                        "State sweep found nothing to evict"
+                    );
+                }
+            }
+        })
+    }
+}
+
+impl Default for SessionStore {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(3600))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_register_and_retrieve() {
+        let state = ConversationState::new();
+
+        state.register_tool_use(
+            "toolu_123".to_string(),
+            "get_weather".to_string(),
+            None,
+            serde_json::json!({}),
+        );
+
+        let result = state.get_function_name("toolu_123");
+        assert_eq!(result, Some("get_weather".to_string()));
+    }
+
+    #[test]
+    fn test_missing_mapping() {
+        let state = ConversationState::new();
+
+        let result = state.get_function_name("toolu_nonexistent");
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_multiple_mappings() {
+        let state = ConversationState::new();
+
+        state.register_tool_use(
+            "toolu_1".to_string(),
+            "func_a".to_string(),
+            None,
+            serde_json::json!({}),
+        );
+        state.register_tool_use(
+            "toolu_2".to_string(),
+            "func_b".to_string(),
+            None,
+            serde_json::json!({}),
+        );
+        state.register_tool_use(
+            "toolu_3".to_string(),
+            "func_c".to_string(),
+            None,
+            serde_json::json!({}),
+        );
+
+        assert_eq!(state.len(), 3);
+        assert_eq!(
+            state.get_function_name("toolu_1"),
+            Some("func_a".to_string())
+        );
+        assert_eq!(
+            state.get_function_name("toolu_2"),
+            Some("func_b".to_string())
+        );
+        assert_eq!(
+            state.get_function_name("toolu_3"),
+            Some("func_c".to_string())
+        );
+    }
+
+    #[test]
+    fn test_thread_safety() {
+        let state = ConversationState::new();
+        let state_clone = state.clone();
+
+        // Register from main thread
+        state.register_tool_use(
+            "toolu_main".to_string(),
+            "main_func".to_string(),
+            None,
+            serde_json::json!({}),
+        );
+
+        // Register from another thread
+        let handle = thread::spawn(move || {
+            state_clone.register_tool_use(
+                "toolu_thread".to_string(),
+                "thread_func".to_string(),
+                None,
+                serde_json::json!({}),
+            );
+        });
+
+        handle.join().unwrap();
+
+        // Both should be accessible
+        assert_eq!(
+            state.get_function_name("toolu_main"),
+            Some("main_func".to_string())
+        );
+        assert_eq!(
+            state.get_function_name("toolu_thread"),
+            Some("thread_func".to_string())
+        );
+        assert_eq!(state.len(), 2);
+    }
+
+    #[test]
+    fn test_cleanup_old_entries() {
+        let state = ConversationState::with_retention(Duration::from_millis(100));
+
+        state.register_tool_use(
+            "toolu_old".to_string(),
+            "old_func".to_string(),
+            None,
+            serde_json::json!({}),
+        );
+        state.register_tool_use(
+            "toolu_new".to_string(),
+            "new_func".to_string(),
+            None,
+            serde_json::json!({}),
+        );
+
+        // Wait for first entry to expire
+        thread::sleep(Duration::from_millis(150));
+
+        // Add another new entry
+        state.register_tool_use(
+            "toolu_newer".to_string(),
+            "newer_func".to_string(),
+            None,
+            serde_json::json!({}),
+        );
+
+        // Cleanup should remove the old entry
+        let removed = state.cleanup_old_entries();
+
+        // At least the old one should be removed
+        assert!(removed >= 1);
+        assert!(state.get_function_name("toolu_old").is_none());
+
+        // New entries should still exist
+        assert!(state.get_function_name("toolu_newer").is_some());
+    }
+
+    #[test]
+    fn test_clear() {
+        let state = ConversationState::new();
+
+        state.register_tool_use(
+            "toolu_1".to_string(),
+            "func_1".to_string(),
+            None,
+            serde_json::json!({}),
+        );
+        state.register_tool_use(
+            "toolu_2".to_string(),
+            "func_2".to_string(),
+            None,
+            serde_json::json!({}),
+        );
+
+        assert_eq!(state.len(), 2);
+
+        state.clear();
+
+        assert_eq!(state.len(), 0);
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn test_overwrite_mapping() {
+        let state = ConversationState::new();
+
+        state.register_tool_use(
+            "toolu_123".to_string(),
+            "first_func".to_string(),
+            None,
+            serde_json::json!({}),
+        );
+        state.register_tool_use(
+            "toolu_123".to_string(),
+            "second_func".to_string(),
+            None,
+            serde_json::json!({}),
+        );
+
+        // Should have the second value
+        assert_eq!(
+            state.get_function_name("toolu_123"),
+            Some("second_func".to_string())
+        );
+        assert_eq!(state.len(), 1);
+    }
+
+    fn request(json: serde_json::Value) -> ClaudeRequest {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_session_key_sources() {
+        let req = request(serde_json::json!({
+            "model": "claude-sonnet-4",
+            "messages": [{"role": "user", "content": "Fix the build"}],
+            "metadata": {"user_id": "user_abc_account_1_session_42"}
+        }));
+
+        // Explicit header wins, then metadata.user_id
+        assert_eq!(session_key(Some("win-2"), &req), "header:win-2");
+        assert_eq!(
+            session_key(None, &req),
+            "user:user_abc_account_1_session_42"
+        );
+
+        // Without either, the first user message identifies the conversation
+        let first_turn = request(serde_json::json!({
+            "model": "claude-sonnet-4",
+            "messages": [{"role": "user", "content": "Fix the build"}]
+        }));
+        let later_turn = request(serde_json::json!({
+            "model": "claude-sonnet-4",
+            "messages": [
+                {"role": "user", "content": "Fix the build"},
+                {"role": "assistant", "content": "Done."},
+                {"role": "user", "content": "Now run the tests"}
+            ]
+        }));
+        let other = request(serde_json::json!({
+            "model": "claude-sonnet-4",
+            "messages": [{"role": "user", "content": "Write docs"}]
+        }));
+        let key = session_key(None, &first_turn);
+        assert!(key.starts_with("msg:"));
+        assert_eq!(key, session_key(None, &later_turn));
+        assert_ne!(key, session_key(None, &other));
+    }
+
+    #[test]
+    fn test_sessions_are_isolated() {
+        let sessions = SessionStore::default();
+
+        sessions.get("user:a").register_tool_use(
+            "toolu_1".to_string(),
+            "Bash".to_string(),
+            None,
+            serde_json::json!({}),
+        );
+
+        assert_eq!(
+            sessions.get("user:a").get_function_name("toolu_1"),
+            Some("Bash".to_string())
+        );
+        assert!(
+            sessions
+                .get("user:b")
+                .get_function_name("toolu_1")
+                .is_none()
+        );
+        assert_eq!(
+            sessions.get("user:a").get_by_conversation("user:a").len(),
+            1
+        );
+        assert_eq!(sessions.len(), 2);
+    }
+
+    #[test]
+    fn test_idle_sessions_evicted() {
+        let sessions = SessionStore::new(Duration::from_millis(100));
+        sessions.get("user:old").register_tool_use(
+            "toolu_old".to_string(),
+            "Read".to_string(),
+            None,
+            serde_json::json!({}),
+        );
+
+        thread::sleep(Duration::from_millis(150));
+        sessions.get("user:new");
+
+        assert_eq!(sessions.cleanup_expired(), 1);
+        assert_eq!(sessions.len(), 1);
+        assert!(sessions.get("user:old").is_empty());
+    }
+
+    fn register(state: &ConversationState, id: &str) {
+        state.register_tool_use(
+            id.to_string(),
+            "Read".to_string(),
+            None,
+            serde_json::json!({}),
+        );
+    }
+
+    #[test]
+    fn test_sweep_reports_evictions() {
+        let sessions = SessionStore::new(Duration::from_millis(100));
+        register(&sessions.get("user:idle"), "toolu_1");
+        register(&sessions.get("user:idle"), "toolu_2");
+
+        thread::sleep(Duration::from_millis(150));
+        register(&sessions.get("user:live"), "toolu_3");
+
+        assert_eq!(
+            sessions.sweep(),
+            SweepStats {
+                sessions: 1,
+                entries: 2
+            }
+        );
+        assert_eq!(sessions.entry_count(), 1);
+    }
+
+    #[test]
+    fn test_max_entries_evicts_least_recently_used() {
+        let sessions = SessionStore::default().with_max_entries(2);
+        let a = sessions.get("user:a");
+        let b = sessions.get("user:b");
+        register(&a, "toolu_1");
+        thread::sleep(Duration::from_millis(5));
+        register(&b, "toolu_2");
+        thread::sleep(Duration::from_millis(5));
+        register(&a, "toolu_3");
+        thread::sleep(Duration::from_millis(5));
+
+        // Touching the oldest mapping makes toolu_2 the least recently used
+        assert!(a.get_metadata("toolu_1").is_some());
+
+        assert_eq!(sessions.sweep().entries, 1);
+        assert_eq!(sessions.entry_count(), 2);
+        assert!(b.get_function_name("toolu_2").is_none());
+        assert!(a.get_function_name("toolu_1").is_some());
+        assert!(a.get_function_name("toolu_3").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_sweeper_task_runs_on_interval() {
+        let sessions = SessionStore::default().with_max_entries(1);
+        let state = sessions.get("user:a");
+        register(&state, "toolu_1");
+        register(&state, "toolu_2");
+
+        let sweeper = sessions.spawn_sweeper(Duration::from_millis(20));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(sessions.entry_count(), 1);
+        sweeper.abort();
+    }
+
+    fn temp_state_dir() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("ccp-state-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_disk_state_survives_restart() {
+        let dir = temp_state_dir();
+        let storage = StateStorage::Disk { dir: dir.clone() };
+
+        {
+            let sessions =
+                SessionStore::with_storage(Duration::from_secs(3600), storage.open().unwrap());
+            let state = sessions.get("user:a");
+            state.register_tool_use(
+                "toolu_1".to_string(),
+                "Bash".to_string(),
+                Some("sig_abc".to_string()),
+                serde_json::json!({"command": "ls"}),
+            );
+            state.register_tool_use(
+                "toolu_2".to_string(),
+                "Read".to_string(),
+                None,
+                serde_json::json!({}),
+            );
+            sessions.flush();
+        }
+
+        let sessions =
+            SessionStore::with_storage(Duration::from_secs(3600), storage.open().unwrap());
+        let state = sessions.get("user:a");
+        let metadata = state.get_metadata("toolu_1").unwrap();
+        assert_eq!(metadata.function_name, "Bash");
+        assert_eq!(metadata.thought_signature.as_deref(), Some("sig_abc"));
+        assert_eq!(metadata.args["command"], "ls");
+        assert_eq!(state.len(), 2);
+        // Request indices continue after the restored entries
+        assert_eq!(state.current_request_count(), 2);
+        assert!(sessions.get("user:b").is_empty());
+
+        sessions.clear();
+        assert!(sessions.get("user:a").is_empty());
+        drop(sessions);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_disk_state_prunes_expired_entries_on_open() {
+        let dir = temp_state_dir();
+        let storage = StateStorage::Disk { dir: dir.clone() };
+
+        {
+            let sessions =
+                SessionStore::with_storage(Duration::from_millis(100), storage.open().unwrap());
+            sessions.get("user:old").register_tool_use(
+                "toolu_old".to_string(),
+                "Read".to_string(),
+                None,
+                serde_json::json!({}),
+            );
+            sessions.flush();
+        }
+
+        thread::sleep(Duration::from_millis(150));
+        let sessions =
+            SessionStore::with_storage(Duration::from_millis(100), storage.open().unwrap());
+        assert!(sessions.storage.session_ids().is_empty());
+        drop(sessions);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_restored_sessions_are_swept_and_capped() {
+        let dir = temp_state_dir();
+        let storage = StateStorage::Disk { dir: dir.clone() };
+
+        {
+            let sessions =
+                SessionStore::with_storage(Duration::from_secs(3600), storage.open().unwrap());
+            register(&sessions.get("user:a"), "toolu_1");
+            thread::sleep(Duration::from_millis(5));
+            register(&sessions.get("user:b"), "toolu_2");
+            register(&sessions.get("user:b"), "toolu_3");
+            sessions.flush();
+        }
+
+        let sessions =
+            SessionStore::with_storage(Duration::from_secs(3600), storage.open().unwrap())
+                .with_max_entries(2);
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions.entry_count(), 3);
+
+        // The least recently used restored mapping goes, without the session being looked up
+        assert_eq!(sessions.sweep().entries, 1);
+        assert_eq!(sessions.entry_count(), 2);
+        assert!(sessions.get("user:a").is_empty());
+
+        drop(sessions);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_lookups_persist_last_used_on_sweep() {
+        let sessions = SessionStore::default();
+        let state = sessions.get("user:a");
+        register(&state, "toolu_1");
+        let stored = state.tool_mappings.get("toolu_1").unwrap().last_used;
+
+        thread::sleep(Duration::from_millis(5));
+        let looked_up = state.get_metadata("toolu_1").unwrap().last_used;
+        assert!(looked_up > stored);
+        assert_eq!(
+            state.tool_mappings.get("toolu_1").unwrap().last_used,
+            stored
+        );
+
+        sessions.sweep();
+        assert_eq!(
+            state.tool_mappings.get("toolu_1").unwrap().last_used,
+            looked_up
+        );
+    }
+}
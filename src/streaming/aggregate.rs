@@ -0,0 +1,312 @@
+use crate::error::{ProxyError, Result};
+use serde_json::{Value, json};
+
+/// Folds a sequence of Claude SSE events back into a single Messages API response
+///
+/// Used for `stream: false` requests: the upstream response is still converted
+/// with [`SSEEventGenerator`](super::SSEEventGenerator), so content blocks and stop
+/// reasons come out exactly as they would in streaming mode.
+#[derive(Debug, Default)]
+pub struct MessageAggregator {
+    message: Option<Value>,
+    /// Content blocks keyed by their SSE index
+    blocks: Vec<(u64, Value)>,
+    /// Raw `input_json_delta` fragments for tool_use blocks, keyed by index
+    partial_inputs: Vec<(u64, String)>,
+    stop_reason: Option<Value>,
+    stop_sequence: Option<Value>,
+    /// Usage counters from `message_delta`, overriding those in `message_start`
+    usage: serde_json::Map<String, Value>,
+    error: Option<(String, String)>,
+}
+
+impl MessageAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one or more formatted SSE events (`event: ...\ndata: ...\n\n`)
+    pub fn push_event(&mut self, event: &str) {
+        for line in event.lines() {
+            if let Some(data) = line.strip_prefix("data: ") {
+                match serde_json::from_str::<Value>(data) {
+                    Ok(value) => self.apply(value),
+                    Err(e) => tracing::warn!(error = %e, "Skipping unparseable SSE data line"),
+                }
+            }
+        }
+    }
+
+    fn apply(&mut self, data: Value) {
+        match data["type"].as_str().unwrap_or_default() {
+            "message_start" => {
+                self.message = Some(data["message"].clone());
+            }
+            "content_block_start" => {
+                let index = data["index"].as_u64().unwrap_or_default();
+                self.blocks.push((index, data["content_block"].clone()));
+            }
+            "content_block_delta" => {
+                let index = data["index"].as_u64().unwrap_or_default();
+                let delta = &data["delta"];
+                match delta["type"].as_str().unwrap_or_default() {
+                    "text_delta" => {
+                        if let Some(block) = self.block_mut(index) {
+                            let text = format!(
+                                "{}{}",
+                                block["text"].as_str().unwrap_or_default(),
+                                delta["text"].as_str().unwrap_or_default()
+                            );
+                            block["text"] = json!(text);
+                        }
+                    }
+                    "thinking_delta" => {
+                        if let Some(block) = self.block_mut(index) {
+                            let thinking = format!(
+                                "{}{}",
+                                block["thinking"].as_str().unwrap_or_default(),
+                                delta["thinking"].as_str().unwrap_or_default()
+                            );
+                            block["thinking"] = json!(thinking);
+                        }
+                    }
+                    "signature_delta" => {
+                        if let Some(block) = self.block_mut(index) {
+                            block["signature"] = delta["signature"].clone();
+                        }
+                    }
+                    "input_json_delta" => {
+                        let fragment = delta["partial_json"].as_str().unwrap_or_default();
+                        match self.partial_inputs.iter_mut().find(|(i, _)| *i == index) {
+                            Some((_, buf)) => buf.push_str(fragment),
+                            None => self.partial_inputs.push((index, fragment.to_string())),
+                        }
+                    }
+                    other => tracing::debug!(delta_type = %other, "Ignoring unknown delta type"),
+                }
+            }
+            "content_block_stop" => {
+                let index = data["index"].as_u64().unwrap_or_default();
+                if let Some(pos) = self.partial_inputs.iter().position(|(i, _)| *i == index) {
+                    let (_, raw) = self.partial_inputs.remove(pos);
+                    let input = if raw.trim().is_empty() {
+                        json!({})
+                    } else {
+                        serde_json::from_str(&raw).unwrap_or_else(|e| {
+                            tracing::warn!(error = %e, "Tool input is not valid JSON, keeping raw string");
+                            Value::String(raw)
+                        })
+                    };
+                    if let Some(block) = self.block_mut(index) {
+                        block["input"] = input;
+                    }
+                }
+            }
+            "message_delta" => {
+                self.stop_reason = Some(data["delta"]["stop_reason"].clone());
+                self.stop_sequence = Some(data["delta"]["stop_sequence"].clone());
+                if let Some(usage) = data["usage"].as_object() {
+                    for (key, value) in usage.iter().filter(|(_, v)| v.is_u64()) {
+                        self.usage.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            "error" => {
+                self.error = Some((
+                    data["error"]["type"]
+                        .as_str()
+                        .unwrap_or("api_error")
+                        .to_string(),
+                    data["error"]["message"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    fn block_mut(&mut self, index: u64) -> Option<&mut Value> {
+        self.blocks
+            .iter_mut()
+            .find(|(i, _)| *i == index)
+            .map(|(_, block)| block)
+    }
+
+    /// Check if an error event was received
+    pub fn has_error(&self) -> bool {
+        self.error.is_some()
+    }
+
+    /// Build the final `Message` object
+    ///
+    /// Empty text blocks (emitted as placeholders in streaming mode) are dropped.
+    pub fn finish(mut self) -> Result<Value> {
+        if let Some((error_type, message)) = self.error {
+            return Err(ProxyError::from_error_event(&error_type, message));
+        }
+
+        let mut message = self.message.take().ok_or_else(|| {
+            ProxyError::InvalidGeminiResponse("Upstream returned no message content".into())
+        })?;
+
+        self.blocks.sort_by_key(|(index, _)| *index);
+        let content: Vec<Value> = self
+            .blocks
+            .into_iter()
+            .map(|(_, block)| block)
+            .filter(|block| {
+                block["type"] != "text" || !block["text"].as_str().unwrap_or_default().is_empty()
+            })
+            .collect();
+
+        message["content"] = Value::Array(content);
+        message["stop_reason"] = self.stop_reason.unwrap_or(Value::Null);
+        message["stop_sequence"] = self.stop_sequence.unwrap_or(Value::Null);
+        for (key, value) in self.usage {
+            message["usage"][key] = value;
+        }
+
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::gemini::{Candidate, GeminiContent, GeminiPart, GeminiStreamChunk};
+    use crate::streaming::SSEEventGenerator;
+
+    fn chunk(parts: Vec<GeminiPart>, finish_reason: Option<&str>) -> GeminiStreamChunk {
+        GeminiStreamChunk {
+            candidates: vec![Candidate {
+                content: Some(GeminiContent {
+                    role: Some("model".to_string()),
+                    parts,
+                }),
+                finish_reason: finish_reason.map(String::from),
+                safety_ratings: None,
+                index: None,
+            }],
+            usage_metadata: None,
+            prompt_feedback: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_aggregate_text_message() {
+        let mut generator = SSEEventGenerator::new("gemini-3-pro-preview".to_string());
+        let mut aggregator = MessageAggregator::new();
+
+        let chunks = vec![
+            chunk(
+                vec![GeminiPart::Text {
+                    text: "Hello".to_string(),
+                }],
+                None,
+            ),
+            chunk(
+                vec![GeminiPart::Text {
+                    text: " world".to_string(),
+                }],
+                Some("STOP"),
+            ),
+        ];
+        for c in chunks {
+            for event in generator.generate_events(c) {
+                aggregator.push_event(&event);
+            }
+        }
+
+        let message = aggregator.finish().unwrap();
+        assert_eq!(message["type"], "message");
+        assert_eq!(message["role"], "assistant");
+        assert_eq!(message["content"][0]["type"], "text");
+        assert_eq!(message["content"][0]["text"], "Hello world");
+        assert_eq!(message["stop_reason"], "end_turn");
+    }
+
+    #[test]
+    fn test_aggregate_text_then_tool_use() {
+        use crate::models::gemini::FunctionCall;
+
+        let mut generator = SSEEventGenerator::new("gemini-3-pro-preview".to_string());
+        let mut aggregator = MessageAggregator::new();
+
+        let c = chunk(
+            vec![
+                GeminiPart::Text {
+                    text: "Let me check.".to_string(),
+                },
+                GeminiPart::FunctionCall {
+                    function_call: FunctionCall {
+                        name: "get_weather".to_string(),
+                        args: json!({"location": "Paris"}),
+                    },
+                },
+            ],
+            Some("STOP"),
+        );
+        for event in generator.generate_events(c) {
+            aggregator.push_event(&event);
+        }
+
+        let message = aggregator.finish().unwrap();
+        let content = message["content"].as_array().unwrap();
+        assert_eq!(content.len(), 2);
+        assert_eq!(content[0]["text"], "Let me check.");
+        assert_eq!(content[1]["type"], "tool_use");
+        assert_eq!(content[1]["name"], "get_weather");
+        assert_eq!(content[1]["input"]["location"], "Paris");
+        assert_eq!(message["stop_reason"], "tool_use");
+    }
+
+    #[test]
+    fn test_aggregate_thinking_block() {
+        let mut generator = SSEEventGenerator::new("gemini-3-pro-preview".to_string());
+        let mut aggregator = MessageAggregator::new();
+
+        let c = chunk(
+            vec![
+                GeminiPart::Thought {
+                    text: "Simple greeting.".to_string(),
+                    thought: true,
+                    thought_signature: None,
+                },
+                GeminiPart::TextWithThought {
+                    text: "Hi!".to_string(),
+                    thought_signature: "c2ln".to_string(),
+                },
+            ],
+            Some("STOP"),
+        );
+        for event in generator.generate_events(c) {
+            aggregator.push_event(&event);
+        }
+
+        let message = aggregator.finish().unwrap();
+        assert_eq!(
+            message["content"],
+            json!([
+                {"type": "thinking", "thinking": "Simple greeting.", "signature": "c2ln"},
+                {"type": "text", "text": "Hi!"}
+            ])
+        );
+    }
+
+    #[test]
+    fn test_aggregate_error_event() {
+        let mut aggregator = MessageAggregator::new();
+        aggregator.push_event(&SSEEventGenerator::format_error(
+            "overloaded_error",
+            "Overloaded",
+        ));
+
+        assert!(aggregator.has_error());
+        let err = aggregator.finish().unwrap_err();
+        assert!(err.to_string().contains("Overloaded"));
+        assert_eq!(err.error_type(), "overloaded_error");
+    }
+}
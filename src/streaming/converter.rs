@@ -0,0 +1,65 @@
+use crate::error::{ProxyError, Result};
+use crate::state::ConversationState;
+
+use super::{SSEEventGenerator, StreamingJsonParser};
+
+/// Converts a provider's raw streaming response into Claude SSE events
+///
+/// One converter is created per response. The handler feeds it upstream bytes
+/// as they arrive and forwards the returned events to the client.
+pub trait SseConverter: Send {
+    /// Feed raw upstream bytes and return any complete SSE events
+    fn feed(&mut self, chunk: &[u8]) -> Result<Vec<String>>;
+
+    /// Check if message_stop has been emitted
+    fn is_finished(&self) -> bool;
+
+    /// Check if an error event has been emitted
+    fn has_failed(&self) -> bool;
+
+    /// Format a terminal error event for `err`; no further events follow
+    fn fail(&mut self, err: &ProxyError) -> String;
+}
+
+/// Gemini JSON array stream to Claude SSE
+pub struct GeminiSseConverter {
+    parser: StreamingJsonParser,
+    generator: SSEEventGenerator,
+}
+
+impl GeminiSseConverter {
+    pub fn new(model_name: String, state: ConversationState) -> Self {
+        Self {
+            parser: StreamingJsonParser::new(),
+            generator: SSEEventGenerator::with_state(model_name, state),
+        }
+    }
+
+    /// Report `tokens` written to a cached content as cache creation usage
+    pub fn with_cache_creation_tokens(mut self, tokens: u32) -> Self {
+        self.generator.set_cache_creation_tokens(tokens);
+        self
+    }
+}
+
+impl SseConverter for GeminiSseConverter {
+    fn feed(&mut self, chunk: &[u8]) -> Result<Vec<String>> {
+        let mut events = Vec::new();
+        for gemini_chunk in self.parser.feed(chunk)? {
+            events.extend(self.generator.generate_events(gemini_chunk));
+        }
+        Ok(events)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.generator.is_finished()
+    }
+
+    fn has_failed(&self) -> bool {
+        self.generator.has_failed()
+    }
+
+    fn fail(&mut self, err: &ProxyError) -> String {
+        self.generator.format_failure(err)
+    }
+}
@@ -1,7 +1,13 @@
+pub mod aggregate;
 pub mod content;
+pub mod converter;
+pub mod openai;
 pub mod parser;
 pub mod sse;
 
+pub use aggregate::MessageAggregator;
 pub use content::{ContentBlock, ContentBlockManager, ContentBlockType};
+pub use converter::{GeminiSseConverter, SseConverter};
+pub use openai::OpenAISseConverter;
 pub use parser::{StreamingJsonParser, ToolInputBuffer};
 pub use sse::SSEEventGenerator;
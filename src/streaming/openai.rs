@@ -0,0 +1,432 @@
+use bytes::{Buf, BytesMut};
+use serde_json::json;
+
+use crate::error::{ProxyError, Result};
+use crate::models::openai::{ChatCompletionChunk, ToolCallDelta};
+
+use super::SSEEventGenerator;
+use super::converter::SseConverter;
+
+/// Content block currently open on the Claude side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OpenBlock {
+    Text {
+        index: u32,
+    },
+    /// `call` is the OpenAI tool call index, `index` the Claude block index
+    ToolUse {
+        call: u32,
+        index: u32,
+    },
+}
+
+/// Converts an OpenAI Chat Completions `data:` stream into Claude SSE events
+///
+/// Text deltas become `text_delta` events and incremental `tool_calls` become
+/// `tool_use` blocks whose arguments are forwarded as `input_json_delta`.
+/// `message_delta`/`message_stop` are sent on `data: [DONE]`, after the
+/// trailing usage chunk.
+pub struct OpenAISseConverter {
+    buffer: BytesMut,
+    model_name: String,
+    header_sent: bool,
+    input_tokens: u32,
+    output_tokens: u32,
+    /// Index assigned to the next content block that gets started
+    next_index: u32,
+    open_block: Option<OpenBlock>,
+    finish_reason: Option<String>,
+    saw_tool_call: bool,
+    finished: bool,
+    failed: bool,
+}
+
+impl OpenAISseConverter {
+    pub fn new(model_name: String) -> Self {
+        Self {
+            buffer: BytesMut::with_capacity(8192),
+            model_name,
+            header_sent: false,
+            input_tokens: 0,
+            output_tokens: 0,
+            next_index: 0,
+            open_block: None,
+            finish_reason: None,
+            saw_tool_call: false,
+            finished: false,
+            failed: false,
+        }
+    }
+
+    fn process_line(&mut self, line: &str, events: &mut Vec<String>) -> Result<()> {
+        // Comments (`: keep-alive`) and `event:`/`id:` fields carry nothing we need
+        let Some(data) = line.strip_prefix("data:") else {
+            return Ok(());
+        };
+        let data = data.trim();
+
+        if data == "[DONE]" {
+            self.finish(events);
+            return Ok(());
+        }
+
+        let chunk: ChatCompletionChunk = serde_json::from_str(data).map_err(|e| {
+            ProxyError::UpstreamError(format!("Invalid OpenAI stream chunk: {} - {}", e, data))
+        })?;
+        self.process_chunk(chunk, events);
+        Ok(())
+    }
+
+    fn process_chunk(&mut self, chunk: ChatCompletionChunk, events: &mut Vec<String>) {
+        if let Some(error) = chunk.error {
+            let status = error
+                .code
+                .as_ref()
+                .and_then(|c| c.as_u64())
+                .and_then(|c| u16::try_from(c).ok())
+                .unwrap_or(502);
+            let err = ProxyError::UpstreamStatus {
+                status,
+                message: format!("OpenAI API error: {}", error.message),
+            };
+            tracing::error!(error = %err, "OpenAI returned an error inside the stream");
+            events.push(self.fail(&err));
+            return;
+        }
+
+        if let Some(usage) = chunk.usage {
+            self.input_tokens = usage.prompt_tokens;
+            self.output_tokens = usage.completion_tokens;
+        }
+
+        if !self.header_sent {
+            events.push(self.format_message_start());
+        }
+
+        // Claude has a single candidate; ignore any n > 1 choices
+        let Some(choice) = chunk.choices.into_iter().find(|c| c.index == 0) else {
+            return;
+        };
+
+        if let Some(text) = choice.delta.content
+            && !text.is_empty()
+        {
+            let index = self.ensure_text_block(events);
+            events.push(format_event(
+                "content_block_delta",
+                json!({
+                    "type": "content_block_delta",
+                    "index": index,
+                    "delta": {"type": "text_delta", "text": text}
+                }),
+            ));
+        }
+
+        for call in choice.delta.tool_calls.unwrap_or_default() {
+            self.process_tool_call(call, events);
+        }
+
+        if let Some(reason) = choice.finish_reason {
+            self.close_block(events);
+            self.finish_reason = Some(reason);
+        }
+    }
+
+    fn process_tool_call(&mut self, call: ToolCallDelta, events: &mut Vec<String>) {
+        let function = call.function.unwrap_or_default();
+
+        let index = match self.open_block {
+            Some(OpenBlock::ToolUse { call: open, index }) if open == call.index => index,
+            _ => {
+                self.close_block(events);
+                let index = self.next_index;
+                self.next_index += 1;
+                self.open_block = Some(OpenBlock::ToolUse {
+                    call: call.index,
+                    index,
+                });
+                self.saw_tool_call = true;
+
+                // Some local servers omit the id; Claude Code still needs one to reply
+                let id = call
+                    .id
+                    .filter(|id| !id.is_empty())
+                    .unwrap_or_else(|| format!("toolu_{}", uuid::Uuid::new_v4().simple()));
+                let name = function.name.clone().unwrap_or_default();
+                tracing::info!(tool_name = %name, tool_use_id = %id, "OpenAI called tool");
+
+                events.push(format_event(
+                    "content_block_start",
+                    json!({
+                        "type": "content_block_start",
+                        "index": index,
+                        "content_block": {
+                            "type": "tool_use",
+                            "id": id,
+                            "name": name,
+                            "input": {}
+                        }
+                    }),
+                ));
+                index
+            }
+        };
+
+        if let Some(arguments) = function.arguments
+            && !arguments.is_empty()
+        {
+            events.push(format_event(
+                "content_block_delta",
+                json!({
+                    "type": "content_block_delta",
+                    "index": index,
+                    "delta": {"type": "input_json_delta", "partial_json": arguments}
+                }),
+            ));
+        }
+    }
+
+    /// Return the open text block index, opening a new text block if needed
+    fn ensure_text_block(&mut self, events: &mut Vec<String>) -> u32 {
+        if let Some(OpenBlock::Text { index }) = self.open_block {
+            return index;
+        }
+
+        self.close_block(events);
+        let index = self.next_index;
+        self.next_index += 1;
+        self.open_block = Some(OpenBlock::Text { index });
+        events.push(format_event(
+            "content_block_start",
+            json!({
+                "type": "content_block_start",
+                "index": index,
+                "content_block": {"type": "text", "text": ""}
+            }),
+        ));
+        index
+    }
+
+    fn close_block(&mut self, events: &mut Vec<String>) {
+        let index = match self.open_block.take() {
+            Some(OpenBlock::Text { index }) | Some(OpenBlock::ToolUse { index, .. }) => index,
+            None => return,
+        };
+        events.push(format_event(
+            "content_block_stop",
+            json!({"type": "content_block_stop", "index": index}),
+        ));
+    }
+
+    fn finish(&mut self, events: &mut Vec<String>) {
+        if self.finished {
+            return;
+        }
+        if !self.header_sent {
+            events.push(self.format_message_start());
+        }
+        self.close_block(events);
+
+        let stop_reason = self.map_finish_reason();
+        events.push(format_event(
+            "message_delta",
+            json!({
+                "type": "message_delta",
+                "delta": {"stop_reason": stop_reason, "stop_sequence": null},
+                "usage": {
+                    "input_tokens": self.input_tokens,
+                    "output_tokens": self.output_tokens
+                }
+            }),
+        ));
+        events.push("event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n".to_string());
+        self.finished = true;
+    }
+
+    /// Map the OpenAI finish reason to a Claude stop reason
+    ///
+    /// Some servers report `stop` even after emitting tool calls, so any tool
+    /// call in the response forces `tool_use`.
+    fn map_finish_reason(&self) -> &'static str {
+        if self.saw_tool_call {
+            return "tool_use";
+        }
+        match self.finish_reason.as_deref() {
+            Some("length") => "max_tokens",
+            Some("tool_calls") | Some("function_call") => "tool_use",
+            Some("content_filter") => "refusal",
+            _ => "end_turn",
+        }
+    }
+
+    fn format_message_start(&mut self) -> String {
+        self.header_sent = true;
+        format_event(
+            "message_start",
+            json!({
+                "type": "message_start",
+                "message": {
+                    "id": format!("msg_openai_{}", uuid::Uuid::new_v4()),
+                    "type": "message",
+                    "role": "assistant",
+                    "model": self.model_name,
+                    "content": [],
+                    "stop_reason": null,
+                    "stop_sequence": null,
+                    "usage": {
+                        "input_tokens": self.input_tokens,
+                        "output_tokens": 1
+                    }
+                }
+            }),
+        )
+    }
+}
+
+impl SseConverter for OpenAISseConverter {
+    fn feed(&mut self, chunk: &[u8]) -> Result<Vec<String>> {
+        let mut events = Vec::new();
+        if self.failed || self.finished {
+            return Ok(events);
+        }
+
+        self.buffer.extend_from_slice(chunk);
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line = self.buffer.split_to(pos);
+            self.buffer.advance(1);
+
+            let line = String::from_utf8_lossy(&line);
+            self.process_line(line.trim_end_matches('\r'), &mut events)?;
+            if self.failed || self.finished {
+                break;
+            }
+        }
+
+        Ok(events)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn has_failed(&self) -> bool {
+        self.failed
+    }
+
+    fn fail(&mut self, err: &ProxyError) -> String {
+        self.failed = true;
+        SSEEventGenerator::format_error(err.error_type(), &err.client_message())
+    }
+}
+
+fn format_event(event: &str, data: serde_json::Value) -> String {
+    format!("event: {}\ndata: {}\n\n", event, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streaming::MessageAggregator;
+
+    fn convert(chunks: &[&str]) -> (OpenAISseConverter, Vec<String>) {
+        let mut converter = OpenAISseConverter::new("gpt-4o".to_string());
+        let mut events = Vec::new();
+        for chunk in chunks {
+            events.extend(converter.feed(chunk.as_bytes()).unwrap());
+        }
+        (converter, events)
+    }
+
+    fn aggregate(events: &[String]) -> serde_json::Value {
+        let mut aggregator = MessageAggregator::new();
+        for event in events {
+            aggregator.push_event(event);
+        }
+        aggregator.finish().unwrap()
+    }
+
+    #[test]
+    fn test_text_stream() {
+        let (converter, events) = convert(&[
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Hel\"}}]}\n\n",
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"lo\"},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":2}}\n\ndata: [DONE]\n\n",
+        ]);
+
+        assert!(converter.is_finished());
+        assert!(events[0].starts_with("event: message_start"));
+
+        let message = aggregate(&events);
+        assert_eq!(message["content"][0]["text"], "Hello");
+        assert_eq!(message["stop_reason"], "end_turn");
+        assert_eq!(message["usage"]["input_tokens"], 12);
+        assert_eq!(message["usage"]["output_tokens"], 2);
+    }
+
+    #[test]
+    fn test_content_filter_is_a_refusal() {
+        let (_, events) = convert(&[
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"I can't\"},\"finish_reason\":\"content_filter\"}]}\n\ndata: [DONE]\n\n",
+        ]);
+
+        let message = aggregate(&events);
+        assert_eq!(message["stop_reason"], "refusal");
+        assert!(message["stop_sequence"].is_null());
+    }
+
+    #[test]
+    fn test_tool_call_stream_split_across_reads() {
+        let (_, events) = convert(&[
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Let me check.\"}}]}\n",
+            "\ndata: {\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"type\":\"function\",\"function\":{\"name\":\"get_weather\",\"arguments\":\"\"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"{\\\"location\\\":\"}}]}}]}\n\ndata: {\"choices\":[{\"index\":0,\"del",
+            "ta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"\\\"Paris\\\"}\"}}]},\"finish_reason\":\"tool_calls\"}]}\r\n\r\n",
+            "data: [DONE]\n\n",
+        ]);
+
+        let message = aggregate(&events);
+        let content = message["content"].as_array().unwrap();
+        assert_eq!(content.len(), 2);
+        assert_eq!(content[0]["text"], "Let me check.");
+        assert_eq!(content[1]["type"], "tool_use");
+        assert_eq!(content[1]["id"], "call_1");
+        assert_eq!(content[1]["input"]["location"], "Paris");
+        assert_eq!(message["stop_reason"], "tool_use");
+    }
+
+    #[test]
+    fn test_parallel_tool_calls_get_separate_blocks() {
+        let (_, events) = convert(&[
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_a\",\"function\":{\"name\":\"read\",\"arguments\":\"{}\"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":1,\"function\":{\"name\":\"write\",\"arguments\":\"{}\"}}]},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: [DONE]\n\n",
+        ]);
+
+        let message = aggregate(&events);
+        let content = message["content"].as_array().unwrap();
+        assert_eq!(content.len(), 2);
+        assert_eq!(content[0]["name"], "read");
+        assert_eq!(content[1]["name"], "write");
+        assert!(content[1]["id"].as_str().unwrap().starts_with("toolu_"));
+        assert_eq!(message["stop_reason"], "tool_use");
+    }
+
+    #[test]
+    fn test_in_stream_error() {
+        let (converter, events) = convert(&[
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\"}}]}\n\n",
+            ": OPENROUTER PROCESSING\n\ndata: {\"error\":{\"message\":\"Rate limited\",\"code\":429}}\n\n",
+        ]);
+
+        assert!(converter.has_failed());
+        let last = events.last().unwrap();
+        assert!(last.starts_with("event: error"));
+        assert!(last.contains("rate_limit_error"));
+    }
+
+    #[test]
+    fn test_invalid_chunk_is_an_error() {
+        let mut converter = OpenAISseConverter::new("gpt-4o".to_string());
+        assert!(converter.feed(b"data: {not json}\n\n").is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::ProxyError;
 use crate::models::gemini::{GeminiPart, GeminiStreamChunk};
 use crate::state::ConversationState;
 
@@ -6,9 +7,24 @@ pub struct SSEEventGenerator {
     header_sent: bool,
     input_tokens: u32,
     output_tokens: u32,
+    /// Prompt tokens served from a Gemini cached content
+    cached_tokens: u32,
+    /// Tokens written to a cached content for this request
+    cache_creation_tokens: u32,
     model_name: String,
     state: ConversationState,
+    /// Index assigned to the next content block that gets started
     content_block_index: u32,
+    /// Index of the currently open text block, if any
+    open_text_block: Option<u32>,
+    /// Index of the currently open thinking block, if any
+    open_thinking_block: Option<u32>,
+    /// Signature to attach to the open thinking block before it closes
+    thinking_signature: Option<String>,
+    /// Set once message_stop has been emitted
+    finished: bool,
+    /// Set once an error event has been emitted; later chunks are ignored
+    failed: bool,
 }
 
 impl SSEEventGenerator {
@@ -17,9 +33,16 @@ impl SSEEventGenerator {
             header_sent: false,
             input_tokens: 0,
             output_tokens: 0,
+            cached_tokens: 0,
+            cache_creation_tokens: 0,
             model_name,
             state: ConversationState::new(),
             content_block_index: 0,
+            open_text_block: None,
+            open_thinking_block: None,
+            thinking_signature: None,
+            finished: false,
+            failed: false,
         }
     }
 
@@ -28,46 +51,107 @@ impl SSEEventGenerator {
             header_sent: false,
             input_tokens: 0,
             output_tokens: 0,
+            cached_tokens: 0,
+            cache_creation_tokens: 0,
             model_name,
             state,
             content_block_index: 0,
+            open_text_block: None,
+            open_thinking_block: None,
+            thinking_signature: None,
+            finished: false,
+            failed: false,
         }
     }
 
+    /// Record tokens the proxy wrote to a cached content before this response
+    ///
+    /// Gemini counts them as cached input, so they are reported as
+    /// `cache_creation_input_tokens` rather than `cache_read_input_tokens`.
+    pub fn set_cache_creation_tokens(&mut self, tokens: u32) {
+        self.cache_creation_tokens = tokens;
+    }
+
     pub fn generate_events(&mut self, chunk: GeminiStreamChunk) -> Vec<String> {
         let mut events = Vec::new();
 
+        if self.failed {
+            return events;
+        }
+
+        // Gemini can report errors (quota, overload) inside a 200 OK array stream
+        if let Some(error) = &chunk.error {
+            let err = error.to_proxy_error();
+            tracing::error!(error = %err, "Gemini returned an error inside the stream");
+            events.push(self.format_failure(&err));
+            return events;
+        }
+
         // Update token counts from usage metadata (Gemini provides actual counts)
         if let Some(usage) = &chunk.usage_metadata {
             if let Some(prompt_tokens) = usage.prompt_token_count {
                 self.input_tokens = prompt_tokens;
             }
+            if let Some(cached) = usage.cached_content_token_count {
+                self.cached_tokens = cached;
+            }
             if let Some(output) = usage.candidates_token_count {
-                self.output_tokens = output;
+                // Thinking tokens are billed as output, as Claude reports them
+                self.output_tokens = output + usage.thoughts_token_count.unwrap_or(0);
             }
         }
 
         // Send header events on first chunk ONLY if we're going to have content
         // Skip headers if this is an empty response after tool use
         if !self.header_sent && self.chunk_has_meaningful_content(&chunk) {
-            events.push(self.format_message_start());
-            events.push(self.format_content_block_start());
-            self.header_sent = true;
+            if Self::chunk_starts_with_thought(&chunk) {
+                // The thinking block must come first, so don't open a text block yet
+                events.push(self.format_message_start());
+                self.header_sent = true;
+            } else {
+                self.send_headers(&mut events);
+            }
         }
 
         // Process candidates
         if let Some(candidate) = chunk.candidates.first() {
             if let Some(content) = &candidate.content {
                 for part in &content.parts {
+                    // Any non-thought part ends the current thinking block
+                    if !matches!(part, GeminiPart::Thought { thought: true, .. }) {
+                        events.extend(self.close_thinking_block(Self::part_signature(part)));
+                    }
+
                     match part {
+                        GeminiPart::Thought {
+                            text,
+                            thought,
+                            thought_signature,
+                        } => {
+                            if !*thought {
+                                // Not actually a thought; treat like plain text
+                                if !text.trim().is_empty() {
+                                    events.push(self.format_content_block_delta(text));
+                                }
+                                continue;
+                            }
+                            if !self.header_sent {
+                                events.push(self.format_message_start());
+                                self.header_sent = true;
+                            }
+                            if let Some(signature) = thought_signature {
+                                self.thinking_signature = Some(signature.clone());
+                            }
+                            if !text.is_empty() {
+                                events.push(self.format_thinking_delta(text));
+                            }
+                        }
                         GeminiPart::Text { text } => {
                             // Skip empty or whitespace-only text
                             if !text.trim().is_empty() {
                                 // Send headers if not sent yet (for non-empty text)
                                 if !self.header_sent {
-                                    events.push(self.format_message_start());
-                                    events.push(self.format_content_block_start());
-                                    self.header_sent = true;
+                                    self.send_headers(&mut events);
                                 }
                                 events.push(self.format_content_block_delta(text));
                             }
@@ -75,9 +159,7 @@ impl SSEEventGenerator {
                         GeminiPart::TextWithThought { text, .. } => {
                             if !text.trim().is_empty() {
                                 if !self.header_sent {
-                                    events.push(self.format_message_start());
-                                    events.push(self.format_content_block_start());
-                                    self.header_sent = true;
+                                    self.send_headers(&mut events);
                                 }
                                 events.push(self.format_content_block_delta(text));
                             }
@@ -94,8 +176,11 @@ impl SSEEventGenerator {
                                 "Gemini called tool WITH thought_signature"
                             );
 
-                            // Transform to tool_use block
-                            match crate::transform::tools::transform_function_call(function_call) {
+                            // Transform to tool_use block; the id references the signature
+                            match crate::transform::tools::transform_function_call_with_signature(
+                                function_call,
+                                Some(thought_signature),
+                            ) {
                                 Ok((tool_use_id, content_block)) => {
                                     // Register the mapping INCLUDING thought signature AND args
                                     self.state.register_tool_use(
@@ -105,6 +190,10 @@ impl SSEEventGenerator {
                                         function_call.args.clone(),
                                     );
 
+                                    // Close any open text block before starting the tool block
+                                    if let Some(stop) = self.close_text_block() {
+                                        events.push(stop);
+                                    }
                                     // Emit tool_use content block (start + delta)
                                     events.push(self.format_tool_use_start(&content_block));
                                     // Emit content_block_stop for this tool
@@ -137,6 +226,10 @@ impl SSEEventGenerator {
                                         function_call.args.clone(),
                                     );
 
+                                    // Close any open text block before starting the tool block
+                                    if let Some(stop) = self.close_text_block() {
+                                        events.push(stop);
+                                    }
                                     // Emit tool_use content block (start + delta)
                                     events.push(self.format_tool_use_start(&content_block));
                                     // Emit content_block_stop for this tool
@@ -150,7 +243,7 @@ impl SSEEventGenerator {
                                 }
                             }
                         }
-                        GeminiPart::InlineData { .. } => {
+                        GeminiPart::InlineData { .. } | GeminiPart::FileData { .. } => {
                             // Skip inline data in responses for now
                         }
                         GeminiPart::FunctionResponse { .. } => {
@@ -170,19 +263,19 @@ impl SSEEventGenerator {
                 // This handles the case where Gemini returns only whitespace after tool use
                 if !self.header_sent {
                     tracing::debug!("Sending headers for empty response");
-                    events.push(self.format_message_start());
-                    events.push(self.format_content_block_start());
-                    self.header_sent = true;
+                    self.send_headers(&mut events);
                 }
 
-                // Only send content_block_stop if we haven't already sent individual stops for tool_use blocks
-                // For tool calls, we already sent stops for each tool above
-                if !self.has_function_call_in_chunk(&chunk) {
-                    events.push(self.format_content_block_stop());
+                // Tool use blocks are stopped as soon as they are emitted, so only
+                // a still-open thinking or text block needs to be closed here
+                events.extend(self.close_thinking_block(None));
+                if let Some(stop) = self.close_text_block() {
+                    events.push(stop);
                 }
 
                 events.push(self.format_message_delta(stop_reason));
                 events.push(self.format_message_stop());
+                self.finished = true;
             }
         }
 
@@ -200,19 +293,28 @@ impl SSEEventGenerator {
                 "content": [],
                 "stop_reason": null,
                 "stop_sequence": null,
-                "usage": {
-                    "input_tokens": self.input_tokens,
-                    "output_tokens": 1
-                }
+                "usage": self.usage(1)
             }
         });
         format!("event: message_start\ndata: {}\n\n", data)
     }
 
-    fn format_content_block_start(&self) -> String {
+    /// Send message_start followed by the initial text block
+    fn send_headers(&mut self, events: &mut Vec<String>) {
+        events.push(self.format_message_start());
+        events.push(self.format_content_block_start());
+        self.header_sent = true;
+    }
+
+    /// Open a new text block at the next content block index
+    fn format_content_block_start(&mut self) -> String {
+        let index = self.content_block_index;
+        self.content_block_index += 1;
+        self.open_text_block = Some(index);
+
         let data = serde_json::json!({
             "type": "content_block_start",
-            "index": 0,
+            "index": index,
             "content_block": {
                 "type": "text",
                 "text": ""
@@ -221,24 +323,105 @@ impl SSEEventGenerator {
         format!("event: content_block_start\ndata: {}\n\n", data)
     }
 
-    fn format_content_block_delta(&self, text: &str) -> String {
+    /// Format a text delta, reopening a text block if a tool block closed the previous one
+    fn format_content_block_delta(&mut self, text: &str) -> String {
+        let mut events = String::new();
+        let index = match self.open_text_block {
+            Some(index) => index,
+            None => {
+                events.push_str(&self.format_content_block_start());
+                self.content_block_index - 1
+            }
+        };
+
         let data = serde_json::json!({
             "type": "content_block_delta",
-            "index": 0,
+            "index": index,
             "delta": {
                 "type": "text_delta",
                 "text": text
             }
         });
-        format!("event: content_block_delta\ndata: {}\n\n", data)
+        events.push_str(&format!("event: content_block_delta\ndata: {}\n\n", data));
+        events
     }
 
-    fn format_content_block_stop(&self) -> String {
+    /// Close the open text block, returning its content_block_stop event
+    fn close_text_block(&mut self) -> Option<String> {
+        let index = self.open_text_block.take()?;
         let data = serde_json::json!({
             "type": "content_block_stop",
-            "index": 0
+            "index": index
         });
-        format!("event: content_block_stop\ndata: {}\n\n", data)
+        Some(format!("event: content_block_stop\ndata: {}\n\n", data))
+    }
+
+    /// Format a thinking delta, opening a thinking block first if needed
+    fn format_thinking_delta(&mut self, thinking: &str) -> String {
+        let mut events = String::new();
+        let index = match self.open_thinking_block {
+            Some(index) => index,
+            None => {
+                if let Some(stop) = self.close_text_block() {
+                    events.push_str(&stop);
+                }
+                let index = self.content_block_index;
+                self.content_block_index += 1;
+                self.open_thinking_block = Some(index);
+
+                let data = serde_json::json!({
+                    "type": "content_block_start",
+                    "index": index,
+                    "content_block": {
+                        "type": "thinking",
+                        "thinking": "",
+                        "signature": ""
+                    }
+                });
+                events.push_str(&format!("event: content_block_start\ndata: {}\n\n", data));
+                index
+            }
+        };
+
+        let data = serde_json::json!({
+            "type": "content_block_delta",
+            "index": index,
+            "delta": {
+                "type": "thinking_delta",
+                "thinking": thinking
+            }
+        });
+        events.push_str(&format!("event: content_block_delta\ndata: {}\n\n", data));
+        events
+    }
+
+    /// Close the open thinking block, emitting its signature first
+    ///
+    /// Gemini usually attaches the thought signature to the first part after the
+    /// thoughts, so that part's signature is used when the thoughts had none.
+    fn close_thinking_block(&mut self, next_signature: Option<&String>) -> Vec<String> {
+        let Some(index) = self.open_thinking_block.take() else {
+            return Vec::new();
+        };
+
+        let mut events = Vec::new();
+        if let Some(signature) = self.thinking_signature.take().or(next_signature.cloned()) {
+            let data = serde_json::json!({
+                "type": "content_block_delta",
+                "index": index,
+                "delta": {
+                    "type": "signature_delta",
+                    "signature": signature
+                }
+            });
+            events.push(format!("event: content_block_delta\ndata: {}\n\n", data));
+        }
+        let data = serde_json::json!({
+            "type": "content_block_stop",
+            "index": index
+        });
+        events.push(format!("event: content_block_stop\ndata: {}\n\n", data));
+        events
     }
 
     fn format_tool_use_stop(&self) -> String {
@@ -258,13 +441,23 @@ impl SSEEventGenerator {
                 "stop_reason": stop_reason,
                 "stop_sequence": null
             },
-            "usage": {
-                "output_tokens": self.output_tokens
-            }
+            "usage": self.usage(self.output_tokens)
         });
         format!("event: message_delta\ndata: {}\n\n", data)
     }
 
+    /// Claude usage object; Gemini's prompt count includes cached tokens, Claude's doesn't
+    fn usage(&self, output_tokens: u32) -> serde_json::Value {
+        let cached = self.cached_tokens.min(self.input_tokens);
+        let created = self.cache_creation_tokens.min(cached);
+        serde_json::json!({
+            "input_tokens": self.input_tokens - cached,
+            "cache_creation_input_tokens": created,
+            "cache_read_input_tokens": cached - created,
+            "output_tokens": output_tokens
+        })
+    }
+
     fn format_message_stop(&self) -> String {
         "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n".to_string()
     }
@@ -382,6 +575,7 @@ impl SSEEventGenerator {
         chunk.candidates.first().is_some_and(|candidate| {
             candidate.content.as_ref().is_some_and(|content| {
                 content.parts.iter().any(|part| match part {
+                    GeminiPart::Thought { text, .. } => !text.trim().is_empty(),
                     GeminiPart::Text { text } => !text.trim().is_empty(),
                     GeminiPart::TextWithThought { text, .. } => !text.trim().is_empty(),
                     GeminiPart::FunctionCall { .. } => true,
@@ -392,6 +586,36 @@ impl SSEEventGenerator {
         })
     }
 
+    /// Thought signature carried by a non-thought part, if any
+    fn part_signature(part: &GeminiPart) -> Option<&String> {
+        match part {
+            GeminiPart::TextWithThought {
+                thought_signature, ..
+            }
+            | GeminiPart::FunctionCallWithThought {
+                thought_signature, ..
+            } => Some(thought_signature),
+            _ => None,
+        }
+    }
+
+    /// Check if the first meaningful part of the chunk is a thought
+    fn chunk_starts_with_thought(chunk: &GeminiStreamChunk) -> bool {
+        chunk
+            .candidates
+            .first()
+            .and_then(|candidate| candidate.content.as_ref())
+            .and_then(|content| {
+                content.parts.iter().find(|part| match part {
+                    GeminiPart::Text { text } | GeminiPart::TextWithThought { text, .. } => {
+                        !text.trim().is_empty()
+                    }
+                    _ => true,
+                })
+            })
+            .is_some_and(|part| matches!(part, GeminiPart::Thought { thought: true, .. }))
+    }
+
     /// Check if headers have been sent
     pub fn is_header_sent(&self) -> bool {
         self.header_sent
@@ -402,6 +626,24 @@ impl SSEEventGenerator {
         (self.input_tokens, self.output_tokens)
     }
 
+    /// Check if message_stop has been emitted
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Check if an error event has been emitted
+    ///
+    /// Once this returns true the stream should be closed; no further events follow.
+    pub fn has_failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Format a terminal error event for `err` and stop generating further events
+    pub fn format_failure(&mut self, err: &ProxyError) -> String {
+        self.failed = true;
+        Self::format_error(err.error_type(), &err.client_message())
+    }
+
     /// Format error as SSE event
     pub fn format_error(error_type: &str, message: &str) -> String {
         let data = serde_json::json!({
@@ -435,6 +677,7 @@ mod tests {
             }],
             usage_metadata: None,
             prompt_feedback: None,
+            error: None,
         }
     }
 
@@ -450,8 +693,11 @@ mod tests {
                 prompt_token_count: None,
                 candidates_token_count: Some(10),
                 total_token_count: Some(20),
+                thoughts_token_count: None,
+                cached_content_token_count: None,
             }),
             prompt_feedback: None,
+            error: None,
         }
     }
 
@@ -466,8 +712,11 @@ mod tests {
                 prompt_token_count: Some(15),
                 candidates_token_count: None,
                 total_token_count: None,
+                thoughts_token_count: None,
+                cached_content_token_count: None,
             }),
             prompt_feedback: None,
+            error: None,
         };
 
         let events = event_gen.generate_events(chunk);
@@ -535,8 +784,11 @@ mod tests {
                 prompt_token_count: Some(20),
                 candidates_token_count: Some(3),
                 total_token_count: Some(23),
+                thoughts_token_count: None,
+                cached_content_token_count: None,
             }),
             prompt_feedback: None,
+            error: None,
         };
 
         event_gen.generate_events(chunk);
@@ -545,6 +797,35 @@ mod tests {
         assert!(output > 0); // Approximate counting
     }
 
+    #[test]
+    fn test_cached_tokens_reported_as_cache_usage() {
+        let mut event_gen = SSEEventGenerator::new("gemini-3-pro-preview".to_string());
+        event_gen.set_cache_creation_tokens(1500);
+
+        let mut chunk = make_finish_chunk();
+        chunk.usage_metadata = Some(UsageMetadata {
+            prompt_token_count: Some(2100),
+            candidates_token_count: Some(10),
+            total_token_count: Some(2110),
+            thoughts_token_count: None,
+            cached_content_token_count: Some(2000),
+        });
+        event_gen.generate_events(make_text_chunk("Hi"));
+        let events = event_gen.generate_events(chunk);
+
+        let delta = events
+            .iter()
+            .find(|e| e.starts_with("event: message_delta"))
+            .unwrap();
+        let data: serde_json::Value =
+            serde_json::from_str(delta.lines().nth(1).unwrap().trim_start_matches("data: "))
+                .unwrap();
+        assert_eq!(data["usage"]["input_tokens"], 100);
+        assert_eq!(data["usage"]["cache_creation_input_tokens"], 1500);
+        assert_eq!(data["usage"]["cache_read_input_tokens"], 500);
+        assert_eq!(data["usage"]["output_tokens"], 10);
+    }
+
     #[test]
     fn test_finish_reason_mapping() {
         let event_gen = SSEEventGenerator::new("test".to_string());
@@ -598,6 +879,7 @@ mod tests {
             }],
             usage_metadata: None,
             prompt_feedback: None,
+            error: None,
         };
 
         let events = event_gen.generate_events(chunk);
@@ -608,4 +890,98 @@ mod tests {
 
         assert_eq!(delta_events.len(), 2);
     }
+
+    #[test]
+    fn test_in_stream_error_emits_error_event_and_stops() {
+        use crate::models::gemini::GeminiError;
+
+        let mut event_gen = SSEEventGenerator::new("gemini-3-pro-preview".to_string());
+        event_gen.generate_events(make_text_chunk("partial"));
+
+        let error_chunk = GeminiStreamChunk {
+            candidates: vec![],
+            usage_metadata: None,
+            prompt_feedback: None,
+            error: Some(GeminiError {
+                code: 429,
+                message: "Resource has been exhausted".to_string(),
+                status: Some("RESOURCE_EXHAUSTED".to_string()),
+            }),
+        };
+
+        let events = event_gen.generate_events(error_chunk);
+        assert_eq!(events.len(), 1);
+        assert!(events[0].starts_with("event: error\n"));
+        assert!(events[0].contains("rate_limit_error"));
+        assert!(event_gen.has_failed());
+
+        // Anything after the error is dropped
+        assert!(
+            event_gen
+                .generate_events(make_text_chunk("more"))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_thought_parts_stream_as_thinking_block() {
+        let mut event_gen = SSEEventGenerator::new("gemini-3-pro-preview".to_string());
+
+        let thought = |text: &str| GeminiPart::Thought {
+            text: text.to_string(),
+            thought: true,
+            thought_signature: None,
+        };
+        let mut chunk = make_text_chunk("");
+        chunk.candidates[0].content.as_mut().unwrap().parts =
+            vec![thought("Considering"), thought(" the options")];
+        let mut events = event_gen.generate_events(chunk);
+
+        let mut chunk = make_text_chunk("");
+        chunk.candidates[0].content.as_mut().unwrap().parts = vec![GeminiPart::TextWithThought {
+            text: "The answer".to_string(),
+            thought_signature: "c2lnLTE=".to_string(),
+        }];
+        events.extend(event_gen.generate_events(chunk));
+        events.extend(event_gen.generate_events(make_finish_chunk()));
+
+        let data: Vec<serde_json::Value> = events
+            .concat()
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .map(|data| serde_json::from_str(data).unwrap())
+            .collect();
+        let blocks: Vec<_> = data
+            .iter()
+            .filter(|d| d["type"] == "content_block_start")
+            .map(|d| {
+                (
+                    d["index"].as_u64().unwrap(),
+                    d["content_block"]["type"].clone(),
+                )
+            })
+            .collect();
+        // The thinking block comes first, with no placeholder text block before it
+        assert_eq!(blocks, vec![(0, "thinking".into()), (1, "text".into())]);
+
+        let deltas: Vec<_> = data
+            .iter()
+            .filter(|d| d["type"] == "content_block_delta")
+            .map(|d| (d["index"].as_u64().unwrap(), d["delta"].clone()))
+            .collect();
+        assert_eq!(deltas[0].1["thinking"], "Considering");
+        assert_eq!(deltas[1].1["thinking"], " the options");
+        assert_eq!(deltas[2].1["type"], "signature_delta");
+        assert_eq!(deltas[2].1["signature"], "c2lnLTE=");
+
+        // The thoughts don't leak into the answer text
+        assert_eq!(
+            deltas[3],
+            (
+                1,
+                serde_json::json!({"type": "text_delta", "text": "The answer"})
+            )
+        );
+        assert_eq!(deltas.len(), 4);
+    }
 }
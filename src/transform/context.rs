@@ -0,0 +1,128 @@
+use crate::models::claude::ClaudeMessage;
+
+/// How much conversation history is forwarded to the backend
+///
+/// The default forwards everything. `Recent` trims the middle of long sessions
+/// while keeping the original request and every tool call paired with its result.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContextStrategy {
+    /// Forward the full history
+    #[default]
+    Full,
+    /// Keep the first message plus roughly the last `max_messages` messages
+    Recent { max_messages: usize },
+}
+
+impl ContextStrategy {
+    /// Parse a `CONTEXT_MAX_MESSAGES` value; `0` means no limit
+    pub fn from_max_messages(max_messages: usize) -> Self {
+        match max_messages {
+            0 => ContextStrategy::Full,
+            max_messages => ContextStrategy::Recent { max_messages },
+        }
+    }
+
+    /// Trim `messages` in place, returning the number of messages dropped
+    ///
+    /// The kept tail always starts on an assistant turn, so no `tool_result`
+    /// is separated from the `tool_use` it answers and roles keep alternating
+    /// after the first user message. The tail may therefore be one message
+    /// longer than `max_messages`.
+    pub fn apply(&self, messages: &mut Vec<ClaudeMessage>) -> usize {
+        let ContextStrategy::Recent { max_messages } = *self else {
+            return 0;
+        };
+        if messages.len() <= max_messages + 1 {
+            return 0;
+        }
+
+        let mut start = messages.len() - max_messages;
+        while start > 1 && messages[start].role != "assistant" {
+            start -= 1;
+        }
+        if start <= 1 {
+            return 0;
+        }
+
+        let dropped = start - 1;
+        messages.drain(1..start);
+        tracing::info!(
+            dropped,
+            kept = messages.len(),
+            "Trimmed conversation history (context strategy)"
+        );
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::claude::ContentType;
+
+    fn conversation(len: usize) -> Vec<ClaudeMessage> {
+        (0..len)
+            .map(|i| ClaudeMessage {
+                role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                content: ContentType::Text(format!("message {}", i)),
+            })
+            .collect()
+    }
+
+    fn texts(messages: &[ClaudeMessage]) -> Vec<String> {
+        messages
+            .iter()
+            .map(|m| match &m.content {
+                ContentType::Text(text) => text.clone(),
+                ContentType::Blocks(_) => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_full_keeps_everything() {
+        let mut messages = conversation(11);
+        assert_eq!(ContextStrategy::Full.apply(&mut messages), 0);
+        assert_eq!(messages.len(), 11);
+    }
+
+    #[test]
+    fn test_recent_keeps_first_message_and_tail() {
+        let mut messages = conversation(11);
+        let dropped = ContextStrategy::Recent { max_messages: 5 }.apply(&mut messages);
+
+        // Tail of 5 would start on a user turn, so it grows to the preceding assistant turn
+        assert_eq!(dropped, 4);
+        assert_eq!(
+            texts(&messages),
+            vec![
+                "message 0",
+                "message 5",
+                "message 6",
+                "message 7",
+                "message 8",
+                "message 9",
+                "message 10"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recent_short_conversation_untouched() {
+        let mut messages = conversation(5);
+        assert_eq!(
+            ContextStrategy::Recent { max_messages: 4 }.apply(&mut messages),
+            0
+        );
+        assert_eq!(messages.len(), 5);
+    }
+
+    #[test]
+    fn test_from_max_messages() {
+        assert_eq!(ContextStrategy::from_max_messages(0), ContextStrategy::Full);
+        assert_eq!(
+            ContextStrategy::from_max_messages(40),
+            ContextStrategy::Recent { max_messages: 40 }
+        );
+    }
+}
@@ -1,4 +1,7 @@
+pub mod context;
+pub mod openai;
 pub mod request;
+pub mod schema;
 pub mod tools;
 pub mod validation;
 
@@ -6,9 +9,10 @@ pub use request::*;
 pub use tools::*;
 pub use validation::*;
 
-/// Maps Claude model identifiers to appropriate Gemini models
+/// Default Gemini model used when no routing rule matches
+///
+/// See [`crate::routing::ModelRouter`] for configurable per-model routing.
 pub fn map_model_name(_claude_model: &str) -> &'static str {
-    // Currently all Claude models map to Gemini 3 Pro Preview
     "gemini-3-pro-preview"
 }
 
@@ -0,0 +1,569 @@
+use crate::error::{ProxyError, Result};
+use crate::models::claude::{
+    ClaudeRequest, ClaudeTool, ContentBlock, ContentType, DocumentSource, ImageSource,
+    SystemPrompt, ToolChoice,
+};
+use crate::models::openai::{
+    ChatCompletionRequest, ChatContent, ChatMessage, ChatTool, ChatToolChoice, ContentPart,
+    FileContent, FunctionCall, FunctionDefinition, ImageUrl, StreamOptions, ToolCall,
+    ToolChoiceFunction,
+};
+
+/// Transform a Claude request into an OpenAI Chat Completions request
+///
+/// The upstream request is always streamed (with a trailing usage chunk);
+/// non-streaming clients get the stream folded into one message by the handler.
+pub fn transform_request(claude_req: ClaudeRequest, model: &str) -> Result<ChatCompletionRequest> {
+    let mut messages = Vec::new();
+
+    if let Some(system) = convert_system_prompt(claude_req.system) {
+        messages.push(ChatMessage::text("system", system));
+    }
+
+    for msg in claude_req.messages {
+        match msg.role.as_str() {
+            "user" => messages.extend(convert_user_message(msg.content)),
+            "assistant" => messages.push(convert_assistant_message(msg.content)),
+            _ => {
+                return Err(ProxyError::TransformationError(format!(
+                    "Invalid role: {}",
+                    msg.role
+                )));
+            }
+        }
+    }
+
+    let tools = claude_req
+        .tools
+        .map(|tools| tools.into_iter().map(transform_tool).collect::<Vec<_>>());
+
+    // Tool choice only applies when tools are declared
+    let tool_choice = claude_req.tool_choice.filter(|_| tools.is_some());
+    let parallel_tool_calls = tool_choice
+        .as_ref()
+        .filter(|choice| choice.disable_parallel_tool_use())
+        .map(|_| false);
+
+    Ok(ChatCompletionRequest {
+        model: model.to_string(),
+        messages,
+        max_tokens: claude_req.max_tokens,
+        temperature: claude_req.temperature,
+        top_p: claude_req.top_p,
+        stop: claude_req.stop_sequences,
+        stream: true,
+        stream_options: Some(StreamOptions {
+            include_usage: true,
+        }),
+        tools,
+        tool_choice: tool_choice.map(convert_tool_choice),
+        parallel_tool_calls,
+    })
+}
+
+/// Map Claude `tool_choice` to OpenAI's (`any` is called `required` there)
+fn convert_tool_choice(tool_choice: ToolChoice) -> ChatToolChoice {
+    match tool_choice {
+        ToolChoice::Auto { .. } => ChatToolChoice::Mode("auto".to_string()),
+        ToolChoice::Any { .. } => ChatToolChoice::Mode("required".to_string()),
+        ToolChoice::None => ChatToolChoice::Mode("none".to_string()),
+        ToolChoice::Tool { name, .. } => ChatToolChoice::Function {
+            choice_type: "function".to_string(),
+            function: ToolChoiceFunction { name },
+        },
+    }
+}
+
+/// Flatten the Claude system prompt into a single string
+fn convert_system_prompt(system: Option<SystemPrompt>) -> Option<String> {
+    let text = match system? {
+        SystemPrompt::Text(text) => text,
+        SystemPrompt::Blocks(blocks) => blocks
+            .into_iter()
+            .filter_map(|b| match b {
+                ContentBlock::Text { text, .. } => Some(text),
+                _ => None, // Skip non-text blocks in system prompt
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// Convert a user turn into `tool` messages (one per tool_result) followed by the user text
+///
+/// OpenAI requires tool results to directly follow the assistant message that
+/// made the calls, so they are emitted before any accompanying text.
+fn convert_user_message(content: ContentType) -> Vec<ChatMessage> {
+    let blocks = match content {
+        ContentType::Text(text) => return vec![ChatMessage::text("user", text)],
+        ContentType::Blocks(blocks) => blocks,
+    };
+
+    let mut messages = Vec::new();
+    let mut parts = Vec::new();
+
+    for block in blocks {
+        match block {
+            ContentBlock::Text { text, .. } => parts.push(ContentPart::Text { text }),
+            ContentBlock::Image { source } => parts.push(ContentPart::ImageUrl {
+                image_url: ImageUrl {
+                    url: image_url(source),
+                },
+            }),
+            ContentBlock::Document { source, title, .. } => {
+                parts.push(document_part(source, title));
+            }
+            ContentBlock::ToolResult {
+                tool_use_id,
+                content,
+                is_error,
+            } => {
+                // Tool messages are text-only; attached images/documents go
+                // into the user message that follows
+                for attachment in content.attachments().cloned() {
+                    match attachment {
+                        ContentBlock::Image { source } => parts.push(ContentPart::ImageUrl {
+                            image_url: ImageUrl {
+                                url: image_url(source),
+                            },
+                        }),
+                        ContentBlock::Document { source, title, .. } => {
+                            parts.push(document_part(source, title));
+                        }
+                        _ => {}
+                    }
+                }
+                let content = if is_error.unwrap_or(false) {
+                    format!("Error: {}", content.text())
+                } else {
+                    content.text()
+                };
+                messages.push(ChatMessage {
+                    role: "tool".to_string(),
+                    content: Some(ChatContent::Text(content)),
+                    tool_calls: None,
+                    tool_call_id: Some(tool_use_id),
+                });
+            }
+            ContentBlock::ToolUse { name, .. } => {
+                tracing::warn!(tool_name = %name, "Ignoring tool_use block in user message");
+            }
+            ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => {}
+        }
+    }
+
+    if parts.is_empty() {
+        return messages;
+    }
+
+    // Text-only turns stay plain strings for servers without multimodal support
+    let content = if parts.iter().all(|p| matches!(p, ContentPart::Text { .. })) {
+        let text: Vec<_> = parts
+            .into_iter()
+            .filter_map(|p| match p {
+                ContentPart::Text { text } => Some(text),
+                ContentPart::ImageUrl { .. } | ContentPart::File { .. } => None,
+            })
+            .collect();
+        ChatContent::Text(text.join("\n"))
+    } else {
+        ChatContent::Parts(parts)
+    };
+
+    messages.push(ChatMessage {
+        role: "user".to_string(),
+        content: Some(content),
+        tool_calls: None,
+        tool_call_id: None,
+    });
+    messages
+}
+
+/// Image source as an `image_url` (base64 data becomes a `data:` URL)
+fn image_url(source: ImageSource) -> String {
+    match source {
+        ImageSource::Base64 { media_type, data } => format!("data:{};base64,{}", media_type, data),
+        ImageSource::Url { url } => url,
+    }
+}
+
+/// Document source as a content part
+///
+/// PDFs become `file` parts; text documents and URLs (which `file` parts
+/// cannot reference) are sent as text.
+fn document_part(source: DocumentSource, title: Option<String>) -> ContentPart {
+    match source {
+        DocumentSource::Base64 { media_type, data } => ContentPart::File {
+            file: FileContent {
+                filename: title,
+                file_data: format!("data:{};base64,{}", media_type, data),
+            },
+        },
+        DocumentSource::Text { data, .. } => ContentPart::Text {
+            text: match title {
+                Some(title) => format!("{}\n\n{}", title, data),
+                None => data,
+            },
+        },
+        DocumentSource::Url { url } => ContentPart::Text {
+            text: format!("Document: {}", url),
+        },
+    }
+}
+
+/// Convert an assistant turn, turning tool_use blocks into `tool_calls`
+fn convert_assistant_message(content: ContentType) -> ChatMessage {
+    let blocks = match content {
+        ContentType::Text(text) => return ChatMessage::text("assistant", text),
+        ContentType::Blocks(blocks) => blocks,
+    };
+
+    let mut text = Vec::new();
+    let mut tool_calls = Vec::new();
+
+    for block in blocks {
+        match block {
+            ContentBlock::Text { text: t, .. } => text.push(t),
+            ContentBlock::ToolUse { id, name, input } => tool_calls.push(ToolCall {
+                id,
+                call_type: "function".to_string(),
+                function: FunctionCall {
+                    name,
+                    arguments: input.to_string(),
+                },
+            }),
+            ContentBlock::ToolResult { tool_use_id, .. } => {
+                tracing::warn!(tool_use_id = %tool_use_id, "Ignoring tool_result block in assistant message");
+            }
+            ContentBlock::Image { .. } | ContentBlock::Document { .. } => {
+                tracing::warn!("Ignoring attachment block in assistant message");
+            }
+            // Chat Completions has no way to send prior reasoning back
+            ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => {}
+        }
+    }
+
+    // Servers reject an assistant message with neither content nor tool calls,
+    // as left by a turn that only held thinking or attachments
+    ChatMessage {
+        role: "assistant".to_string(),
+        content: (!text.is_empty() || tool_calls.is_empty())
+            .then(|| ChatContent::Text(text.join(""))),
+        tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+        tool_call_id: None,
+    }
+}
+
+/// Transform a Claude tool definition into an OpenAI function tool
+///
+/// OpenAI-compatible servers accept full JSON Schema, so the schema is sent
+/// complete rather than through the Gemini-filtered serializer.
+fn transform_tool(tool: ClaudeTool) -> ChatTool {
+    ChatTool {
+        tool_type: "function".to_string(),
+        function: FunctionDefinition {
+            name: tool.name,
+            description: tool.description,
+            parameters: tool.input_schema.to_json_schema(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::claude::{ClaudeMessage, JsonSchema, ToolResultContent};
+    use serde_json::json;
+
+    fn request(messages: Vec<ClaudeMessage>) -> ClaudeRequest {
+        ClaudeRequest {
+            model: "claude-sonnet-4".to_string(),
+            messages,
+            system: Some(SystemPrompt::Text("You are helpful.".to_string())),
+            max_tokens: Some(1024),
+            temperature: Some(0.2),
+            stop_sequences: None,
+            stream: true,
+            top_p: None,
+            top_k: None,
+            thinking: None,
+            metadata: None,
+            tools: None,
+            tool_choice: None,
+        }
+    }
+
+    #[test]
+    fn test_transform_simple_request() {
+        let req = request(vec![ClaudeMessage {
+            role: "user".to_string(),
+            content: ContentType::Text("Hello".to_string()),
+        }]);
+
+        let openai_req = transform_request(req, "gpt-4o").unwrap();
+        assert_eq!(openai_req.model, "gpt-4o");
+        assert_eq!(openai_req.messages.len(), 2);
+        assert_eq!(openai_req.messages[0].role, "system");
+        assert_eq!(
+            openai_req.messages[1].content.as_ref().unwrap().as_text(),
+            Some("Hello")
+        );
+        assert_eq!(openai_req.max_tokens, Some(1024));
+        assert!(openai_req.stream);
+    }
+
+    #[test]
+    fn test_transform_tool_round_trip() {
+        let mut req = request(vec![
+            ClaudeMessage {
+                role: "user".to_string(),
+                content: ContentType::Text("Weather in Paris?".to_string()),
+            },
+            ClaudeMessage {
+                role: "assistant".to_string(),
+                content: ContentType::Blocks(vec![
+                    ContentBlock::Text {
+                        text: "Checking.".to_string(),
+                        cache_control: None,
+                    },
+                    ContentBlock::ToolUse {
+                        id: "call_1".to_string(),
+                        name: "get_weather".to_string(),
+                        input: json!({"location": "Paris"}),
+                    },
+                ]),
+            },
+            ClaudeMessage {
+                role: "user".to_string(),
+                content: ContentType::Blocks(vec![
+                    ContentBlock::ToolResult {
+                        tool_use_id: "call_1".to_string(),
+                        content: "Sunny".into(),
+                        is_error: None,
+                    },
+                    ContentBlock::Text {
+                        text: "Thanks".to_string(),
+                        cache_control: None,
+                    },
+                ]),
+            },
+        ]);
+        req.tools = Some(vec![ClaudeTool {
+            name: "get_weather".to_string(),
+            description: "Get weather".to_string(),
+            input_schema: JsonSchema {
+                schema_type: "object".to_string(),
+                ..Default::default()
+            },
+            cache_control: None,
+        }]);
+
+        let openai_req = transform_request(req, "gpt-4o").unwrap();
+        let roles: Vec<_> = openai_req
+            .messages
+            .iter()
+            .map(|m| m.role.as_str())
+            .collect();
+        assert_eq!(roles, vec!["system", "user", "assistant", "tool", "user"]);
+
+        let assistant = &openai_req.messages[2];
+        assert_eq!(
+            assistant.content.as_ref().unwrap().as_text(),
+            Some("Checking.")
+        );
+        let call = &assistant.tool_calls.as_ref().unwrap()[0];
+        assert_eq!(call.id, "call_1");
+        assert_eq!(call.function.arguments, r#"{"location":"Paris"}"#);
+
+        assert_eq!(
+            openai_req.messages[3].tool_call_id.as_deref(),
+            Some("call_1")
+        );
+
+        let tools = openai_req.tools.unwrap();
+        assert_eq!(tools[0].tool_type, "function");
+        assert_eq!(tools[0].function.parameters["type"], "object");
+    }
+
+    #[test]
+    fn test_thinking_only_assistant_turn_keeps_content() {
+        let req = request(vec![
+            ClaudeMessage {
+                role: "user".to_string(),
+                content: ContentType::Text("Hi".to_string()),
+            },
+            ClaudeMessage {
+                role: "assistant".to_string(),
+                content: ContentType::Blocks(vec![ContentBlock::Thinking {
+                    thinking: "The user said hi.".to_string(),
+                    signature: "sig".to_string(),
+                }]),
+            },
+        ]);
+
+        let openai_req = transform_request(req, "gpt-4o").unwrap();
+        let assistant = &openai_req.messages[2];
+        assert_eq!(assistant.content.as_ref().unwrap().as_text(), Some(""));
+        assert!(assistant.tool_calls.is_none());
+    }
+
+    #[test]
+    fn test_transform_tool_keeps_full_schema() {
+        let mut req = request(vec![ClaudeMessage {
+            role: "user".to_string(),
+            content: ContentType::Text("Edit the file".to_string()),
+        }]);
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "mode": {"const": "replace"},
+                "target": {"$ref": "#/$defs/path"},
+                "edit": {"oneOf": [{"type": "string"}, {"type": "integer"}]}
+            },
+            "required": ["mode"],
+            "additionalProperties": false,
+            "$defs": {"path": {"type": "string"}}
+        });
+        req.tools = Some(vec![ClaudeTool {
+            name: "edit".to_string(),
+            description: "Edit a file".to_string(),
+            input_schema: serde_json::from_value(schema.clone()).unwrap(),
+            cache_control: None,
+        }]);
+
+        let openai_req = transform_request(req, "gpt-4o").unwrap();
+        assert_eq!(openai_req.tools.unwrap()[0].function.parameters, schema);
+    }
+
+    #[test]
+    fn test_transform_tool_choice() {
+        let mut req = request(vec![ClaudeMessage {
+            role: "user".to_string(),
+            content: ContentType::Text("Weather?".to_string()),
+        }]);
+        req.tools = Some(vec![ClaudeTool {
+            name: "get_weather".to_string(),
+            description: "Get weather".to_string(),
+            input_schema: JsonSchema {
+                schema_type: "object".to_string(),
+                ..Default::default()
+            },
+            cache_control: None,
+        }]);
+        req.tool_choice = Some(ToolChoice::Tool {
+            name: "get_weather".to_string(),
+            disable_parallel_tool_use: Some(true),
+        });
+
+        let json = serde_json::to_value(transform_request(req.clone(), "gpt-4o").unwrap()).unwrap();
+        assert_eq!(
+            json["tool_choice"],
+            json!({"type": "function", "function": {"name": "get_weather"}})
+        );
+        assert_eq!(json["parallel_tool_calls"], false);
+
+        req.tool_choice = Some(ToolChoice::Any {
+            disable_parallel_tool_use: None,
+        });
+        let json = serde_json::to_value(transform_request(req, "gpt-4o").unwrap()).unwrap();
+        assert_eq!(json["tool_choice"], "required");
+        assert!(json.get("parallel_tool_calls").is_none());
+    }
+
+    #[test]
+    fn test_transform_image_blocks() {
+        let req = request(vec![ClaudeMessage {
+            role: "user".to_string(),
+            content: ContentType::Blocks(vec![
+                ContentBlock::Text {
+                    text: "What's wrong here?".to_string(),
+                    cache_control: None,
+                },
+                ContentBlock::Image {
+                    source: ImageSource::Base64 {
+                        media_type: "image/png".to_string(),
+                        data: "iVBORw0KGgo=".to_string(),
+                    },
+                },
+            ]),
+        }]);
+
+        let openai_req = transform_request(req, "gpt-4o").unwrap();
+        let json = serde_json::to_value(&openai_req.messages[1]).unwrap();
+        assert_eq!(json["content"][0]["type"], "text");
+        assert_eq!(json["content"][1]["type"], "image_url");
+        assert_eq!(
+            json["content"][1]["image_url"]["url"],
+            "data:image/png;base64,iVBORw0KGgo="
+        );
+    }
+
+    #[test]
+    fn test_transform_structured_tool_result() {
+        let req = request(vec![ClaudeMessage {
+            role: "user".to_string(),
+            content: ContentType::Blocks(vec![ContentBlock::ToolResult {
+                tool_use_id: "call_1".to_string(),
+                content: ToolResultContent::Blocks(vec![
+                    ContentBlock::Text {
+                        text: "Screenshot taken".to_string(),
+                        cache_control: None,
+                    },
+                    ContentBlock::Image {
+                        source: ImageSource::Base64 {
+                            media_type: "image/png".to_string(),
+                            data: "iVBORw0KGgo=".to_string(),
+                        },
+                    },
+                ]),
+                is_error: None,
+            }]),
+        }]);
+
+        let openai_req = transform_request(req, "gpt-4o").unwrap();
+        let tool = &openai_req.messages[1];
+        assert_eq!(tool.role, "tool");
+        assert_eq!(
+            tool.content.as_ref().unwrap().as_text(),
+            Some("Screenshot taken")
+        );
+
+        let json = serde_json::to_value(&openai_req.messages[2]).unwrap();
+        assert_eq!(json["role"], "user");
+        assert_eq!(json["content"][0]["type"], "image_url");
+    }
+
+    #[test]
+    fn test_transform_document_blocks() {
+        let req = request(vec![ClaudeMessage {
+            role: "user".to_string(),
+            content: ContentType::Blocks(vec![ContentBlock::Document {
+                source: DocumentSource::Base64 {
+                    media_type: "application/pdf".to_string(),
+                    data: "JVBERi0=".to_string(),
+                },
+                title: Some("spec.pdf".to_string()),
+                context: None,
+            }]),
+        }]);
+
+        let openai_req = transform_request(req, "gpt-4o").unwrap();
+        let json = serde_json::to_value(&openai_req.messages[1]).unwrap();
+        assert_eq!(json["content"][0]["type"], "file");
+        assert_eq!(json["content"][0]["file"]["filename"], "spec.pdf");
+        assert_eq!(
+            json["content"][0]["file"]["file_data"],
+            "data:application/pdf;base64,JVBERi0="
+        );
+    }
+
+    #[test]
+    fn test_invalid_role_rejected() {
+        let req = request(vec![ClaudeMessage {
+            role: "system".to_string(),
+            content: ContentType::Text("Hi".to_string()),
+        }]);
+        assert!(transform_request(req, "gpt-4o").is_err());
+    }
+}
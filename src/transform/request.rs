@@ -1,52 +1,128 @@
 use crate::error::{ProxyError, Result};
-use crate::models::claude::{ClaudeRequest, ContentBlock, ContentType, SystemPrompt};
+use crate::models::claude::{
+    ClaudeMessage, ClaudeRequest, ContentBlock, ContentType, DocumentSource, ImageSource,
+    SystemPrompt, ThinkingConfig, ToolChoice,
+};
 use crate::models::gemini::{
-    GeminiContent, GeminiPart, GeminiRequest, GeminiSystemInstruction, GenerationConfig,
+    FileData, FunctionCall, FunctionCallingConfig, FunctionCallingMode, GeminiContent, GeminiPart,
+    GeminiRequest, GeminiSystemInstruction, GenerationConfig, InlineData, ToolConfig,
 };
 use crate::state::ConversationState;
+use crate::transform::tools::{DUMMY_THOUGHT_SIGNATURE, decode_tool_use_id};
+use std::collections::HashMap;
 
 /// Extract Gemini parts from Claude content
 ///
-/// Handles text, tool_use, and tool_result blocks.
+/// Handles text, image, document, tool_use, and tool_result blocks.
 /// Tool blocks require state tracking which is done externally.
 /// Returns (parts, has_non_todo_tool_results)
 pub fn extract_parts(
     content: ContentType,
     state: Option<&ConversationState>,
+) -> Result<(Vec<GeminiPart>, bool)> {
+    convert_content(content, state, &HashMap::new())
+}
+
+/// Map each `tool_use` id in the history to its function name
+///
+/// Lets tool results be paired with their call even when the proxy has no
+/// state for it (e.g. after a restart).
+fn collect_tool_names(messages: &[ClaudeMessage]) -> HashMap<String, String> {
+    messages
+        .iter()
+        .filter_map(|msg| match &msg.content {
+            ContentType::Blocks(blocks) => Some(blocks),
+            ContentType::Text(_) => None,
+        })
+        .flatten()
+        .filter_map(|block| match block {
+            ContentBlock::ToolUse { id, name, .. } => Some((id.clone(), name.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+fn convert_content(
+    content: ContentType,
+    state: Option<&ConversationState>,
+    tool_names: &HashMap<String, String>,
 ) -> Result<(Vec<GeminiPart>, bool)> {
     let mut has_non_todo_tool_results = false;
     match content {
         ContentType::Text(text) => Ok((vec![GeminiPart::Text { text }], false)),
         ContentType::Blocks(blocks) => {
             let mut parts = Vec::new();
+            let mut seen_function_call = false;
+            let mut thinking_signature: Option<String> = None;
             for block in blocks {
                 match block {
-                    ContentBlock::Text { text } => {
+                    ContentBlock::Text { text, .. } => {
                         parts.push(GeminiPart::Text { text });
                     }
                     ContentBlock::ToolUse { id, name, input } => {
-                        // Tool use blocks appear in assistant messages during multi-turn
-                        // SKIP THEM - Gemini doesn't need the model's own function calls in history
-                        // Only user messages with functionResponse are needed
-                        tracing::debug!(
-                            tool_use_id = %id,
-                            tool_name = %name,
-                            args_empty = input.as_object().is_none_or(|o| o.is_empty()),
-                            "Skipping ToolUse block (Gemini doesn't need model's own calls in history)"
-                        );
-                        // Don't add to parts - this creates empty model messages which get filtered
+                        // The model's own calls must stay in history so each
+                        // functionResponse follows the functionCall it answers.
+                        // Gemini 3 also requires the thought signature it sent
+                        // with the call, which is replayed from state.
+                        let is_first_call = !seen_function_call;
+                        seen_function_call = true;
+                        let signature_ref =
+                            decode_tool_use_id(&id).and_then(|decoded| decoded.signature_ref);
+                        let thought_signature = state
+                            .and_then(|state| state.get_metadata(&id))
+                            .and_then(|metadata| metadata.thought_signature)
+                            .or_else(|| {
+                                // The thinking block before the first call carries
+                                // the same signature when the client echoes it back.
+                                // Ids that reference a signature must match it.
+                                let candidate = thinking_signature.take()?;
+                                let matches = match &signature_ref {
+                                    Some(expected) => {
+                                        *expected
+                                            == crate::transform::tools::signature_ref(&candidate)
+                                    }
+                                    None => is_first_call,
+                                };
+                                matches.then_some(candidate)
+                            });
+
+                        // A call that had a signature must carry one again;
+                        // if it was lost, Gemini's documented placeholder lets
+                        // the request through
+                        let thought_signature = match thought_signature {
+                            None if signature_ref.is_some() => {
+                                tracing::warn!(
+                                    tool_use_id = %id,
+                                    tool_name = %name,
+                                    "Thought signature not found in state or history; sending placeholder"
+                                );
+                                Some(DUMMY_THOUGHT_SIGNATURE.to_string())
+                            }
+                            None if is_first_call => {
+                                tracing::warn!(
+                                    tool_use_id = %id,
+                                    tool_name = %name,
+                                    "No thought signature for first function call in model turn; Gemini 3 may reject it"
+                                );
+                                None
+                            }
+                            signature => signature,
+                        };
+
+                        parts.push(rebuild_function_call(name, input, thought_signature));
                     }
                     ContentBlock::ToolResult {
-                        tool_use_id,
-                        content,
-                        is_error,
+                        ref tool_use_id, ..
                     } => {
                         // Transform tool result to function response
-                        // Look up the function name from state
-                        use crate::models::gemini::FunctionResponse;
-
-                        let function_name = if let Some(state) = state {
-                            state.get_function_name(&tool_use_id).unwrap_or_else(|| {
+                        // Look up the function name from the history, then from the
+                        // id itself, then from state
+                        let function_name = if let Some(name) = tool_names.get(tool_use_id) {
+                            name.clone()
+                        } else if let Some(decoded) = decode_tool_use_id(tool_use_id) {
+                            decoded.function_name
+                        } else if let Some(state) = state {
+                            state.get_function_name(tool_use_id).unwrap_or_else(|| {
                                 tracing::warn!(
                                     tool_use_id = %tool_use_id,
                                     "No function name found in state, using tool_use_id as fallback"
@@ -65,16 +141,25 @@ pub fn extract_parts(
                             has_non_todo_tool_results = true;
                         }
 
-                        parts.push(GeminiPart::FunctionResponse {
-                            function_response: FunctionResponse {
-                                name: function_name,
-                                response: serde_json::json!({
-                                    "result": content,
-                                    "error": is_error.unwrap_or(false)
-                                }),
-                            },
-                        });
+                        parts.extend(crate::transform::tools::transform_tool_result(
+                            &block,
+                            function_name,
+                        )?);
+                    }
+                    ContentBlock::Image { source } => {
+                        parts.push(convert_image_source(source));
+                    }
+                    ContentBlock::Document { source, title, .. } => {
+                        parts.push(convert_document_source(source, title));
+                    }
+                    ContentBlock::Thinking { signature, .. } => {
+                        // Gemini doesn't accept thought summaries as input; the
+                        // reasoning context travels via thought signatures instead
+                        if !signature.is_empty() {
+                            thinking_signature = Some(signature);
+                        }
                     }
+                    ContentBlock::RedactedThinking { .. } => {}
                 }
             }
             Ok((parts, has_non_todo_tool_results))
@@ -82,6 +167,171 @@ pub fn extract_parts(
     }
 }
 
+/// Rebuild the Gemini function call part for a `tool_use` block in history
+///
+/// Calls that originally carried a thought signature become
+/// `FunctionCallWithThought` again; parallel calls after the first never had one.
+pub fn rebuild_function_call(
+    name: String,
+    input: serde_json::Value,
+    thought_signature: Option<String>,
+) -> GeminiPart {
+    let function_call = FunctionCall {
+        name,
+        args: if input.is_null() {
+            serde_json::json!({})
+        } else {
+            input
+        },
+    };
+
+    match thought_signature {
+        Some(thought_signature) => GeminiPart::FunctionCallWithThought {
+            function_call,
+            thought_signature,
+        },
+        None => GeminiPart::FunctionCall { function_call },
+    }
+}
+
+/// Convert a Claude image source to a Gemini part
+///
+/// Base64 images are sent inline; URLs are passed by reference as `fileData`.
+pub fn convert_image_source(source: ImageSource) -> GeminiPart {
+    match source {
+        ImageSource::Base64 { media_type, data } => GeminiPart::InlineData {
+            inline_data: InlineData {
+                mime_type: media_type,
+                data,
+            },
+        },
+        ImageSource::Url { url } => GeminiPart::FileData {
+            file_data: FileData {
+                mime_type: guess_image_mime_type(&url).to_string(),
+                file_uri: url,
+            },
+        },
+    }
+}
+
+/// Convert a Claude document source to a Gemini part
+///
+/// PDFs are sent as `application/pdf` inline data (or `fileData` for URLs);
+/// plain-text documents become text parts, prefixed with the title if any.
+pub fn convert_document_source(source: DocumentSource, title: Option<String>) -> GeminiPart {
+    match source {
+        DocumentSource::Base64 { media_type, data } => GeminiPart::InlineData {
+            inline_data: InlineData {
+                mime_type: media_type,
+                data,
+            },
+        },
+        DocumentSource::Text { data, .. } => GeminiPart::Text {
+            text: match title {
+                Some(title) => format!("{}\n\n{}", title, data),
+                None => data,
+            },
+        },
+        DocumentSource::Url { url } => GeminiPart::FileData {
+            file_data: FileData {
+                mime_type: "application/pdf".to_string(),
+                file_uri: url,
+            },
+        },
+    }
+}
+
+/// Map Claude extended thinking to Gemini `thinkingConfig`
+///
+/// An enabled budget turns on thought summaries so they can be streamed back as
+/// `thinking` blocks. `disabled` leaves the model default, since some Gemini
+/// models can't turn thinking off entirely.
+fn convert_thinking_config(
+    thinking: Option<ThinkingConfig>,
+) -> Option<crate::models::gemini::GeminiThinkingConfig> {
+    match thinking? {
+        ThinkingConfig::Enabled { budget_tokens } => {
+            Some(crate::models::gemini::GeminiThinkingConfig {
+                thinking_budget: Some(budget_tokens),
+                include_thoughts: Some(true),
+            })
+        }
+        ThinkingConfig::Disabled => None,
+    }
+}
+
+/// Map Claude `tool_choice` to Gemini `toolConfig.functionCallingConfig`
+///
+/// `auto`, `any` and `none` map to the modes of the same name; a specific tool
+/// becomes `ANY` restricted to that function. Gemini has no switch for
+/// parallel function calls, so `disable_parallel_tool_use` is not forwarded.
+pub fn convert_tool_choice(tool_choice: &ToolChoice) -> ToolConfig {
+    if tool_choice.disable_parallel_tool_use() {
+        tracing::debug!("disable_parallel_tool_use has no Gemini equivalent; ignoring");
+    }
+
+    let (mode, allowed_function_names) = match tool_choice {
+        ToolChoice::Auto { .. } => (FunctionCallingMode::Auto, None),
+        ToolChoice::Any { .. } => (FunctionCallingMode::Any, None),
+        ToolChoice::Tool { name, .. } => (FunctionCallingMode::Any, Some(vec![name.clone()])),
+        ToolChoice::None => (FunctionCallingMode::None, None),
+    };
+
+    ToolConfig {
+        function_calling_config: FunctionCallingConfig {
+            mode,
+            allowed_function_names,
+        },
+    }
+}
+
+/// Maximum total size of inline data in one Gemini request
+///
+/// Gemini rejects requests over 20 MB; larger files must go through the File API.
+pub const GEMINI_INLINE_DATA_LIMIT: usize = 20 * 1024 * 1024;
+
+/// Reject requests whose inline attachments exceed [`GEMINI_INLINE_DATA_LIMIT`]
+///
+/// Sizes are measured on the base64 payload, which is what counts against the limit.
+fn check_inline_data_size(contents: &[GeminiContent]) -> Result<()> {
+    let total: usize = contents
+        .iter()
+        .flat_map(|c| &c.parts)
+        .map(|part| match part {
+            GeminiPart::InlineData { inline_data } => inline_data.data.len(),
+            _ => 0,
+        })
+        .sum();
+
+    if total > GEMINI_INLINE_DATA_LIMIT {
+        return Err(ProxyError::RequestTooLarge(format!(
+            "Inline images and documents total {:.1} MB, exceeding Gemini's {} MB inline data limit. \
+             Attach fewer or smaller files.",
+            total as f64 / (1024.0 * 1024.0),
+            GEMINI_INLINE_DATA_LIMIT / (1024 * 1024)
+        )));
+    }
+    Ok(())
+}
+
+/// Guess an image MIME type from a URL's file extension (Gemini requires one for fileData)
+fn guess_image_mime_type(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "heic" => "image/heic",
+        "heif" => "image/heif",
+        _ => "image/jpeg",
+    }
+}
+
 /// Convert Claude system prompt to Gemini system instruction
 pub fn convert_system_prompt(system: Option<SystemPrompt>) -> Option<GeminiSystemInstruction> {
     system.map(|sys| {
@@ -90,7 +340,7 @@ pub fn convert_system_prompt(system: Option<SystemPrompt>) -> Option<GeminiSyste
             SystemPrompt::Blocks(blocks) => blocks
                 .into_iter()
                 .filter_map(|b| match b {
-                    ContentBlock::Text { text } => Some(GeminiPart::Text { text }),
+                    ContentBlock::Text { text, .. } => Some(GeminiPart::Text { text }),
                     _ => None, // Skip non-text blocks in system prompt
                 })
                 .collect(),
@@ -112,9 +362,8 @@ pub fn transform_request_with_state(
     state: Option<&ConversationState>,
     auto_todo_prompt: bool,
 ) -> Result<GeminiRequest> {
-    // 1. Convert messages
-    // For tool use: only keep first user message + most recent user message with tool results
-    // This prevents accumulation of duplicate tool results across turns
+    // 1. Convert messages, keeping the full history (trimming is up to the
+    // configured ContextStrategy, applied before this point)
     let mut contents = Vec::new();
     let mut has_non_todo_tool_results = false;
     tracing::info!(
@@ -122,24 +371,9 @@ pub fn transform_request_with_state(
         claude_req.messages.len()
     );
 
-    // Find the first user message and the last user message (which should have tool results)
-    let first_user_idx = claude_req.messages.iter().position(|m| m.role == "user");
-    let last_user_idx = claude_req.messages.iter().rposition(|m| m.role == "user");
-
-    for (idx, msg) in claude_req.messages.iter().enumerate() {
-        // For long conversations with tool use, only keep:
-        // - First user message (the original request)
-        // - Last user message (the most recent tool results)
-        // Skip intermediate user messages that contain old tool results
-        if msg.role == "user"
-            && claude_req.messages.len() > 3
-            && Some(idx) != first_user_idx
-            && Some(idx) != last_user_idx
-        {
-            tracing::debug!("Skipping intermediate user message (old tool results)");
-            continue;
-        }
+    let tool_names = collect_tool_names(&claude_req.messages);
 
+    for msg in &claude_req.messages {
         let role = match msg.role.as_str() {
             "assistant" => "model",
             "user" => "user",
@@ -151,14 +385,15 @@ pub fn transform_request_with_state(
             }
         };
 
-        let (parts, msg_has_tool_results) = extract_parts(msg.content.clone(), state)?;
+        let (parts, msg_has_tool_results) =
+            convert_content(msg.content.clone(), state, &tool_names)?;
 
         // Track if this message has non-TodoWrite tool results
         if msg_has_tool_results {
             has_non_todo_tool_results = true;
         }
 
-        // Skip empty messages (can happen when all tool results are TodoWrite or all blocks are ToolUse)
+        // Skip empty messages (e.g. an assistant turn that only held thinking blocks)
         if parts.is_empty() {
             tracing::debug!(
                 role = %role,
@@ -178,6 +413,8 @@ pub fn transform_request_with_state(
         contents.len()
     );
 
+    check_inline_data_size(&contents)?;
+
     // Inject todo update requirement if auto_todo_prompt is enabled and we have tool results
     if auto_todo_prompt && has_non_todo_tool_results {
         tracing::info!("Adding mandatory todo update instruction after tool results");
@@ -208,6 +445,7 @@ pub fn transform_request_with_state(
         top_p: claude_req.top_p,
         top_k: claude_req.top_k,
         stop_sequences: claude_req.stop_sequences,
+        thinking_config: convert_thinking_config(claude_req.thinking),
     });
 
     // 4. Transform tools if present
@@ -216,12 +454,20 @@ pub fn transform_request_with_state(
         .map(crate::transform::tools::transform_tools)
         .transpose()?;
 
+    // 5. Tool choice only applies when tools are declared
+    let tool_config = match (&tools, &claude_req.tool_choice) {
+        (Some(_), Some(tool_choice)) => Some(convert_tool_choice(tool_choice)),
+        _ => None,
+    };
+
     Ok(GeminiRequest {
         contents,
         system_instruction,
         generation_config,
         safety_settings: None, // Use Gemini defaults
         tools,
+        tool_config,
+        cached_content: None,
     })
 }
 
@@ -243,14 +489,124 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_extract_parts_images() {
+        let blocks = vec![
+            ContentBlock::Text {
+                text: "What's wrong with this UI?".to_string(),
+                cache_control: None,
+            },
+            ContentBlock::Image {
+                source: ImageSource::Base64 {
+                    media_type: "image/png".to_string(),
+                    data: "iVBORw0KGgo=".to_string(),
+                },
+            },
+            ContentBlock::Image {
+                source: ImageSource::Url {
+                    url: "https://example.com/shots/Login.WEBP?v=2".to_string(),
+                },
+            },
+        ];
+        let (parts, _) = extract_parts(ContentType::Blocks(blocks), None).unwrap();
+
+        assert_eq!(parts.len(), 3);
+        let json = serde_json::to_value(&parts).unwrap();
+        assert_eq!(json[1]["inlineData"]["mimeType"], "image/png");
+        assert_eq!(json[1]["inlineData"]["data"], "iVBORw0KGgo=");
+        assert_eq!(json[2]["fileData"]["mimeType"], "image/webp");
+        assert_eq!(
+            json[2]["fileData"]["fileUri"],
+            "https://example.com/shots/Login.WEBP?v=2"
+        );
+    }
+
+    #[test]
+    fn test_extract_parts_documents() {
+        let blocks = vec![
+            ContentBlock::Document {
+                source: DocumentSource::Base64 {
+                    media_type: "application/pdf".to_string(),
+                    data: "JVBERi0xLjQ=".to_string(),
+                },
+                title: Some("Design spec".to_string()),
+                context: None,
+            },
+            ContentBlock::Document {
+                source: DocumentSource::Text {
+                    media_type: "text/plain".to_string(),
+                    data: "Buttons must be 44px tall.".to_string(),
+                },
+                title: Some("Guidelines".to_string()),
+                context: None,
+            },
+        ];
+        let (parts, _) = extract_parts(ContentType::Blocks(blocks), None).unwrap();
+
+        let json = serde_json::to_value(&parts).unwrap();
+        assert_eq!(json[0]["inlineData"]["mimeType"], "application/pdf");
+        assert_eq!(json[0]["inlineData"]["data"], "JVBERi0xLjQ=");
+        assert_eq!(json[1]["text"], "Guidelines\n\nButtons must be 44px tall.");
+    }
+
+    #[test]
+    fn test_inline_data_size_limit() {
+        let pdf = |size: usize| ContentBlock::Document {
+            source: DocumentSource::Base64 {
+                media_type: "application/pdf".to_string(),
+                data: "A".repeat(size),
+            },
+            title: None,
+            context: None,
+        };
+        let request = |blocks| ClaudeRequest {
+            model: "claude-3-5-sonnet".to_string(),
+            messages: vec![ClaudeMessage {
+                role: "user".to_string(),
+                content: ContentType::Blocks(blocks),
+            }],
+            system: None,
+            max_tokens: Some(100),
+            temperature: None,
+            stop_sequences: None,
+            stream: true,
+            top_p: None,
+            top_k: None,
+            thinking: None,
+            metadata: None,
+            tools: None,
+            tool_choice: None,
+        };
+
+        let half = GEMINI_INLINE_DATA_LIMIT / 2;
+        assert!(transform_request(request(vec![pdf(half)])).is_ok());
+
+        let err = transform_request(request(vec![pdf(half), pdf(half + 1)])).unwrap_err();
+        assert_eq!(err.error_type(), "request_too_large");
+        assert!(err.to_string().contains("20 MB"));
+    }
+
+    #[test]
+    fn test_guess_image_mime_type() {
+        assert_eq!(guess_image_mime_type("https://x.io/a.png"), "image/png");
+        assert_eq!(guess_image_mime_type("https://x.io/a.JPG"), "image/jpeg");
+        assert_eq!(
+            guess_image_mime_type("https://x.io/a.gif#frag"),
+            "image/gif"
+        );
+        assert_eq!(guess_image_mime_type("https://x.io/image"), "image/jpeg");
+    }
+
     #[test]
     fn test_extract_parts_blocks() {
         let blocks = vec![
             ContentBlock::Text {
                 text: "First".to_string(),
+                cache_control: None,
             },
             ContentBlock::Text {
                 text: "Second".to_string(),
+                cache_control: None,
             },
         ];
         let content = ContentType::Blocks(blocks);
@@ -293,7 +649,10 @@ mod tests {
             stream: true,
             top_p: None,
             top_k: None,
+            thinking: None,
+            metadata: None,
             tools: None,
+            tool_choice: None,
         };
 
         let gemini_req = transform_request(claude_req).unwrap();
@@ -312,6 +671,301 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_transform_request_thinking_config() {
+        let mut claude_req = ClaudeRequest {
+            model: "claude-sonnet-4".to_string(),
+            messages: vec![ClaudeMessage {
+                role: "user".to_string(),
+                content: ContentType::Text("Think hard".to_string()),
+            }],
+            system: None,
+            max_tokens: Some(16000),
+            temperature: None,
+            stop_sequences: None,
+            stream: true,
+            top_p: None,
+            top_k: None,
+            thinking: Some(ThinkingConfig::Enabled {
+                budget_tokens: 8192,
+            }),
+            metadata: None,
+            tools: None,
+            tool_choice: None,
+        };
+
+        let gemini_req = transform_request(claude_req.clone()).unwrap();
+        let json = serde_json::to_value(&gemini_req).unwrap();
+        assert_eq!(
+            json["generationConfig"]["thinkingConfig"],
+            serde_json::json!({"thinkingBudget": 8192, "includeThoughts": true})
+        );
+
+        claude_req.thinking = Some(ThinkingConfig::Disabled);
+        let gemini_req = transform_request(claude_req).unwrap();
+        let json = serde_json::to_value(&gemini_req).unwrap();
+        assert!(json["generationConfig"].get("thinkingConfig").is_none());
+    }
+
+    #[test]
+    fn test_transform_request_tool_choice() {
+        let mut claude_req: ClaudeRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4",
+            "messages": [{"role": "user", "content": "Plan the work"}],
+            "tools": [{
+                "name": "TodoWrite",
+                "description": "Write todos",
+                "input_schema": {"type": "object"}
+            }],
+            "tool_choice": {"type": "tool", "name": "TodoWrite", "disable_parallel_tool_use": true}
+        }))
+        .unwrap();
+
+        let json = serde_json::to_value(transform_request(claude_req.clone()).unwrap()).unwrap();
+        assert_eq!(
+            json["toolConfig"],
+            serde_json::json!({
+                "functionCallingConfig": {"mode": "ANY", "allowedFunctionNames": ["TodoWrite"]}
+            })
+        );
+
+        for (choice, mode) in [
+            (serde_json::json!({"type": "auto"}), "AUTO"),
+            (serde_json::json!({"type": "any"}), "ANY"),
+            (serde_json::json!({"type": "none"}), "NONE"),
+        ] {
+            claude_req.tool_choice = Some(serde_json::from_value(choice).unwrap());
+            let json =
+                serde_json::to_value(transform_request(claude_req.clone()).unwrap()).unwrap();
+            assert_eq!(json["toolConfig"]["functionCallingConfig"]["mode"], mode);
+            assert!(
+                json["toolConfig"]["functionCallingConfig"]
+                    .get("allowedFunctionNames")
+                    .is_none()
+            );
+        }
+
+        // Without tools there is nothing to configure
+        claude_req.tools = None;
+        let json = serde_json::to_value(transform_request(claude_req).unwrap()).unwrap();
+        assert!(json.get("toolConfig").is_none());
+    }
+
+    #[test]
+    fn test_extract_parts_skips_thinking_blocks() {
+        let blocks = vec![
+            ContentBlock::Thinking {
+                thinking: "The user wants a greeting".to_string(),
+                signature: "c2ln".to_string(),
+            },
+            ContentBlock::Text {
+                text: "Hello!".to_string(),
+                cache_control: None,
+            },
+        ];
+
+        let (parts, _) = extract_parts(ContentType::Blocks(blocks), None).unwrap();
+        assert_eq!(
+            parts,
+            vec![GeminiPart::Text {
+                text: "Hello!".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_transform_request_keeps_full_tool_history() {
+        let tool_turn = |id: &str, command: &str| ClaudeMessage {
+            role: "assistant".to_string(),
+            content: ContentType::Blocks(vec![ContentBlock::ToolUse {
+                id: id.to_string(),
+                name: "Bash".to_string(),
+                input: serde_json::json!({"command": command}),
+            }]),
+        };
+        let result_turn = |id: &str, output: &str| ClaudeMessage {
+            role: "user".to_string(),
+            content: ContentType::Blocks(vec![ContentBlock::ToolResult {
+                tool_use_id: id.to_string(),
+                content: output.into(),
+                is_error: None,
+            }]),
+        };
+
+        let claude_req = ClaudeRequest {
+            model: "claude-sonnet-4".to_string(),
+            messages: vec![
+                ClaudeMessage {
+                    role: "user".to_string(),
+                    content: ContentType::Text("Run the tests".to_string()),
+                },
+                tool_turn("toolu_1", "cargo build"),
+                result_turn("toolu_1", "Finished"),
+                tool_turn("toolu_2", "cargo test"),
+                result_turn("toolu_2", "ok. 12 passed"),
+            ],
+            system: None,
+            max_tokens: None,
+            temperature: None,
+            stop_sequences: None,
+            stream: true,
+            top_p: None,
+            top_k: None,
+            thinking: None,
+            metadata: None,
+            tools: None,
+            tool_choice: None,
+        };
+
+        // No state: function names are recovered from the history itself
+        let gemini_req = transform_request(claude_req).unwrap();
+        let roles: Vec<_> = gemini_req
+            .contents
+            .iter()
+            .map(|c| c.role.as_deref().unwrap())
+            .collect();
+        assert_eq!(roles, vec!["user", "model", "user", "model", "user"]);
+
+        match &gemini_req.contents[1].parts[0] {
+            GeminiPart::FunctionCall { function_call } => {
+                assert_eq!(function_call.name, "Bash");
+                assert_eq!(function_call.args["command"], "cargo build");
+            }
+            other => panic!("Expected FunctionCall, got {:?}", other),
+        }
+        match &gemini_req.contents[2].parts[0] {
+            GeminiPart::FunctionResponse { function_response } => {
+                assert_eq!(function_response.name, "Bash");
+                assert_eq!(function_response.response["result"], "Finished");
+            }
+            other => panic!("Expected FunctionResponse, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_extract_parts_signature_from_thinking_block() {
+        let blocks = vec![
+            ContentBlock::Thinking {
+                thinking: "Need to list files".to_string(),
+                signature: "sig-from-thinking".to_string(),
+            },
+            ContentBlock::ToolUse {
+                id: "toolu_unknown_1".to_string(),
+                name: "Bash".to_string(),
+                input: serde_json::json!({"command": "ls"}),
+            },
+            ContentBlock::ToolUse {
+                id: "toolu_unknown_2".to_string(),
+                name: "Bash".to_string(),
+                input: serde_json::json!({"command": "pwd"}),
+            },
+        ];
+
+        // Not in state: the first call falls back to the echoed thinking signature
+        let state = ConversationState::new();
+        let (parts, _) = extract_parts(ContentType::Blocks(blocks), Some(&state)).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert!(matches!(
+            &parts[0],
+            GeminiPart::FunctionCallWithThought { thought_signature, .. }
+                if thought_signature == "sig-from-thinking"
+        ));
+        assert!(matches!(&parts[1], GeminiPart::FunctionCall { .. }));
+    }
+
+    #[test]
+    fn test_extract_parts_tool_result_without_state() {
+        // A tool result arriving after a restart: no history, nothing in state
+        let tool_use_id = crate::transform::tools::encode_tool_use_id("Grep", Some("sig"));
+        let blocks = vec![ContentBlock::ToolResult {
+            tool_use_id,
+            content: "3 matches".into(),
+            is_error: None,
+        }];
+
+        let state = ConversationState::new();
+        let (parts, _) = extract_parts(ContentType::Blocks(blocks), Some(&state)).unwrap();
+        match &parts[0] {
+            GeminiPart::FunctionResponse { function_response } => {
+                assert_eq!(function_response.name, "Grep");
+            }
+            other => panic!("Expected FunctionResponse, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_extract_parts_signature_ref_must_match_thinking_block() {
+        use crate::transform::tools::encode_tool_use_id;
+
+        let tool_use = |signature: &str| ContentBlock::ToolUse {
+            id: encode_tool_use_id("Bash", Some(signature)),
+            name: "Bash".to_string(),
+            input: serde_json::json!({}),
+        };
+        let thinking = ContentBlock::Thinking {
+            thinking: "Listing files".to_string(),
+            signature: "sig-a".to_string(),
+        };
+
+        let state = ConversationState::new();
+        let (parts, _) = extract_parts(
+            ContentType::Blocks(vec![thinking.clone(), tool_use("sig-a")]),
+            Some(&state),
+        )
+        .unwrap();
+        assert!(matches!(
+            &parts[0],
+            GeminiPart::FunctionCallWithThought { thought_signature, .. } if thought_signature == "sig-a"
+        ));
+
+        // A thinking signature that isn't the one the call referenced is not
+        // replayed; the placeholder stands in for the lost one
+        let (parts, _) = extract_parts(
+            ContentType::Blocks(vec![thinking, tool_use("sig-b")]),
+            Some(&state),
+        )
+        .unwrap();
+        assert!(matches!(
+            &parts[0],
+            GeminiPart::FunctionCallWithThought { thought_signature, .. }
+                if thought_signature == DUMMY_THOUGHT_SIGNATURE
+        ));
+    }
+
+    #[test]
+    fn test_extract_parts_after_restart_with_empty_state() {
+        use crate::transform::tools::encode_tool_use_id;
+
+        // History replayed to a fresh proxy: no state, thinking blocks not echoed
+        let blocks = vec![
+            ContentBlock::ToolUse {
+                id: encode_tool_use_id("Read", Some("sig-before-restart")),
+                name: "Read".to_string(),
+                input: serde_json::json!({"path": "src/main.rs"}),
+            },
+            ContentBlock::ToolUse {
+                id: encode_tool_use_id("Grep", None),
+                name: "Grep".to_string(),
+                input: serde_json::json!({"pattern": "fn main"}),
+            },
+        ];
+
+        let state = ConversationState::new();
+        let (parts, _) = extract_parts(ContentType::Blocks(blocks), Some(&state)).unwrap();
+        match &parts[0] {
+            GeminiPart::FunctionCallWithThought {
+                function_call,
+                thought_signature,
+            } => {
+                assert_eq!(function_call.name, "Read");
+                assert_eq!(thought_signature, DUMMY_THOUGHT_SIGNATURE);
+            }
+            other => panic!("Expected FunctionCallWithThought, got {:?}", other),
+        }
+        // Parallel calls never had a signature and don't get one
+        assert!(matches!(&parts[1], GeminiPart::FunctionCall { .. }));
+    }
+
     #[test]
     fn test_transform_request_with_system() {
         let claude_req = ClaudeRequest {
@@ -327,7 +981,10 @@ mod tests {
             stream: true,
             top_p: None,
             top_k: None,
+            thinking: None,
+            metadata: None,
             tools: None,
+            tool_choice: None,
         };
 
         let gemini_req = transform_request(claude_req).unwrap();
@@ -360,7 +1017,10 @@ mod tests {
             stream: true,
             top_p: None,
             top_k: None,
+            thinking: None,
+            metadata: None,
             tools: None,
+            tool_choice: None,
         };
 
         let gemini_req = transform_request(claude_req).unwrap();
@@ -385,7 +1045,10 @@ mod tests {
             stream: true,
             top_p: None,
             top_k: None,
+            thinking: None,
+            metadata: None,
             tools: None,
+            tool_choice: None,
         };
 
         let result = transform_request(claude_req);
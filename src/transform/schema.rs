@@ -0,0 +1,532 @@
+use crate::models::claude::{GEMINI_SCHEMA_KEYWORDS, JsonSchema};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// A tool schema lowered to Gemini's OpenAPI subset
+#[derive(Debug, Clone)]
+pub struct LoweredSchema {
+    pub schema: JsonSchema,
+    /// Human-readable notes on constraints that could not be expressed
+    pub lost: Vec<String>,
+}
+
+/// Keywords dropped without a warning: they carry no constraint on the arguments
+const IGNORED_KEYWORDS: &[&str] = &["$schema", "$id", "$comment", "$defs", "definitions"];
+
+/// `format` values Gemini accepts, per type
+const SUPPORTED_FORMATS: &[(&str, &str)] = &[
+    ("string", "enum"),
+    ("string", "date-time"),
+    ("integer", "int32"),
+    ("integer", "int64"),
+    ("number", "float"),
+    ("number", "double"),
+];
+
+/// Lower a JSON Schema to what Gemini's function declarations accept
+///
+/// - `$ref` into `$defs`/`definitions` is inlined (recursive references become a plain object)
+/// - `const` becomes a one-value `enum`
+/// - `type: [T, "null"]` becomes `type: T` with `nullable: true`
+/// - `anyOf`/`oneOf` with a `null` variant collapse to a nullable schema; other
+///   unions are kept as `anyOf`, and `allOf` of objects is merged
+/// - unsupported keywords and formats are dropped and reported in [`LoweredSchema::lost`]
+pub fn lower_schema(schema: &JsonSchema) -> LoweredSchema {
+    let mut lowering = Lowering {
+        defs: collect_defs(schema),
+        resolving: Vec::new(),
+        lost: Vec::new(),
+    };
+    let schema = lowering.lower(schema, "#");
+    LoweredSchema {
+        schema,
+        lost: lowering.lost,
+    }
+}
+
+fn collect_defs(schema: &JsonSchema) -> HashMap<String, Value> {
+    let mut defs = HashMap::new();
+    for (prefix, key) in [("#/$defs/", "$defs"), ("#/definitions/", "definitions")] {
+        if let Some(Value::Object(entries)) = schema.additional.get(key) {
+            for (name, def) in entries {
+                defs.insert(format!("{}{}", prefix, name), def.clone());
+            }
+        }
+    }
+    defs
+}
+
+struct Lowering {
+    defs: HashMap<String, Value>,
+    /// `$ref`s currently being inlined, to break cycles
+    resolving: Vec<String>,
+    lost: Vec<String>,
+}
+
+impl Lowering {
+    fn lose(&mut self, path: &str, what: impl std::fmt::Display) {
+        self.lost.push(format!("{}: {}", path, what));
+    }
+
+    fn parse(&mut self, value: &Value, path: &str) -> Option<JsonSchema> {
+        match serde_json::from_value(value.clone()) {
+            Ok(schema) => Some(schema),
+            Err(e) => {
+                self.lose(path, format_args!("unreadable subschema ({})", e));
+                None
+            }
+        }
+    }
+
+    fn lower(&mut self, schema: &JsonSchema, path: &str) -> JsonSchema {
+        let mut extra = schema.additional.clone();
+
+        if let Some(reference) = extra.remove("$ref")
+            && let Some(mut resolved) = self.resolve(&reference, path)
+        {
+            if schema.description.is_some() {
+                resolved.description = schema.description.clone();
+            }
+            return resolved;
+        }
+
+        let mut out = JsonSchema {
+            schema_type: schema.schema_type.clone(),
+            description: schema.description.clone(),
+            properties: None,
+            required: None,
+            enum_values: schema.enum_values.clone(),
+            items: None,
+            minimum: schema.minimum,
+            maximum: schema.maximum,
+            pattern: schema.pattern.clone(),
+            additional: HashMap::new(),
+        };
+
+        if let Some(properties) = &schema.properties {
+            out.properties = Some(
+                properties
+                    .iter()
+                    .map(|(name, property)| {
+                        let lowered =
+                            self.lower(property, &format!("{}/properties/{}", path, name));
+                        (name.clone(), Box::new(lowered))
+                    })
+                    .collect(),
+            );
+        }
+        if let Some(items) = &schema.items {
+            out.items = Some(Box::new(self.lower(items, &format!("{}/items", path))));
+        }
+        if let Some(required) = &schema.required {
+            out.required = Some(required.clone());
+        }
+
+        if let Some(types) = extra.remove("type") {
+            self.lower_type_array(&types, &mut out, path);
+        }
+
+        if let Some(value) = extra.remove("const") {
+            match value {
+                Value::String(_) if out.enum_values.is_none() => {
+                    if out.schema_type.is_empty() {
+                        out.schema_type = "string".to_string();
+                    }
+                    out.enum_values = Some(vec![value]);
+                }
+                other => self.lose(
+                    path,
+                    format_args!("const {} (Gemini enums only hold strings)", other),
+                ),
+            }
+        }
+
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(variants) = extra.remove(keyword) {
+                if keyword == "oneOf" {
+                    self.lose(path, "oneOf lowered to anyOf (exclusivity not enforced)");
+                }
+                self.lower_union(&variants, &mut out, &format!("{}/{}", path, keyword));
+            }
+        }
+
+        if let Some(variants) = extra.remove("allOf") {
+            self.lower_all_of(&variants, &mut out, &format!("{}/allOf", path));
+        }
+
+        if let Some(bound) = extra.remove("exclusiveMinimum") {
+            match bound.as_f64() {
+                Some(bound) if out.minimum.is_none() => {
+                    out.minimum = Some(bound);
+                    self.lose(path, "exclusiveMinimum relaxed to minimum");
+                }
+                _ => self.lose(path, "exclusiveMinimum"),
+            }
+        }
+        if let Some(bound) = extra.remove("exclusiveMaximum") {
+            match bound.as_f64() {
+                Some(bound) if out.maximum.is_none() => {
+                    out.maximum = Some(bound);
+                    self.lose(path, "exclusiveMaximum relaxed to maximum");
+                }
+                _ => self.lose(path, "exclusiveMaximum"),
+            }
+        }
+
+        if let Some(format) = extra.remove("format") {
+            let supported = format.as_str().is_some_and(|format| {
+                SUPPORTED_FORMATS.contains(&(out.schema_type.as_str(), format))
+            });
+            if supported {
+                out.additional.insert("format".to_string(), format);
+            } else {
+                self.lose(path, format_args!("format {}", format));
+            }
+        }
+
+        // Gemini rejects `required` names that are not declared properties
+        if let (Some(required), Some(properties)) = (&mut out.required, &out.properties) {
+            let missing: Vec<String> = required
+                .iter()
+                .filter(|name| !properties.contains_key(*name))
+                .cloned()
+                .collect();
+            if !missing.is_empty() {
+                required.retain(|name| properties.contains_key(name));
+                self.lose(
+                    path,
+                    format_args!("required names without a property: {}", missing.join(", ")),
+                );
+            }
+        }
+
+        let mut dropped: Vec<String> = Vec::new();
+        for (keyword, value) in extra {
+            if GEMINI_SCHEMA_KEYWORDS.contains(&keyword.as_str()) {
+                out.additional.entry(keyword).or_insert(value);
+            } else if !IGNORED_KEYWORDS.contains(&keyword.as_str()) {
+                dropped.push(keyword);
+            }
+        }
+        if !dropped.is_empty() {
+            dropped.sort();
+            self.lose(path, format_args!("dropped {}", dropped.join(", ")));
+        }
+
+        out
+    }
+
+    fn resolve(&mut self, reference: &Value, path: &str) -> Option<JsonSchema> {
+        let Some(reference) = reference.as_str() else {
+            self.lose(path, "non-string $ref");
+            return None;
+        };
+
+        if self.resolving.iter().any(|r| r == reference) {
+            self.lose(
+                path,
+                format_args!("recursive $ref {} replaced by a plain object", reference),
+            );
+            return Some(JsonSchema {
+                schema_type: "object".to_string(),
+                ..Default::default()
+            });
+        }
+
+        let Some(target) = self.defs.get(reference).cloned() else {
+            self.lose(path, format_args!("unresolved $ref {}", reference));
+            return None;
+        };
+        let target = self.parse(&target, path)?;
+
+        self.resolving.push(reference.to_string());
+        let lowered = self.lower(&target, path);
+        self.resolving.pop();
+        Some(lowered)
+    }
+
+    /// `type: ["string", "null"]` → `type: "string", nullable: true`
+    fn lower_type_array(&mut self, types: &Value, out: &mut JsonSchema, path: &str) {
+        let Some(types) = types.as_array() else {
+            self.lose(path, format_args!("type {}", types));
+            return;
+        };
+
+        let mut names: Vec<&str> = types.iter().filter_map(Value::as_str).collect();
+        if names.contains(&"null") {
+            names.retain(|name| *name != "null");
+            out.additional
+                .insert("nullable".to_string(), Value::Bool(true));
+        }
+
+        match names.as_slice() {
+            [] => {}
+            [single] => out.schema_type = single.to_string(),
+            several => {
+                let variants = several
+                    .iter()
+                    .map(|name| serde_json::json!({ "type": name }))
+                    .collect();
+                out.additional
+                    .insert("anyOf".to_string(), Value::Array(variants));
+            }
+        }
+    }
+
+    /// `anyOf: [X, {type: null}]` collapses to a nullable X; other unions stay `anyOf`
+    fn lower_union(&mut self, variants: &Value, out: &mut JsonSchema, path: &str) {
+        let Some(variants) = variants.as_array() else {
+            self.lose(path, "union is not an array");
+            return;
+        };
+
+        let mut nullable = false;
+        let mut lowered = Vec::new();
+        for (index, variant) in variants.iter().enumerate() {
+            let variant_path = format!("{}/{}", path, index);
+            let Some(variant) = self.parse(variant, &variant_path) else {
+                continue;
+            };
+            if variant.schema_type == "null" {
+                nullable = true;
+                continue;
+            }
+            lowered.push(self.lower(&variant, &variant_path));
+        }
+
+        if nullable {
+            out.additional
+                .insert("nullable".to_string(), Value::Bool(true));
+        }
+        match lowered.len() {
+            0 => {}
+            1 if is_unconstrained(out) => merge_into(out, lowered.remove(0)),
+            _ => {
+                let variants = lowered
+                    .iter()
+                    .filter_map(|variant| serde_json::to_value(variant).ok())
+                    .collect();
+                out.additional
+                    .insert("anyOf".to_string(), Value::Array(variants));
+            }
+        }
+    }
+
+    /// `allOf` is merged when every part is an object schema
+    fn lower_all_of(&mut self, variants: &Value, out: &mut JsonSchema, path: &str) {
+        let Some(variants) = variants.as_array() else {
+            self.lose(path, "allOf is not an array");
+            return;
+        };
+
+        for (index, variant) in variants.iter().enumerate() {
+            let variant_path = format!("{}/{}", path, index);
+            let Some(variant) = self.parse(variant, &variant_path) else {
+                continue;
+            };
+            let variant = self.lower(&variant, &variant_path);
+
+            if is_unconstrained(out) {
+                merge_into(out, variant);
+            } else if out.schema_type == "object" && variant.schema_type == "object" {
+                let properties = out.properties.get_or_insert_with(HashMap::new);
+                properties.extend(variant.properties.unwrap_or_default());
+                if let Some(required) = variant.required {
+                    out.required.get_or_insert_with(Vec::new).extend(required);
+                }
+            } else {
+                self.lose(&variant_path, "allOf part that is not an object");
+            }
+        }
+    }
+}
+
+/// True if the schema has no type or structure of its own yet
+fn is_unconstrained(schema: &JsonSchema) -> bool {
+    schema.schema_type.is_empty()
+        && schema.properties.is_none()
+        && schema.items.is_none()
+        && schema.enum_values.is_none()
+        && !schema.additional.contains_key("anyOf")
+}
+
+/// Take `from`'s structure, keeping `into`'s description and flags
+fn merge_into(into: &mut JsonSchema, from: JsonSchema) {
+    let description = into.description.take().or(from.description);
+    let mut additional = from.additional;
+    additional.extend(std::mem::take(&mut into.additional));
+
+    *into = JsonSchema {
+        description,
+        additional,
+        ..from
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn lower(schema: Value) -> (Value, Vec<String>) {
+        let schema: JsonSchema = serde_json::from_value(schema).unwrap();
+        let lowered = lower_schema(&schema);
+        (serde_json::to_value(&lowered.schema).unwrap(), lowered.lost)
+    }
+
+    #[test]
+    fn test_inlines_refs() {
+        let (schema, lost) = lower(json!({
+            "type": "object",
+            "properties": {
+                "home": {"$ref": "#/$defs/Address", "description": "Home address"},
+                "work": {"$ref": "#/definitions/Address"}
+            },
+            "$defs": {
+                "Address": {
+                    "type": "object",
+                    "properties": {"city": {"type": "string"}},
+                    "required": ["city"]
+                }
+            },
+            "definitions": {
+                "Address": {"type": "object", "properties": {"zip": {"type": "string"}}}
+            }
+        }));
+
+        assert_eq!(schema["properties"]["home"]["description"], "Home address");
+        assert_eq!(
+            schema["properties"]["home"]["properties"]["city"]["type"],
+            "string"
+        );
+        assert_eq!(
+            schema["properties"]["work"]["properties"]["zip"]["type"],
+            "string"
+        );
+        assert!(schema.get("$defs").is_none());
+        assert!(lost.is_empty(), "{:?}", lost);
+    }
+
+    #[test]
+    fn test_recursive_ref_becomes_object() {
+        let (schema, lost) = lower(json!({
+            "$ref": "#/$defs/Node",
+            "$defs": {
+                "Node": {
+                    "type": "object",
+                    "properties": {"children": {"type": "array", "items": {"$ref": "#/$defs/Node"}}}
+                }
+            }
+        }));
+
+        assert_eq!(schema["type"], "object");
+        assert_eq!(
+            schema["properties"]["children"]["items"],
+            json!({"type": "object"})
+        );
+        assert_eq!(lost.len(), 1);
+        assert!(lost[0].contains("recursive $ref"));
+    }
+
+    #[test]
+    fn test_const_and_nullable_types() {
+        let (schema, lost) = lower(json!({
+            "type": "object",
+            "properties": {
+                "kind": {"const": "file"},
+                "limit": {"type": ["integer", "null"]},
+                "value": {"type": ["string", "number"]}
+            }
+        }));
+
+        let properties = &schema["properties"];
+        assert_eq!(
+            properties["kind"],
+            json!({"type": "string", "enum": ["file"]})
+        );
+        assert_eq!(
+            properties["limit"],
+            json!({"type": "integer", "nullable": true})
+        );
+        assert_eq!(
+            properties["value"]["anyOf"],
+            json!([{"type": "string"}, {"type": "number"}])
+        );
+        assert!(lost.is_empty(), "{:?}", lost);
+    }
+
+    #[test]
+    fn test_any_of() {
+        let (schema, lost) = lower(json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "description": "Optional path",
+                    "anyOf": [{"type": "string"}, {"type": "null"}]
+                },
+                "target": {
+                    "anyOf": [
+                        {"type": "string"},
+                        {"type": "object", "properties": {"id": {"type": "integer"}}}
+                    ]
+                }
+            }
+        }));
+
+        assert_eq!(
+            schema["properties"]["path"],
+            json!({"type": "string", "description": "Optional path", "nullable": true})
+        );
+        let target = &schema["properties"]["target"]["anyOf"];
+        assert_eq!(target[0]["type"], "string");
+        assert_eq!(target[1]["properties"]["id"]["type"], "integer");
+        assert!(lost.is_empty(), "{:?}", lost);
+    }
+
+    #[test]
+    fn test_all_of_objects_merged() {
+        let (schema, _) = lower(json!({
+            "allOf": [
+                {"type": "object", "properties": {"a": {"type": "string"}}, "required": ["a"]},
+                {"type": "object", "properties": {"b": {"type": "integer"}}}
+            ]
+        }));
+
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"]["a"]["type"], "string");
+        assert_eq!(schema["properties"]["b"]["type"], "integer");
+        assert_eq!(schema["required"], json!(["a"]));
+    }
+
+    #[test]
+    fn test_reports_lost_constraints() {
+        let (schema, lost) = lower(json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "url": {"type": "string", "format": "uri"},
+                "when": {"type": "string", "format": "date-time"},
+                "count": {"type": "integer", "exclusiveMinimum": 0, "default": 1},
+                "tags": {"type": "array", "items": {"type": "string"}, "uniqueItems": true, "minItems": 1}
+            },
+            "required": ["url", "missing"]
+        }));
+
+        assert!(schema.get("additionalProperties").is_none());
+        assert!(schema["properties"]["url"].get("format").is_none());
+        assert_eq!(schema["properties"]["when"]["format"], "date-time");
+        assert_eq!(schema["properties"]["count"]["minimum"], 0.0);
+        assert_eq!(schema["properties"]["count"]["default"], 1);
+        assert_eq!(schema["properties"]["tags"]["minItems"], 1);
+        assert_eq!(schema["required"], json!(["url"]));
+
+        let lost = lost.join("\n");
+        assert!(lost.contains("#: dropped additionalProperties"));
+        assert!(lost.contains("#/properties/url: format \"uri\""));
+        assert!(lost.contains("#/properties/count: exclusiveMinimum relaxed"));
+        assert!(lost.contains("#/properties/tags: dropped uniqueItems"));
+        assert!(lost.contains("required names without a property: missing"));
+        assert!(!lost.contains("$schema"));
+    }
+}
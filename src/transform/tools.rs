@@ -38,8 +38,18 @@ fn transform_tool(tool: ClaudeTool) -> Result<GeminiFunctionDeclaration> {
 /// Generates a unique tool_use_id and returns the tool use content block.
 /// Returns the tool_use_id for state tracking.
 pub fn transform_function_call(function_call: &FunctionCall) -> Result<(String, ContentBlock)> {
-    // Generate unique tool_use_id
-    let id = format!("toolu_{}", uuid::Uuid::new_v4().simple());
+    transform_function_call_with_signature(function_call, None)
+}
+
+/// Transform Gemini function call to Claude tool use block, recording a
+/// reference to its thought signature in the id
+///
+/// See [`encode_tool_use_id`] for the id format.
+pub fn transform_function_call_with_signature(
+    function_call: &FunctionCall,
+    thought_signature: Option<&str>,
+) -> Result<(String, ContentBlock)> {
+    let id = encode_tool_use_id(&function_call.name, thought_signature);
 
     let block = ContentBlock::ToolUse {
         id: id.clone(),
@@ -50,29 +60,139 @@ pub fn transform_function_call(function_call: &FunctionCall) -> Result<(String,
     Ok((id, block))
 }
 
+/// Placeholder Gemini documents for function calls whose real signature is lost
+///
+/// Used when a call is known to have carried a thought signature (its id holds
+/// a [`signature_ref`]) but neither state nor an echoed thinking block has it,
+/// e.g. after a restart with in-memory state. Gemini 3 accepts the call but the
+/// original reasoning context for that turn is gone.
+pub const DUMMY_THOUGHT_SIGNATURE: &str = "context_engineering_is_the_way_to_go";
+
+/// Function name and signature reference decoded from a proxy-generated tool_use_id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolUseRef {
+    pub function_name: String,
+    /// [`signature_ref`] of the thought signature Gemini sent with the call
+    pub signature_ref: Option<String>,
+}
+
+/// Build a self-describing tool_use_id
+///
+/// Format: `toolu_<nonce>[_<signature ref>]_<hex function name>`, using only
+/// `[a-zA-Z0-9_]` as Claude requires. The function name can be recovered with
+/// [`decode_tool_use_id`] even when no proxy instance has state for the id,
+/// e.g. after a restart or behind a load balancer. Signatures are too large to
+/// embed, so only a hash is kept; a lost signature is replaced with
+/// [`DUMMY_THOUGHT_SIGNATURE`].
+pub fn encode_tool_use_id(function_name: &str, thought_signature: Option<&str>) -> String {
+    let nonce = uuid::Uuid::new_v4().simple().to_string();
+    let name: String = function_name
+        .bytes()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    match thought_signature {
+        Some(signature) => format!(
+            "toolu_{}_{}_{}",
+            &nonce[..16],
+            signature_ref(signature),
+            name
+        ),
+        None => format!("toolu_{}_{}", &nonce[..16], name),
+    }
+}
+
+/// Decode an id produced by [`encode_tool_use_id`]
+///
+/// Returns `None` for ids in any other format (e.g. issued by older versions).
+pub fn decode_tool_use_id(tool_use_id: &str) -> Option<ToolUseRef> {
+    let fields: Vec<&str> = tool_use_id.strip_prefix("toolu_")?.split('_').collect();
+    let is_hex = |s: &str| s.bytes().all(|b| b.is_ascii_hexdigit());
+
+    let (nonce, signature_ref, name) = match fields.as_slice() {
+        [nonce, name] => (*nonce, None, *name),
+        [nonce, signature_ref, name] if signature_ref.len() == 12 && is_hex(signature_ref) => {
+            (*nonce, Some(signature_ref.to_string()), *name)
+        }
+        _ => return None,
+    };
+    if nonce.len() != 16 || !is_hex(nonce) || name.is_empty() || name.len() % 2 != 0 {
+        return None;
+    }
+
+    let bytes = (0..name.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&name[i..i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    Some(ToolUseRef {
+        function_name: String::from_utf8(bytes).ok()?,
+        signature_ref,
+    })
+}
+
+/// Short content hash identifying a thought signature
+pub fn signature_ref(thought_signature: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(thought_signature.as_bytes())[..6]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
 /// Transform Claude tool result to Gemini function response
 ///
 /// Requires the function name which should be looked up from state.
+/// Text parts are concatenated into the `functionResponse`; images and
+/// documents follow it as sibling parts (`inlineData`/`fileData`).
 pub fn transform_tool_result(
     tool_result: &ContentBlock,
     function_name: String,
-) -> Result<crate::models::gemini::GeminiPart> {
+) -> Result<Vec<crate::models::gemini::GeminiPart>> {
     use crate::models::gemini::GeminiPart;
+    use crate::transform::request::{convert_document_source, convert_image_source};
 
     TOOL_METRICS.record_tool_result();
 
     match tool_result {
         ContentBlock::ToolResult {
             content, is_error, ..
-        } => Ok(GeminiPart::FunctionResponse {
-            function_response: FunctionResponse {
-                name: function_name,
-                response: serde_json::json!({
-                    "result": content,
-                    "error": is_error.unwrap_or(false)
-                }),
-            },
-        }),
+        } => {
+            let attachments: Vec<GeminiPart> = content
+                .attachments()
+                .filter_map(|block| match block {
+                    ContentBlock::Image { source } => Some(convert_image_source(source.clone())),
+                    ContentBlock::Document { source, title, .. } => {
+                        Some(convert_document_source(source.clone(), title.clone()))
+                    }
+                    other => {
+                        tracing::warn!(block = ?other, "Dropping unsupported block in tool_result");
+                        None
+                    }
+                })
+                .collect();
+
+            let mut result = content.text();
+            if result.is_empty() && !attachments.is_empty() {
+                result = format!(
+                    "Tool returned {} attachment(s), see below",
+                    attachments.len()
+                );
+            }
+
+            let mut parts = vec![GeminiPart::FunctionResponse {
+                function_response: FunctionResponse {
+                    name: function_name,
+                    response: serde_json::json!({
+                        "result": result,
+                        "error": is_error.unwrap_or(false)
+                    }),
+                },
+            }];
+            parts.extend(attachments);
+            Ok(parts)
+        }
         _ => {
             TOOL_METRICS.record_failure();
             Err(ProxyError::TransformationError(
@@ -122,6 +242,7 @@ mod tests {
                 required: Some(vec!["location".to_string()]),
                 ..Default::default()
             },
+            cache_control: None,
         }
     }
 
@@ -146,6 +267,42 @@ mod tests {
         assert_eq!(result[0].function_declarations[0].name, "get_weather");
     }
 
+    #[test]
+    fn test_tool_use_id_round_trip() {
+        let id = encode_tool_use_id("mcp__github.search:v2", Some("c2lnbmF0dXJl"));
+        assert!(
+            id.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+            "invalid characters in {}",
+            id
+        );
+
+        let decoded = decode_tool_use_id(&id).unwrap();
+        assert_eq!(decoded.function_name, "mcp__github.search:v2");
+        assert_eq!(decoded.signature_ref, Some(signature_ref("c2lnbmF0dXJl")));
+
+        let decoded = decode_tool_use_id(&encode_tool_use_id("Bash", None)).unwrap();
+        assert_eq!(decoded.function_name, "Bash");
+        assert_eq!(decoded.signature_ref, None);
+
+        // Ids are unique even for the same call
+        assert_ne!(
+            encode_tool_use_id("Bash", None),
+            encode_tool_use_id("Bash", None)
+        );
+    }
+
+    #[test]
+    fn test_decode_foreign_tool_use_ids() {
+        assert_eq!(decode_tool_use_id("toolu_01A09q90qw90lq917835lq9"), None);
+        assert_eq!(
+            decode_tool_use_id("toolu_4f1c2a9b8e7d6c5b4a3f2e1d0c9b8a7f"),
+            None
+        );
+        assert_eq!(decode_tool_use_id("call_abc123"), None);
+        assert_eq!(decode_tool_use_id("toolu_0123456789abcdef_zz"), None);
+    }
+
     #[test]
     fn test_transform_function_call() {
         let fc = FunctionCall {
@@ -174,13 +331,14 @@ mod tests {
 
         let result_block = ContentBlock::ToolResult {
             tool_use_id: "toolu_123".to_string(),
-            content: "Sunny, 72°F".to_string(),
+            content: "Sunny, 72°F".into(),
             is_error: None,
         };
 
-        let gemini_part = transform_tool_result(&result_block, "get_weather".to_string()).unwrap();
+        let parts = transform_tool_result(&result_block, "get_weather".to_string()).unwrap();
+        assert_eq!(parts.len(), 1);
 
-        match gemini_part {
+        match &parts[0] {
             GeminiPart::FunctionResponse { function_response } => {
                 assert_eq!(function_response.name, "get_weather");
                 assert_eq!(function_response.response["result"], "Sunny, 72°F");
@@ -196,13 +354,13 @@ mod tests {
 
         let result_block = ContentBlock::ToolResult {
             tool_use_id: "toolu_123".to_string(),
-            content: "API key invalid".to_string(),
+            content: "API key invalid".into(),
             is_error: Some(true),
         };
 
-        let gemini_part = transform_tool_result(&result_block, "get_weather".to_string()).unwrap();
+        let parts = transform_tool_result(&result_block, "get_weather".to_string()).unwrap();
 
-        match gemini_part {
+        match &parts[0] {
             GeminiPart::FunctionResponse { function_response } => {
                 assert_eq!(function_response.response["error"], true);
             }
@@ -210,6 +368,52 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_transform_structured_tool_result() {
+        use crate::models::claude::{ImageSource, ToolResultContent};
+        use crate::models::gemini::GeminiPart;
+
+        let result_block = ContentBlock::ToolResult {
+            tool_use_id: "toolu_123".to_string(),
+            content: ToolResultContent::Blocks(vec![
+                ContentBlock::Text {
+                    text: "Rendered page".to_string(),
+                    cache_control: None,
+                },
+                ContentBlock::Image {
+                    source: ImageSource::Base64 {
+                        media_type: "image/png".to_string(),
+                        data: "iVBORw0KGgo=".to_string(),
+                    },
+                },
+                ContentBlock::Text {
+                    text: "2 warnings".to_string(),
+                    cache_control: None,
+                },
+            ]),
+            is_error: None,
+        };
+
+        let parts = transform_tool_result(&result_block, "Read".to_string()).unwrap();
+        assert_eq!(parts.len(), 2);
+        match &parts[0] {
+            GeminiPart::FunctionResponse { function_response } => {
+                assert_eq!(
+                    function_response.response["result"],
+                    "Rendered page\n2 warnings"
+                );
+            }
+            _ => panic!("Expected FunctionResponse"),
+        }
+        match &parts[1] {
+            GeminiPart::InlineData { inline_data } => {
+                assert_eq!(inline_data.mime_type, "image/png");
+                assert_eq!(inline_data.data, "iVBORw0KGgo=");
+            }
+            _ => panic!("Expected InlineData"),
+        }
+    }
+
     #[test]
     fn test_complex_schema_transformation() {
         // Test nested objects and arrays
@@ -247,6 +451,7 @@ mod tests {
                 properties: Some(properties),
                 ..Default::default()
             },
+            cache_control: None,
         };
 
         let result = transform_tool(tool).unwrap();
@@ -286,6 +491,7 @@ mod tests {
                 properties: Some(properties),
                 ..Default::default()
             },
+            cache_control: None,
         };
 
         let result = transform_tool(tool).unwrap();
@@ -1,5 +1,10 @@
 use crate::error::{ProxyError, Result};
-use crate::models::claude::ClaudeRequest;
+use crate::models::claude::{
+    ClaudeRequest, ContentBlock, ContentType, DocumentSource, ImageSource, ToolResultContent,
+};
+
+/// Image formats accepted by the Claude API
+const SUPPORTED_IMAGE_TYPES: &[&str] = &["image/jpeg", "image/png", "image/gif", "image/webp"];
 
 /// Validate Claude request before transformation
 pub fn validate_claude_request(req: &ClaudeRequest) -> Result<()> {
@@ -31,6 +36,13 @@ pub fn validate_claude_request(req: &ClaudeRequest) -> Result<()> {
         prev_role = Some(&msg.role);
     }
 
+    // Validate image and document blocks, including those inside tool results
+    for msg in &req.messages {
+        if let ContentType::Blocks(blocks) = &msg.content {
+            blocks.iter().try_for_each(validate_attachment)?;
+        }
+    }
+
     // Check token limits
     if let Some(max_tokens) = req.max_tokens
         && (max_tokens == 0 || max_tokens > 1_000_000)
@@ -73,10 +85,37 @@ pub fn validate_claude_request(req: &ClaudeRequest) -> Result<()> {
     Ok(())
 }
 
+/// Check the media type of an image or document block
+fn validate_attachment(block: &ContentBlock) -> Result<()> {
+    match block {
+        ContentBlock::Image {
+            source: ImageSource::Base64 { media_type, .. },
+        } if !SUPPORTED_IMAGE_TYPES.contains(&media_type.as_str()) => {
+            Err(ProxyError::InvalidClaudeRequest(format!(
+                "Unsupported image media_type: {}. Must be one of: {}",
+                media_type,
+                SUPPORTED_IMAGE_TYPES.join(", ")
+            )))
+        }
+        ContentBlock::Document {
+            source: DocumentSource::Base64 { media_type, .. },
+            ..
+        } if media_type != "application/pdf" => Err(ProxyError::InvalidClaudeRequest(format!(
+            "Unsupported document media_type: {}. Base64 documents must be application/pdf",
+            media_type
+        ))),
+        ContentBlock::ToolResult {
+            content: ToolResultContent::Blocks(blocks),
+            ..
+        } => blocks.iter().try_for_each(validate_attachment),
+        _ => Ok(()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::claude::{ClaudeMessage, ContentType};
+    use crate::models::claude::ClaudeMessage;
 
     fn make_simple_request() -> ClaudeRequest {
         ClaudeRequest {
@@ -92,7 +131,10 @@ mod tests {
             stream: true,
             top_p: None,
             top_k: None,
+            thinking: None,
+            metadata: None,
             tools: None,
+            tool_choice: None,
         }
     }
 
@@ -154,6 +196,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_validate_image_media_type() {
+        let mut req = make_simple_request();
+        req.messages[0].content = ContentType::Blocks(vec![ContentBlock::Image {
+            source: ImageSource::Base64 {
+                media_type: "image/bmp".to_string(),
+                data: "Qk0=".to_string(),
+            },
+        }]);
+
+        let result = validate_claude_request(&req);
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Unsupported image media_type")
+        );
+    }
+
+    #[test]
+    fn test_validate_attachments_inside_tool_results() {
+        let tool_result = |block| ContentBlock::ToolResult {
+            tool_use_id: "toolu_1".to_string(),
+            content: ToolResultContent::Blocks(vec![block]),
+            is_error: None,
+        };
+
+        let mut req = make_simple_request();
+        req.messages[0].content = ContentType::Blocks(vec![tool_result(ContentBlock::Image {
+            source: ImageSource::Base64 {
+                media_type: "image/bmp".to_string(),
+                data: "Qk0=".to_string(),
+            },
+        })]);
+        let err = validate_claude_request(&req).unwrap_err();
+        assert!(matches!(err, ProxyError::InvalidClaudeRequest(_)));
+        assert!(err.to_string().contains("Unsupported image media_type"));
+
+        req.messages[0].content = ContentType::Blocks(vec![tool_result(ContentBlock::Document {
+            source: DocumentSource::Base64 {
+                media_type: "text/csv".to_string(),
+                data: "YSxi".to_string(),
+            },
+            title: None,
+            context: None,
+        })]);
+        assert!(
+            validate_claude_request(&req)
+                .unwrap_err()
+                .to_string()
+                .contains("Unsupported document media_type")
+        );
+    }
+
     #[test]
     fn test_validate_max_tokens_zero() {
         let mut req = make_simple_request();
@@ -141,6 +141,7 @@ mod tests {
                 properties: Some(properties),
                 ..Default::default()
             },
+            cache_control: None,
         }
     }
 
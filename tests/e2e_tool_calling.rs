@@ -75,6 +75,7 @@ fn test_e2e_todo_write_flow() {
             required: Some(vec!["todos".to_string()]),
             ..Default::default()
         },
+        cache_control: None,
     };
 
     // Turn 1: User asks to create todos
@@ -91,7 +92,10 @@ fn test_e2e_todo_write_flow() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: Some(vec![todo_tool]),
+        tool_choice: None,
     };
 
     let gemini_req1 = transform_request_with_state(turn1, Some(&state), false).unwrap();
@@ -160,7 +164,7 @@ fn test_e2e_todo_write_flow() {
                 role: "user".to_string(),
                 content: ContentType::Blocks(vec![ContentBlock::ToolResult {
                     tool_use_id: tool_use_id.clone(),
-                    content: "Todos have been added successfully".to_string(),
+                    content: "Todos have been added successfully".into(),
                     is_error: None,
                 }]),
             },
@@ -172,23 +176,34 @@ fn test_e2e_todo_write_flow() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     let gemini_req2 = transform_request_with_state(turn2, Some(&state), false).unwrap();
 
-    // Verify the conversation flow - model messages with only ToolUse are skipped
-    // We should have 2 messages: initial user request + user message with TodoWrite result
-    assert_eq!(gemini_req2.contents.len(), 2);
+    // Verify the conversation flow - the model's TodoWrite call stays in history
+    // so the function response has a call to pair with
+    assert_eq!(gemini_req2.contents.len(), 3);
 
     // First message: original user request
     assert_eq!(gemini_req2.contents[0].role, Some("user".to_string()));
 
-    // Second message: user message with TodoWrite tool result
-    assert_eq!(gemini_req2.contents[1].role, Some("user".to_string()));
+    // Second message: model turn with the TodoWrite function call
+    assert_eq!(gemini_req2.contents[1].role, Some("model".to_string()));
+    assert!(matches!(
+        &gemini_req2.contents[1].parts[0],
+        GeminiPart::FunctionCall { function_call } | GeminiPart::FunctionCallWithThought { function_call, .. }
+            if function_call.name == "TodoWrite"
+    ));
+
+    // Third message: user message with TodoWrite tool result
+    assert_eq!(gemini_req2.contents[2].role, Some("user".to_string()));
 
     // Verify that the second message contains the TodoWrite function response
-    let has_todo_write_response = gemini_req2.contents[1].parts.iter().any(|p| {
+    let has_todo_write_response = gemini_req2.contents[2].parts.iter().any(|p| {
         matches!(p, GeminiPart::FunctionResponse { function_response }
                 if function_response.name == "TodoWrite")
     });
@@ -220,6 +235,7 @@ fn test_e2e_bash_tool() {
             required: Some(vec!["command".to_string()]),
             ..Default::default()
         },
+        cache_control: None,
     };
 
     let req = ClaudeRequest {
@@ -235,7 +251,10 @@ fn test_e2e_bash_tool() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: Some(vec![bash_tool]),
+        tool_choice: None,
     };
 
     let gemini_req = transform_request(req).unwrap();
@@ -288,6 +307,7 @@ fn test_e2e_edit_tool() {
             ]),
             ..Default::default()
         },
+        cache_control: None,
     };
 
     let gemini_req = transform_request(ClaudeRequest {
@@ -303,7 +323,10 @@ fn test_e2e_edit_tool() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: Some(vec![edit_tool]),
+        tool_choice: None,
     })
     .unwrap();
 
@@ -401,6 +424,7 @@ fn test_e2e_ask_user_question_complex() {
             required: Some(vec!["questions".to_string()]),
             ..Default::default()
         },
+        cache_control: None,
     };
 
     // Validate the complex schema
@@ -458,6 +482,7 @@ fn test_e2e_error_handling() {
             required: Some(vec!["url".to_string()]),
             ..Default::default()
         },
+        cache_control: None,
     };
 
     // Initial request
@@ -474,7 +499,10 @@ fn test_e2e_error_handling() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: Some(vec![web_tool]),
+        tool_choice: None,
     };
 
     let gemini_req = transform_request_with_state(req, Some(&state), false).unwrap();
@@ -497,13 +525,14 @@ fn test_e2e_error_handling() {
     // Simulate error result
     let error_result = ContentBlock::ToolResult {
         tool_use_id: tool_id,
-        content: "Connection timeout after 30s".to_string(),
+        content: "Connection timeout after 30s".into(),
         is_error: Some(true),
     };
 
-    let function_response = transform_tool_result(&error_result, "WebFetch".to_string()).unwrap();
+    let mut parts = transform_tool_result(&error_result, "WebFetch".to_string()).unwrap();
+    assert_eq!(parts.len(), 1);
 
-    match function_response {
+    match parts.remove(0) {
         GeminiPart::FunctionResponse { function_response } => {
             assert_eq!(function_response.name, "WebFetch");
             assert_eq!(function_response.response["error"], true);
@@ -545,6 +574,7 @@ fn test_all_claude_code_tools() {
                 properties: Some(HashMap::new()),
                 ..Default::default()
             },
+            cache_control: None,
         };
 
         let result = transform_tools(vec![tool]);
@@ -571,6 +601,7 @@ fn test_schema_caching() {
             schema_type: "object".to_string(),
             ..Default::default()
         },
+        cache_control: None,
     };
 
     // First transformation - cache miss
@@ -604,6 +635,7 @@ fn test_metrics_tracking() {
             schema_type: "object".to_string(),
             ..Default::default()
         },
+        cache_control: None,
     };
 
     // Transform should record metrics
@@ -634,6 +666,7 @@ fn test_schema_field_filtering() {
             additional,
             ..Default::default()
         },
+        cache_control: None,
     };
 
     let result = transform_tools(vec![tool]).unwrap();
@@ -665,6 +698,7 @@ fn test_validation_prevents_invalid_tools() {
             schema_type: "object".to_string(),
             ..Default::default()
         },
+        cache_control: None,
     };
 
     assert!(validate_tools(&[invalid_tool]).is_err());
@@ -677,6 +711,7 @@ fn test_validation_prevents_invalid_tools() {
             schema_type: "object".to_string(),
             ..Default::default()
         },
+        cache_control: None,
     };
 
     let tool2 = ClaudeTool {
@@ -686,6 +721,7 @@ fn test_validation_prevents_invalid_tools() {
             schema_type: "object".to_string(),
             ..Default::default()
         },
+        cache_control: None,
     };
 
     assert!(validate_tools(&[tool1, tool2]).is_err());
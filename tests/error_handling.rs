@@ -14,7 +14,10 @@ fn test_empty_messages_validation() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     let result = validate_claude_request(&req);
@@ -38,7 +41,10 @@ fn test_invalid_first_role() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     let result = validate_claude_request(&req);
@@ -76,7 +82,10 @@ fn test_consecutive_assistant_messages() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     let result = validate_claude_request(&req);
@@ -104,7 +113,10 @@ fn test_invalid_max_tokens() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     assert!(validate_claude_request(&req).is_err());
@@ -131,7 +143,10 @@ fn test_invalid_temperature() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     assert!(validate_claude_request(&req).is_err());
@@ -158,7 +173,10 @@ fn test_invalid_top_p() {
         stream: true,
         top_p: Some(-0.1),
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     assert!(validate_claude_request(&req).is_err());
@@ -185,7 +203,10 @@ fn test_invalid_role_in_transformation() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     let result = transform_request(req);
@@ -283,7 +304,10 @@ fn test_empty_content_handling() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     // Empty content should still validate
@@ -309,7 +333,10 @@ fn test_edge_case_parameters() {
         stream: true,
         top_p: Some(0.0), // Minimum valid
         top_k: Some(1),   // Minimum valid
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     assert!(validate_claude_request(&req).is_ok());
@@ -331,7 +358,10 @@ fn test_unicode_content() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     assert!(validate_claude_request(&req).is_ok());
@@ -368,7 +398,10 @@ fn test_large_content_handling() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     assert!(validate_claude_request(&req).is_ok());
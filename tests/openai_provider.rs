@@ -0,0 +1,452 @@
+//! End-to-end tests for the OpenAI Chat Completions provider against a local mock server
+
+use axum::response::{IntoResponse, Response};
+use axum::{Router, body::Bytes, extract::State, http::StatusCode, routing::post};
+use claude_code_proxy::client::{RetryPolicy, Timeouts};
+use claude_code_proxy::config::{
+    DEFAULT_MAX_REQUEST_BYTES, OpenAIConfig, ProviderConfig, ProxyConfig, ServerConfig,
+};
+use claude_code_proxy::handler::{AppState, Backends, handle_messages};
+use claude_code_proxy::metrics::BACKEND_METRICS;
+use claude_code_proxy::routing::{FallbackTarget, ModelRouter};
+use claude_code_proxy::state::{SessionStore, StateStorage};
+use claude_code_proxy::transform::context::ContextStrategy;
+use futures::StreamExt;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const TOOL_CALL_STREAM: &str = concat!(
+    "data: {\"id\":\"chatcmpl-1\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Checking the weather.\"}}]}\n\n",
+    "data: {\"id\":\"chatcmpl-1\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_42\",\"type\":\"function\",\"function\":{\"name\":\"get_weather\",\"arguments\":\"{\\\"loc\"}}]}}]}\n\n",
+    "data: {\"id\":\"chatcmpl-1\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"ation\\\":\\\"Paris\\\"}\"}}]},\"finish_reason\":\"tool_calls\"}]}\n\n",
+    "data: {\"id\":\"chatcmpl-1\",\"choices\":[],\"usage\":{\"prompt_tokens\":31,\"completion_tokens\":9}}\n\n",
+    "data: [DONE]\n\n",
+);
+
+#[derive(Clone)]
+struct MockUpstream {
+    status: StatusCode,
+    body: &'static str,
+    received: Arc<Mutex<Option<Value>>>,
+}
+
+async fn mock_chat_completions(
+    State(mock): State<MockUpstream>,
+    body: Bytes,
+) -> (StatusCode, [(&'static str, &'static str); 1], &'static str) {
+    *mock.received.lock().unwrap() = serde_json::from_slice(&body).ok();
+    (
+        mock.status,
+        [("Content-Type", "text/event-stream")],
+        mock.body,
+    )
+}
+
+/// Answers 503 with `Retry-After: 0` to the first `failures` requests, then streams
+#[derive(Clone)]
+struct FlakyUpstream {
+    failures: usize,
+    calls: Arc<AtomicUsize>,
+}
+
+async fn flaky_chat_completions(State(mock): State<FlakyUpstream>) -> Response {
+    if mock.calls.fetch_add(1, Ordering::SeqCst) < mock.failures {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [("Retry-After", "0")],
+            r#"{"error":{"message":"Overloaded","type":"server_error"}}"#,
+        )
+            .into_response();
+    }
+    (
+        StatusCode::OK,
+        [("Content-Type", "text/event-stream")],
+        TOOL_CALL_STREAM,
+    )
+        .into_response()
+}
+
+/// Streams the first event of a response and then goes silent
+async fn stalled_chat_completions() -> Response {
+    let first = TOOL_CALL_STREAM.split_inclusive("\n\n").next().unwrap();
+    let body = futures::stream::iter([Ok::<_, std::io::Error>(Bytes::from(first))])
+        .chain(futures::stream::pending());
+    (
+        StatusCode::OK,
+        [("Content-Type", "text/event-stream")],
+        axum::body::Body::from_stream(body),
+    )
+        .into_response()
+}
+
+/// Sends error headers and then never finishes the body
+async fn stalled_error_body() -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        [("Content-Type", "application/json")],
+        axum::body::Body::from_stream(futures::stream::pending::<Result<Bytes, std::io::Error>>()),
+    )
+        .into_response()
+}
+
+/// Accepts the request but never answers
+async fn silent_chat_completions() -> Response {
+    futures::future::pending().await
+}
+
+async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+/// Start a mock Chat Completions server; returns its URL and the last request it received
+async fn start_upstream(
+    status: StatusCode,
+    body: &'static str,
+) -> (String, Arc<Mutex<Option<Value>>>) {
+    let received = Arc::new(Mutex::new(None));
+    let upstream = serve(
+        Router::new()
+            .route("/v1/chat/completions", post(mock_chat_completions))
+            .with_state(MockUpstream {
+                status,
+                body,
+                received: received.clone(),
+            }),
+    )
+    .await;
+    (upstream, received)
+}
+
+fn openai_provider(upstream: &str, model: &str) -> ProviderConfig {
+    ProviderConfig::OpenAI(OpenAIConfig {
+        api_key: Some("test-key".to_string()),
+        base_url: format!("{}/v1", upstream),
+        model: model.to_string(),
+        retry: RetryPolicy::none(),
+        timeouts: Timeouts::default(),
+    })
+}
+
+fn proxy_config(provider: ProviderConfig) -> ProxyConfig {
+    ProxyConfig {
+        server: ServerConfig {
+            listen_addr: "127.0.0.1:0".to_string(),
+            workers: 1,
+            max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
+        },
+        provider,
+        default_provider: "openai".to_string(),
+        providers: BTreeMap::new(),
+        routes: ModelRouter::default(),
+        fallback: Vec::new(),
+        context_strategy: ContextStrategy::default(),
+        session_ttl: Duration::from_secs(3600),
+        state_storage: StateStorage::default(),
+        clear_state_on_startup: false,
+        state_sweep_interval: Duration::from_secs(60),
+        state_max_entries: 10000,
+    }
+}
+
+/// Start a proxy for `config`; returns its URL
+async fn serve_proxy(config: ProxyConfig) -> String {
+    let state = Arc::new(AppState::new(
+        Backends::from_config(config).unwrap(),
+        SessionStore::default(),
+    ));
+
+    serve(
+        Router::new()
+            .route("/v1/messages", post(handle_messages))
+            .with_state(state),
+    )
+    .await
+}
+
+/// Start a mock upstream plus a proxy in front of it; returns the proxy URL
+async fn start_proxy(
+    status: StatusCode,
+    body: &'static str,
+) -> (String, Arc<Mutex<Option<Value>>>) {
+    let (upstream, received) = start_upstream(status, body).await;
+    let proxy = serve_proxy(proxy_config(openai_provider(&upstream, "qwen3-coder"))).await;
+    (proxy, received)
+}
+
+/// Primary openai backend answering `status`, with a `local` backend as its fallback
+async fn start_proxy_with_fallback(status: StatusCode) -> (String, Arc<Mutex<Option<Value>>>) {
+    let (primary, _) = start_upstream(
+        status,
+        r#"{"error":{"message":"Upstream refused","type":"requests"}}"#,
+    )
+    .await;
+    let (backup, received) = start_upstream(StatusCode::OK, TOOL_CALL_STREAM).await;
+
+    let mut config = proxy_config(openai_provider(&primary, "gpt-4o"));
+    config
+        .providers
+        .insert("local".to_string(), openai_provider(&backup, "qwen3-coder"));
+    config.fallback = vec![FallbackTarget::new(
+        Some("local".to_string()),
+        "qwen3-coder-fallback",
+    )];
+    config.validate().unwrap();
+
+    (serve_proxy(config).await, received)
+}
+
+fn claude_request(stream: bool) -> String {
+    json!({
+        "model": "claude-sonnet-4",
+        "max_tokens": 512,
+        "stream": stream,
+        "system": "You are a weather bot.",
+        "messages": [{"role": "user", "content": "Weather in Paris?"}],
+        "tools": [{
+            "name": "get_weather",
+            "description": "Get the weather for a location",
+            "input_schema": {
+                "type": "object",
+                "properties": {"location": {"type": "string"}},
+                "required": ["location"]
+            }
+        }]
+    })
+    .to_string()
+}
+
+async fn post_messages(proxy: &str, body: String) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/v1/messages", proxy))
+        .header("Content-Type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_streaming_tool_call_through_openai_provider() {
+    let (proxy, received) = start_proxy(StatusCode::OK, TOOL_CALL_STREAM).await;
+
+    let response = post_messages(&proxy, claude_request(true)).await;
+    assert_eq!(response.status(), 200);
+    let sse = response.text().await.unwrap();
+
+    // Upstream received a Chat Completions request
+    let upstream_req = received.lock().unwrap().clone().unwrap();
+    assert_eq!(upstream_req["model"], "qwen3-coder");
+    assert_eq!(upstream_req["messages"][0]["role"], "system");
+    assert_eq!(upstream_req["tools"][0]["function"]["name"], "get_weather");
+    assert_eq!(upstream_req["stream"], true);
+
+    // Client received Claude SSE events
+    assert!(sse.starts_with("event: message_start"));
+    assert!(sse.contains("\"type\":\"tool_use\""));
+    assert!(sse.contains("\"id\":\"call_42\""));
+    assert!(sse.contains("input_json_delta"));
+    assert!(sse.contains("\"stop_reason\":\"tool_use\""));
+    assert!(
+        sse.trim_end()
+            .ends_with("data: {\"type\":\"message_stop\"}")
+    );
+}
+
+#[tokio::test]
+async fn test_non_streaming_tool_call_through_openai_provider() {
+    let (proxy, _) = start_proxy(StatusCode::OK, TOOL_CALL_STREAM).await;
+
+    let response = post_messages(&proxy, claude_request(false)).await;
+    assert_eq!(response.status(), 200);
+    let message: Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+
+    assert_eq!(message["type"], "message");
+    assert_eq!(message["content"][0]["text"], "Checking the weather.");
+    assert_eq!(message["content"][1]["type"], "tool_use");
+    assert_eq!(message["content"][1]["input"]["location"], "Paris");
+    assert_eq!(message["stop_reason"], "tool_use");
+    assert_eq!(message["usage"]["input_tokens"], 31);
+    assert_eq!(message["usage"]["output_tokens"], 9);
+}
+
+#[tokio::test]
+async fn test_upstream_rate_limit_is_passed_through() {
+    let (proxy, _) = start_proxy(
+        StatusCode::TOO_MANY_REQUESTS,
+        r#"{"error":{"message":"Rate limit reached","type":"requests"}}"#,
+    )
+    .await;
+
+    let response = post_messages(&proxy, claude_request(true)).await;
+    assert_eq!(response.status(), 429);
+    let body: Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(body["error"]["type"], "rate_limit_error");
+    assert!(
+        body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("Rate limit reached")
+    );
+}
+
+#[tokio::test]
+async fn test_rate_limited_backend_falls_back_before_streaming() {
+    let (proxy, received) = start_proxy_with_fallback(StatusCode::TOO_MANY_REQUESTS).await;
+
+    let response = post_messages(&proxy, claude_request(true)).await;
+    assert_eq!(response.status(), 200);
+    let sse = response.text().await.unwrap();
+    assert!(sse.starts_with("event: message_start"));
+    assert!(sse.contains("\"id\":\"call_42\""));
+
+    // The fallback backend got the request with its own model
+    let upstream_req = received.lock().unwrap().clone().unwrap();
+    assert_eq!(upstream_req["model"], "qwen3-coder-fallback");
+
+    let backends = BACKEND_METRICS.snapshot();
+    let served = backends
+        .iter()
+        .find(|(name, _)| name == "local/qwen3-coder-fallback")
+        .unwrap();
+    assert!(served.1.served_as_fallback >= 1);
+}
+
+#[tokio::test]
+async fn test_client_errors_do_not_fall_back() {
+    let (proxy, received) = start_proxy_with_fallback(StatusCode::BAD_REQUEST).await;
+
+    let response = post_messages(&proxy, claude_request(true)).await;
+    assert_eq!(response.status(), 400);
+    assert!(received.lock().unwrap().is_none());
+}
+
+/// Proxy whose only backend fails `failures` times before answering; returns its URL and call counter
+async fn start_proxy_with_flaky_upstream(
+    failures: usize,
+    max_attempts: u32,
+) -> (String, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let upstream = serve(
+        Router::new()
+            .route("/v1/chat/completions", post(flaky_chat_completions))
+            .with_state(FlakyUpstream {
+                failures,
+                calls: calls.clone(),
+            }),
+    )
+    .await;
+
+    let mut provider = openai_provider(&upstream, "qwen3-coder");
+    if let ProviderConfig::OpenAI(openai) = &mut provider {
+        openai.retry = RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(10),
+        };
+    }
+    (serve_proxy(proxy_config(provider)).await, calls)
+}
+
+#[tokio::test]
+async fn test_transient_upstream_errors_are_retried() {
+    let (proxy, calls) = start_proxy_with_flaky_upstream(2, 3).await;
+
+    let response = post_messages(&proxy, claude_request(true)).await;
+    assert_eq!(response.status(), 200);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains("\"id\":\"call_42\"")
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_retries_stop_at_max_attempts() {
+    let (proxy, calls) = start_proxy_with_flaky_upstream(5, 2).await;
+
+    let response = post_messages(&proxy, claude_request(true)).await;
+    // The last 503 is reported as Anthropic's overloaded status
+    assert_eq!(response.status(), 529);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+/// Proxy in front of `upstream` with short first-byte and idle timeouts
+async fn start_proxy_with_timeouts(upstream: Router) -> String {
+    let upstream = serve(upstream).await;
+    let mut provider = openai_provider(&upstream, "qwen3-coder");
+    if let ProviderConfig::OpenAI(openai) = &mut provider {
+        openai.timeouts = Timeouts {
+            connect: Duration::from_secs(1),
+            first_byte: Duration::from_millis(300),
+            idle: Duration::from_millis(300),
+        };
+    }
+    serve_proxy(proxy_config(provider)).await
+}
+
+#[tokio::test]
+async fn test_idle_stream_ends_with_error_event() {
+    let proxy = start_proxy_with_timeouts(
+        Router::new().route("/v1/chat/completions", post(stalled_chat_completions)),
+    )
+    .await;
+
+    let response = post_messages(&proxy, claude_request(true)).await;
+    assert_eq!(response.status(), 200);
+    let sse = response.text().await.unwrap();
+
+    assert!(sse.contains("Checking the weather."));
+    assert!(!sse.contains("message_stop"));
+    assert!(sse.contains("event: error\ndata: "));
+    assert!(sse.contains("OpenAI sent no data for 300ms"));
+}
+
+#[tokio::test]
+async fn test_silent_upstream_times_out_before_streaming() {
+    let proxy = start_proxy_with_timeouts(
+        Router::new().route("/v1/chat/completions", post(silent_chat_completions)),
+    )
+    .await;
+
+    let response = post_messages(&proxy, claude_request(true)).await;
+    assert_eq!(response.status(), 504);
+    let body: Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert!(
+        body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("OpenAI sent no response within 300ms")
+    );
+}
+
+#[tokio::test]
+async fn test_stalled_error_body_times_out() {
+    let proxy = start_proxy_with_timeouts(
+        Router::new().route("/v1/chat/completions", post(stalled_error_body)),
+    )
+    .await;
+
+    let response = tokio::time::timeout(
+        Duration::from_secs(5),
+        post_messages(&proxy, claude_request(true)),
+    )
+    .await
+    .expect("proxy should not wait on a stalled body");
+    assert_eq!(response.status(), 504);
+    let body: Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert!(
+        body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("OpenAI sent no complete response body within 300ms")
+    );
+}
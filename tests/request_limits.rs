@@ -0,0 +1,121 @@
+//! Request body limits through the proxy router
+
+use claude_code_proxy::client::{RetryPolicy, Timeouts};
+use claude_code_proxy::config::{
+    DEFAULT_MAX_REQUEST_BYTES, GeminiConfig, ProviderConfig, ProxyConfig, ServerConfig,
+};
+use claude_code_proxy::handler::{AppState, Backends, router};
+use claude_code_proxy::routing::ModelRouter;
+use claude_code_proxy::state::{SessionStore, StateStorage};
+use claude_code_proxy::transform::context::ContextStrategy;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Proxy for a Gemini backend that is never reached; returns its URL
+async fn start_proxy(max_request_bytes: usize) -> String {
+    let config = ProxyConfig {
+        server: ServerConfig {
+            listen_addr: "127.0.0.1:0".to_string(),
+            workers: 1,
+            max_request_bytes,
+        },
+        provider: ProviderConfig::Gemini(GeminiConfig {
+            api_key: "test-key".to_string(),
+            endpoint: "127.0.0.1:1".to_string(),
+            default_model: Some("gemini-2.5-pro".to_string()),
+            auto_todo_prompt: false,
+            prompt_cache: false,
+            retry: RetryPolicy::none(),
+            timeouts: Timeouts::default(),
+        }),
+        default_provider: "gemini".to_string(),
+        providers: BTreeMap::new(),
+        routes: ModelRouter::default(),
+        fallback: Vec::new(),
+        context_strategy: ContextStrategy::default(),
+        session_ttl: Duration::from_secs(3600),
+        state_storage: StateStorage::default(),
+        clear_state_on_startup: false,
+        state_sweep_interval: Duration::from_secs(60),
+        state_max_entries: 10000,
+    };
+    let state = Arc::new(AppState::new(
+        Backends::from_config(config).unwrap(),
+        SessionStore::default(),
+    ));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = router(state, max_request_bytes);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+/// A user message carrying a base64 PNG of `data_len` bytes
+fn image_request(data_len: usize) -> String {
+    json!({
+        "model": "claude-sonnet-4",
+        "max_tokens": 512,
+        "stream": true,
+        "messages": [{
+            "role": "user",
+            "content": [
+                {"type": "text", "text": "What is in this screenshot?"},
+                {
+                    "type": "image",
+                    "source": {"type": "base64", "media_type": "image/png", "data": "A".repeat(data_len)}
+                }
+            ]
+        }]
+    })
+    .to_string()
+}
+
+async fn post_messages(proxy: &str, body: String) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .post(format!("{}/v1/messages", proxy))
+        .header("Content-Type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    let status = response.status().as_u16();
+    let text = response.text().await.unwrap();
+    (status, serde_json::from_str(&text).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn test_oversized_inline_data_reaches_inline_data_check() {
+    let proxy = start_proxy(DEFAULT_MAX_REQUEST_BYTES).await;
+
+    // Well past axum's 2 MiB default, but within the proxy's body limit
+    let (status, body) = post_messages(&proxy, image_request(21 * 1024 * 1024)).await;
+    assert_eq!(status, 413);
+    assert_eq!(body["error"]["type"], "request_too_large");
+    assert!(
+        body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("inline data limit")
+    );
+}
+
+#[tokio::test]
+async fn test_body_over_limit_is_request_too_large() {
+    let proxy = start_proxy(1024 * 1024).await;
+
+    let (status, body) = post_messages(&proxy, image_request(2 * 1024 * 1024)).await;
+    assert_eq!(status, 413);
+    assert_eq!(body["error"]["type"], "request_too_large");
+}
+
+#[tokio::test]
+async fn test_malformed_body_is_invalid_request() {
+    let proxy = start_proxy(DEFAULT_MAX_REQUEST_BYTES).await;
+
+    let (status, body) = post_messages(&proxy, "{\"model\": ".to_string()).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+}
@@ -98,7 +98,10 @@ fn test_validation_errors() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     assert!(validate_claude_request(&req).is_err());
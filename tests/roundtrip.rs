@@ -4,7 +4,7 @@ use claude_code_proxy::{
         gemini::{Candidate, GeminiContent, GeminiPart, GeminiStreamChunk},
     },
     state::ConversationState,
-    streaming::SSEEventGenerator,
+    streaming::{MessageAggregator, SSEEventGenerator},
     transform::{transform_request_with_state, validate_claude_request},
 };
 use serde_json::json;
@@ -29,12 +29,16 @@ fn test_todowrite_roundtrip() {
                 required: Some(vec!["todos".to_string()]),
                 ..Default::default()
             },
+            cache_control: None,
         }]),
+        tool_choice: None,
         system: None,
         temperature: None,
         stop_sequences: None,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         stream: true,
     };
 
@@ -164,7 +168,7 @@ fn test_todowrite_roundtrip() {
                 role: "user".to_string(),
                 content: ContentType::Blocks(vec![ContentBlock::ToolResult {
                     tool_use_id: extracted_id.clone(),
-                    content: "Todos have been modified successfully".to_string(),
+                    content: "Todos have been modified successfully".into(),
                     is_error: Some(false),
                 }]),
             },
@@ -178,12 +182,16 @@ fn test_todowrite_roundtrip() {
                 required: Some(vec!["todos".to_string()]),
                 ..Default::default()
             },
+            cache_control: None,
         }]),
+        tool_choice: None,
         system: None,
         temperature: None,
         stop_sequences: None,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         stream: true,
     };
 
@@ -331,6 +339,7 @@ fn test_stop_reason_context_awareness() {
         }],
         usage_metadata: None,
         prompt_feedback: None,
+        error: None,
     };
 
     let events = sse_gen.generate_events(function_call_chunk);
@@ -357,6 +366,7 @@ fn test_stop_reason_context_awareness() {
         }],
         usage_metadata: None,
         prompt_feedback: None,
+        error: None,
     };
 
     let mut sse_gen2 = SSEEventGenerator::new("gemini-3-pro-preview".to_string());
@@ -366,3 +376,188 @@ fn test_stop_reason_context_awareness() {
         .any(|e| e.contains("\"stop_reason\":\"max_tokens\""));
     assert!(has_max_tokens, "MAX_TOKENS should map to max_tokens");
 }
+
+/// Run one simulated Gemini response through the SSE pipeline and return the
+/// assistant message Claude Code would store in its history
+fn gemini_turn(state: &ConversationState, response: serde_json::Value) -> ClaudeMessage {
+    let chunk: GeminiStreamChunk = serde_json::from_value(response).unwrap();
+    let mut sse_gen =
+        SSEEventGenerator::with_state("gemini-3-pro-preview".to_string(), state.clone());
+    let mut aggregator = MessageAggregator::new();
+    for event in sse_gen.generate_events(chunk) {
+        aggregator.push_event(&event);
+    }
+    let message = aggregator.finish().unwrap();
+
+    ClaudeMessage {
+        role: "assistant".to_string(),
+        content: ContentType::Blocks(serde_json::from_value(message["content"].clone()).unwrap()),
+    }
+}
+
+/// Tool results Claude Code would send back for every tool_use in `assistant`
+fn tool_results(assistant: &ClaudeMessage) -> ClaudeMessage {
+    let ContentType::Blocks(blocks) = &assistant.content else {
+        panic!("Expected blocks");
+    };
+    let results = blocks
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::ToolUse { id, name, .. } => Some(ContentBlock::ToolResult {
+                tool_use_id: id.clone(),
+                content: format!("{} output", name).into(),
+                is_error: None,
+            }),
+            _ => None,
+        })
+        .collect();
+    ClaudeMessage {
+        role: "user".to_string(),
+        content: ContentType::Blocks(results),
+    }
+}
+
+fn request(messages: Vec<ClaudeMessage>) -> ClaudeRequest {
+    ClaudeRequest {
+        model: "claude-sonnet-4-5-20250929".to_string(),
+        max_tokens: Some(4096),
+        messages,
+        tools: None,
+        tool_choice: None,
+        system: None,
+        temperature: None,
+        stop_sequences: None,
+        top_p: None,
+        top_k: None,
+        thinking: None,
+        metadata: None,
+        stream: true,
+    }
+}
+
+/// Test a multi-turn tool loop: thought signatures from each Gemini response
+/// must be replayed on the matching functionCall in every later request
+#[test]
+fn test_multi_turn_tool_loop_replays_thought_signatures() {
+    let state = ConversationState::new();
+    let mut history = vec![ClaudeMessage {
+        role: "user".to_string(),
+        content: ContentType::Text("Fix the failing test".to_string()),
+    }];
+
+    // Request 1 -> Gemini reads a file (signed call)
+    let gemini_req =
+        transform_request_with_state(request(history.clone()), Some(&state), false).unwrap();
+    assert_eq!(gemini_req.contents.len(), 1);
+    let assistant = gemini_turn(
+        &state,
+        json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{
+                    "functionCall": {"name": "Read", "args": {"file_path": "src/lib.rs"}},
+                    "thoughtSignature": "sig-turn-1"
+                }]},
+                "finishReason": "STOP"
+            }]
+        }),
+    );
+    history.push(assistant.clone());
+    history.push(tool_results(&assistant));
+
+    // Request 2 -> Gemini runs two tools in parallel (only the first is signed)
+    let gemini_req =
+        transform_request_with_state(request(history.clone()), Some(&state), false).unwrap();
+    assert_eq!(gemini_req.contents.len(), 3);
+    assert_eq!(
+        gemini_req.contents[1].parts,
+        vec![GeminiPart::FunctionCallWithThought {
+            function_call: claude_code_proxy::models::gemini::FunctionCall {
+                name: "Read".to_string(),
+                args: json!({"file_path": "src/lib.rs"}),
+            },
+            thought_signature: "sig-turn-1".to_string(),
+        }]
+    );
+    let assistant = gemini_turn(
+        &state,
+        json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [
+                    {
+                        "functionCall": {"name": "Edit", "args": {"file_path": "src/lib.rs"}},
+                        "thoughtSignature": "sig-turn-2"
+                    },
+                    {"functionCall": {"name": "Bash", "args": {"command": "cargo test"}}}
+                ]},
+                "finishReason": "STOP"
+            }]
+        }),
+    );
+    history.push(assistant.clone());
+    history.push(tool_results(&assistant));
+
+    // Request 3 -> every call in the history carries exactly its original signature
+    let gemini_req =
+        transform_request_with_state(request(history.clone()), Some(&state), false).unwrap();
+    let roles: Vec<_> = gemini_req
+        .contents
+        .iter()
+        .map(|c| c.role.as_deref().unwrap())
+        .collect();
+    assert_eq!(roles, vec!["user", "model", "user", "model", "user"]);
+
+    let signatures: Vec<_> = gemini_req
+        .contents
+        .iter()
+        .flat_map(|c| &c.parts)
+        .filter_map(|part| match part {
+            GeminiPart::FunctionCallWithThought {
+                function_call,
+                thought_signature,
+            } => Some((
+                function_call.name.as_str(),
+                Some(thought_signature.as_str()),
+            )),
+            GeminiPart::FunctionCall { function_call } => Some((function_call.name.as_str(), None)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        signatures,
+        vec![
+            ("Read", Some("sig-turn-1")),
+            ("Edit", Some("sig-turn-2")),
+            ("Bash", None)
+        ]
+    );
+
+    // Each functionResponse answers the calls of the preceding model turn
+    let responses: Vec<_> = gemini_req.contents[4]
+        .parts
+        .iter()
+        .filter_map(|part| match part {
+            GeminiPart::FunctionResponse { function_response } => {
+                Some(function_response.name.as_str())
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(responses, vec!["Edit", "Bash"]);
+
+    // Final answer ends the loop
+    let assistant = gemini_turn(
+        &state,
+        json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"text": "The test passes now."}]},
+                "finishReason": "STOP"
+            }]
+        }),
+    );
+    let ContentType::Blocks(blocks) = &assistant.content else {
+        panic!("Expected blocks");
+    };
+    assert!(
+        matches!(&blocks[0], ContentBlock::Text { text, .. } if text == "The test passes now.")
+    );
+}
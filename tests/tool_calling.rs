@@ -30,6 +30,8 @@ fn test_transform_request_with_tools() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: Some(vec![ClaudeTool {
             name: "get_weather".to_string(),
             description: "Get current weather for a location".to_string(),
@@ -39,7 +41,9 @@ fn test_transform_request_with_tools() {
                 required: Some(vec!["location".to_string()]),
                 ..Default::default()
             },
+            cache_control: None,
         }]),
+        tool_choice: None,
     };
 
     let gemini_req = transform_request(claude_req).unwrap();
@@ -71,7 +75,15 @@ fn test_function_call_to_tool_use() {
 
     // Verify ID format
     assert!(tool_use_id.starts_with("toolu_"));
-    assert_eq!(tool_use_id.len(), 38); // toolu_ + 32 char UUID
+    assert!(
+        tool_use_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    );
+    assert_eq!(
+        decode_tool_use_id(&tool_use_id).unwrap().function_name,
+        "get_weather"
+    );
 
     // Verify content block
     match content_block {
@@ -100,14 +112,15 @@ fn test_tool_result_with_state() {
     // Create tool result
     let tool_result = ContentBlock::ToolResult {
         tool_use_id: "toolu_abc123".to_string(),
-        content: "Sunny, 72°F".to_string(),
+        content: "Sunny, 72°F".into(),
         is_error: None,
     };
 
     // Transform with state
-    let gemini_part = transform_tool_result(&tool_result, "get_weather".to_string()).unwrap();
+    let mut parts = transform_tool_result(&tool_result, "get_weather".to_string()).unwrap();
+    assert_eq!(parts.len(), 1);
 
-    match gemini_part {
+    match parts.remove(0) {
         GeminiPart::FunctionResponse { function_response } => {
             assert_eq!(function_response.name, "get_weather");
             assert_eq!(function_response.response["result"], "Sunny, 72°F");
@@ -145,6 +158,8 @@ fn test_multi_turn_tool_conversation() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: Some(vec![ClaudeTool {
             name: "web_search".to_string(),
             description: "Search the web".to_string(),
@@ -154,7 +169,9 @@ fn test_multi_turn_tool_conversation() {
                 required: Some(vec!["query".to_string()]),
                 ..Default::default()
             },
+            cache_control: None,
         }]),
+        tool_choice: None,
     };
 
     let gemini_req1 = transform_request_with_state(turn1_req, Some(&state), false).unwrap();
@@ -188,7 +205,7 @@ fn test_multi_turn_tool_conversation() {
                 role: "user".to_string(),
                 content: ContentType::Blocks(vec![ContentBlock::ToolResult {
                     tool_use_id: "toolu_search_123".to_string(),
-                    content: "Found 10 great Rust tutorials".to_string(),
+                    content: "Found 10 great Rust tutorials".into(),
                     is_error: None,
                 }]),
             },
@@ -200,7 +217,10 @@ fn test_multi_turn_tool_conversation() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None, // Tools not needed for follow-up
+        tool_choice: None,
     };
 
     let gemini_req2 = transform_request_with_state(turn2_req, Some(&state), false).unwrap();
@@ -291,6 +311,7 @@ fn test_complex_tool_schema() {
             required: Some(vec!["coordinates".to_string()]),
             ..Default::default()
         },
+        cache_control: None,
     };
 
     let gemini_tools = transform_tools(vec![claude_tool]).unwrap();
@@ -374,6 +395,7 @@ fn test_todo_write_tool_transformation() {
             required: Some(vec!["todos".to_string()]),
             ..Default::default()
         },
+        cache_control: None,
     };
 
     let gemini_tools = transform_tools(vec![todo_tool]).unwrap();
@@ -433,12 +455,12 @@ fn test_tool_result_state_lookup() {
             content: ContentType::Blocks(vec![
                 ContentBlock::ToolResult {
                     tool_use_id: "toolu_001".to_string(),
-                    content: "Result A".to_string(),
+                    content: "Result A".into(),
                     is_error: None,
                 },
                 ContentBlock::ToolResult {
                     tool_use_id: "toolu_002".to_string(),
-                    content: "Result B".to_string(),
+                    content: "Result B".into(),
                     is_error: None,
                 },
             ]),
@@ -450,7 +472,10 @@ fn test_tool_result_state_lookup() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: None,
+        tool_choice: None,
     };
 
     let gemini_req = transform_request_with_state(claude_req, Some(&state), false).unwrap();
@@ -486,13 +511,14 @@ fn test_tool_result_with_error() {
 
     let tool_result = ContentBlock::ToolResult {
         tool_use_id: "toolu_err".to_string(),
-        content: "API rate limit exceeded".to_string(),
+        content: "API rate limit exceeded".into(),
         is_error: Some(true),
     };
 
-    let gemini_part = transform_tool_result(&tool_result, "failing_tool".to_string()).unwrap();
+    let mut parts = transform_tool_result(&tool_result, "failing_tool".to_string()).unwrap();
+    assert_eq!(parts.len(), 1);
 
-    match gemini_part {
+    match parts.remove(0) {
         GeminiPart::FunctionResponse { function_response } => {
             assert_eq!(function_response.name, "failing_tool");
             assert_eq!(function_response.response["error"], true);
@@ -532,6 +558,8 @@ fn test_serialize_complete_request_with_tools() {
         stream: true,
         top_p: None,
         top_k: None,
+        thinking: None,
+        metadata: None,
         tools: Some(vec![ClaudeTool {
             name: "echo".to_string(),
             description: "Echo text back".to_string(),
@@ -541,7 +569,9 @@ fn test_serialize_complete_request_with_tools() {
                 required: Some(vec!["text".to_string()]),
                 ..Default::default()
             },
+            cache_control: None,
         }]),
+        tool_choice: None,
     };
 
     let gemini_req = transform_request(claude_req).unwrap();